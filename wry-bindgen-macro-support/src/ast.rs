@@ -52,6 +52,10 @@ pub struct Program {
     pub structs: Vec<ExportStruct>,
     /// Exported methods from impl blocks
     pub exports: Vec<ExportMethod>,
+    /// Exported free functions
+    pub export_functions: Vec<ExportFunction>,
+    /// Functions to run when the app starts
+    pub start_functions: Vec<StartFunction>,
 }
 
 /// A string enum - an enum where each variant has a string discriminant
@@ -264,6 +268,8 @@ pub struct ExportMethod {
     pub ret: Option<Type>,
     /// Whether to wrap in try-catch
     pub catch: bool,
+    /// Whether the method is async, which returns a `Promise` to JS
+    pub is_async: bool,
     /// User-provided attributes (like #[cfg(...)] and #[doc = "..."])
     pub rust_attrs: Vec<syn::Attribute>,
     /// Method visibility
@@ -281,6 +287,45 @@ impl ExportMethod {
     }
 }
 
+/// An exported free function
+#[derive(Debug)]
+pub struct ExportFunction {
+    /// Rust function name
+    pub rust_name: Ident,
+    /// JavaScript function name (may differ from rust_name)
+    pub js_name: String,
+    /// Function arguments
+    pub arguments: Vec<FunctionArg>,
+    /// Return type
+    pub ret: Option<Type>,
+    /// User-provided attributes (like #[cfg(...)] and #[doc = "..."])
+    pub rust_attrs: Vec<syn::Attribute>,
+    /// Function visibility
+    pub vis: syn::Visibility,
+    /// The original function body
+    pub body: syn::Block,
+}
+
+impl ExportFunction {
+    /// Get the function rust attributes
+    pub fn fn_rust_attrs(&self) -> proc_macro2::TokenStream {
+        let rust_attrs = &self.rust_attrs;
+        let span = self.rust_name.span();
+        quote_spanned! {span=> #(#rust_attrs)* #[allow(non_snake_case)] }
+    }
+}
+
+/// A function marked with `#[wasm_bindgen(start)]`
+#[derive(Debug)]
+pub struct StartFunction {
+    /// Rust function name
+    pub rust_name: Ident,
+    /// Whether the function is async
+    pub is_async: bool,
+    /// The function, without its wasm_bindgen attributes
+    pub function: syn::ItemFn,
+}
+
 /// Kind of exported method
 #[derive(Debug, Clone)]
 pub enum ExportMethodKind {
@@ -334,10 +379,18 @@ pub fn parse_item(program: &mut Program, item: syn::Item) -> syn::Result<()> {
             let exports = parse_impl_block(i, &program.attrs)?;
             program.exports.extend(exports);
         }
+        syn::Item::Fn(f) if program.attrs.start.is_some() => {
+            let start = parse_start_fn(f)?;
+            program.start_functions.push(start);
+        }
+        syn::Item::Fn(f) => {
+            let export = parse_export_fn(f, &program.attrs)?;
+            program.export_functions.push(export);
+        }
         _ => {
             return Err(syn::Error::new_spanned(
                 item,
-                "wasm_bindgen attribute must be on extern \"C\" block, enum, struct, or impl block",
+                "wasm_bindgen attribute must be on extern \"C\" block, enum, struct, impl block, or function",
             ));
         }
     }
@@ -551,6 +604,10 @@ fn parse_impl_method(
         ExportMethodKind::StaticMethod
     };
 
+    if let Some(asyncness) = &method.sig.asyncness {
+        validate_async_method(asyncness, &kind, &arguments)?;
+    }
+
     // Parse return type
     let ret = match &method.sig.output {
         syn::ReturnType::Default => None,
@@ -573,12 +630,146 @@ fn parse_impl_method(
         arguments,
         ret,
         catch: method_attrs.catch.is_some(),
+        is_async: method.sig.asyncness.is_some(),
         rust_attrs,
         vis: method.vis.clone(),
         body: method.block.clone(),
     })
 }
 
+/// Check that an async method can run after the call from JS returns. Values
+/// JS passes by reference are only borrowed for the call, so references are
+/// rejected unless they are `&str` or slices, which are decoded into owned
+/// values. That includes `&mut` references to exported structs.
+fn validate_async_method(
+    asyncness: &syn::token::Async,
+    kind: &ExportMethodKind,
+    arguments: &[FunctionArg],
+) -> syn::Result<()> {
+    let unsupported = match kind {
+        ExportMethodKind::Constructor => Some("async constructors are not supported"),
+        ExportMethodKind::Getter { .. } => Some("async getters are not supported"),
+        ExportMethodKind::Setter { .. } => Some("async setters are not supported"),
+        _ => None,
+    };
+    if let Some(message) = unsupported {
+        return Err(syn::Error::new_spanned(asyncness, message));
+    }
+    for arg in arguments {
+        if let Type::Reference(reference) = &arg.ty {
+            let owned = match &*reference.elem {
+                Type::Path(path) => path.path.is_ident("str"),
+                Type::Slice(_) => true,
+                _ => false,
+            };
+            if !owned {
+                return Err(syn::Error::new_spanned(
+                    &arg.ty,
+                    "async exported methods cannot take arguments by reference",
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parse a function marked with `#[wasm_bindgen(start)]`
+fn parse_start_fn(mut f: syn::ItemFn) -> syn::Result<StartFunction> {
+    if !f.sig.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &f.sig.generics,
+            "the start function cannot be generic",
+        ));
+    }
+    if !f.sig.inputs.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &f.sig.inputs,
+            "the start function cannot take arguments",
+        ));
+    }
+    f.attrs.retain(|attr| !attr.path().is_ident("wasm_bindgen"));
+
+    Ok(StartFunction {
+        rust_name: f.sig.ident.clone(),
+        is_async: f.sig.asyncness.is_some(),
+        function: f,
+    })
+}
+
+/// Parse a free function for export
+fn parse_export_fn(f: syn::ItemFn, attrs: &BindgenAttrs) -> syn::Result<ExportFunction> {
+    if !f.sig.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &f.sig.generics,
+            "generic exported functions are not supported",
+        ));
+    }
+    if f.sig.asyncness.is_some() {
+        return Err(syn::Error::new_spanned(
+            f.sig.asyncness,
+            "async exported functions are not supported",
+        ));
+    }
+    if f.sig.unsafety.is_some() {
+        return Err(syn::Error::new_spanned(
+            f.sig.unsafety,
+            "unsafe exported functions are not supported",
+        ));
+    }
+
+    let rust_name = f.sig.ident.clone();
+    let js_name = attrs
+        .js_name()
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| rust_name.to_string());
+
+    let mut arguments = Vec::new();
+    for arg in &f.sig.inputs {
+        let FnArg::Typed(pat_type) = arg else {
+            return Err(syn::Error::new_spanned(
+                arg,
+                "self is only supported in impl blocks",
+            ));
+        };
+        let name = match &*pat_type.pat {
+            Pat::Ident(ident) => ident.ident.clone(),
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    pat_type,
+                    "complex patterns not supported",
+                ));
+            }
+        };
+        arguments.push(FunctionArg {
+            name,
+            ty: (*pat_type.ty).clone(),
+        });
+    }
+
+    let ret = match &f.sig.output {
+        syn::ReturnType::Default => None,
+        syn::ReturnType::Type(_, ty) => Some((**ty).clone()),
+    };
+
+    // Extract non-wasm_bindgen attributes
+    let rust_attrs: Vec<syn::Attribute> = f
+        .attrs
+        .iter()
+        .filter(|attr| !attr.path().is_ident("wasm_bindgen"))
+        .cloned()
+        .collect();
+
+    Ok(ExportFunction {
+        rust_name,
+        js_name,
+        arguments,
+        ret,
+        rust_attrs,
+        vis: f.vis,
+        body: *f.block,
+    })
+}
+
 /// Parse an extern "C" block
 fn parse_foreign_mod(program: &mut Program, foreign: syn::ItemForeignMod) -> syn::Result<()> {
     for item in foreign.items {
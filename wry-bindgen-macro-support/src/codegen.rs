@@ -9,8 +9,9 @@ use std::{
 };
 
 use crate::ast::{
-    ExportMethod, ExportMethodKind, ExportStruct, ImportFunction, ImportFunctionKind, ImportStatic,
-    ImportType, Program, SelfType, StringEnum, StructField,
+    ExportFunction, ExportMethod, ExportMethodKind, ExportStruct, FunctionArg, ImportFunction,
+    ImportFunctionKind, ImportStatic, ImportType, Program, SelfType, StartFunction, StringEnum,
+    StructField,
 };
 use proc_macro2::TokenStream;
 use quote::{ToTokens, format_ident, quote, quote_spanned};
@@ -130,6 +131,16 @@ pub fn generate(program: &Program) -> syn::Result<TokenStream> {
         tokens.extend(generate_export_method(export_method, krate)?);
     }
 
+    // Generate exported free function definitions
+    for export_function in &program.export_functions {
+        tokens.extend(generate_export_function(export_function, krate)?);
+    }
+
+    // Generate start function registrations
+    for start_function in &program.start_functions {
+        tokens.extend(generate_start_function(start_function, krate));
+    }
+
     Ok(tokens)
 }
 
@@ -409,6 +420,7 @@ fn generate_function(
                     impl #class_ident {
                         #allows
                         #rust_attrs
+                        #[track_caller]
                         #vis fn #rust_name(#fn_params) -> #ret_type {
                             #func_body
                         }
@@ -418,6 +430,7 @@ fn generate_function(
             Ok(quote_spanned! {span=>
                 #allows
                 #rust_attrs
+                #[track_caller]
                 #vis fn #rust_name(#fn_params) -> #ret_type {
                     #func_body
                 }
@@ -443,6 +456,7 @@ fn generate_function(
                 impl #receiver_type {
                     #allows
                     #rust_attrs
+                    #[track_caller]
                     #vis fn #rust_name(#method_args) -> #ret_type {
                         #func_body
                     }
@@ -456,6 +470,7 @@ fn generate_function(
                 impl #class_ident {
                     #allows
                     #rust_attrs
+                    #[track_caller]
                     #vis fn #rust_name(#fn_params) -> #ret_type {
                         #func_body
                     }
@@ -468,6 +483,7 @@ fn generate_function(
                 impl #class_ident {
                     #allows
                     #rust_attrs
+                    #[track_caller]
                     #vis fn #rust_name(#fn_params) -> #ret_type {
                         #func_body
                     }
@@ -1119,40 +1135,48 @@ fn generate_export_struct(s: &ExportStruct, krate: &TokenStream) -> syn::Result<
         }
     };
 
-    // Generate EncodeTypeDef - exported structs use HeapRef encoding
+    // Generate EncodeTypeDef - exported structs pass the handle of the object in the store
     let encode_type_def_impl = quote_spanned! {span=>
         impl #krate::EncodeTypeDef for #rust_name {
             fn encode_type_def(buf: &mut #krate::alloc::vec::Vec<u8>) {
-                buf.push(#krate::encode::TypeTag::HeapRef as u8);
+                #krate::object_store::encode_object_type_def(buf, #js_name, false);
             }
         }
     };
 
-    // Generate BinaryEncode - encode struct by converting to JsValue
+    // Generate BinaryEncode - by value moves the struct to the JS wrapper, by
+    // reference lends it to the JS call
     let binary_encode_impl = quote_spanned! {span=>
         impl #krate::BinaryEncode for #rust_name {
             fn encode(self, encoder: &mut #krate::EncodedData) {
-                // Convert to JsValue (which inserts into object store and creates wrapper)
-                let js_value = #krate::JsValue::from(self);
-                // Encode the JsValue
-                js_value.encode(encoder);
+                #krate::object_store::encode_owned_object(self, encoder);
+            }
+        }
+
+        impl #krate::BinaryEncode<#krate::BorrowedForCall> for &#rust_name {
+            fn encode(self, encoder: &mut #krate::EncodedData) {
+                #krate::object_store::encode_lent_object(self, encoder);
             }
         }
     };
 
-    // Generate BinaryDecode - decode JsValue, extract handle, remove from object store
+    // Generate BinaryDecode - take the struct JS passed by value out of the object store
     let binary_decode_impl = quote_spanned! {span=>
         impl #krate::BinaryDecode for #rust_name {
             fn decode(decoder: &mut #krate::DecodedData) -> ::core::result::Result<Self, #krate::DecodeError> {
-                // Decode the JsValue
-                let js = #krate::JsValue::decode(decoder)?;
-                // Extract handle from JS wrapper
-                let handle = #krate::extract_rust_handle(&js)
-                    .ok_or_else(|| #krate::DecodeError::Custom(
-                        #krate::alloc::string::String::from("expected Rust object wrapper")
-                    ))?;
-                // Remove from object store and return owned value
-                Ok(#krate::object_store::remove_object::<#rust_name>(handle))
+                #krate::object_store::decode_owned_object(decoder)
+            }
+        }
+
+        impl #krate::convert::RefFromBinaryDecode for #rust_name {
+            type Anchor = #krate::object_store::ObjectAnchor<#rust_name>;
+
+            fn ref_decode(decoder: &mut #krate::DecodedData) -> ::core::result::Result<Self::Anchor, #krate::DecodeError> {
+                #krate::object_store::decode_object_ref(decoder)
+            }
+
+            fn encode_ref_type_def(buf: &mut #krate::alloc::vec::Vec<u8>) {
+                #krate::object_store::encode_object_type_def(buf, #js_name, true);
             }
         }
     };
@@ -1269,7 +1293,11 @@ fn generate_field_accessor(
                 #js_field_name,
                 #getter_name,
                 0,
-                #krate::JsClassMemberKind::Getter
+                #krate::JsClassMemberKind::Getter,
+                |buf| {
+                    buf.push(0);
+                    <#field_ty as #krate::EncodeTypeDef>::encode_type_def(buf);
+                },
             );
 
             #krate::inventory::submit! {
@@ -1288,7 +1316,12 @@ fn generate_field_accessor(
                     #js_field_name,
                     #setter_name,
                     1,
-                    #krate::JsClassMemberKind::Setter
+                    #krate::JsClassMemberKind::Setter,
+                    |buf| {
+                        buf.push(1);
+                        <#field_ty as #krate::EncodeTypeDef>::encode_type_def(buf);
+                        <() as #krate::EncodeTypeDef>::encode_type_def(buf);
+                    },
                 );
 
                 #krate::inventory::submit! {
@@ -1372,7 +1405,11 @@ fn generate_inspectable(
                 "toJSON",
                 #to_json_name,
                 0,
-                #krate::JsClassMemberKind::Method
+                #krate::JsClassMemberKind::Method,
+                |buf| {
+                    buf.push(0);
+                    <::alloc::string::String as #krate::EncodeTypeDef>::encode_type_def(buf);
+                },
             );
 
             #krate::inventory::submit! {
@@ -1408,7 +1445,11 @@ fn generate_inspectable(
                 "toString",
                 #to_string_name,
                 0,
-                #krate::JsClassMemberKind::Method
+                #krate::JsClassMemberKind::Method,
+                |buf| {
+                    buf.push(0);
+                    <::alloc::string::String as #krate::EncodeTypeDef>::encode_type_def(buf);
+                },
             );
 
             #krate::inventory::submit! {
@@ -1428,128 +1469,143 @@ fn generate_export_method(method: &ExportMethod, krate: &TokenStream) -> syn::Re
     let class_str = class.to_string();
     let export_name = format!("{class_str}::{js_name}");
 
-    // Generate argument decoding
-    let arg_names: Vec<_> = method.arguments.iter().map(|a| &a.name).collect();
-    let arg_types: Vec<_> = method.arguments.iter().map(|a| &a.ty).collect();
+    // Decode each argument and pass it to the method. The handler is outside the
+    // impl block, so `Self` is replaced with the class
+    let decodes: Vec<_> = method
+        .arguments
+        .iter()
+        .map(|arg| replace_self(export_arg_decode(arg, krate), class))
+        .collect();
+    let type_defs: Vec<_> = method
+        .arguments
+        .iter()
+        .map(|arg| replace_self(export_arg_type_def(arg, krate), class))
+        .collect();
+    let bindings: Vec<_> = method.arguments.iter().map(export_arg_binding).collect();
+    let arg_names: Vec<_> = method.arguments.iter().map(export_arg_call).collect();
+    let ret_ty = method
+        .ret
+        .as_ref()
+        .map(|ty| replace_self(ty.to_token_stream(), class));
 
     let decode_args = quote_spanned! {span=>
         #(
-            let #arg_names = <#arg_types as #krate::BinaryDecode>::decode(decoder)?;
+            let #bindings = #decodes;
         )*
     };
 
     // Generate the method call and return encoding based on kind
-    let method_body = match &method.kind {
-        ExportMethodKind::Constructor => {
-            // Constructor: create new instance and store in object store
-            quote_spanned! {span=>
-                #decode_args
-                let result = #class::#rust_name(#(#arg_names),*);
-                let handle = #krate::object_store::insert_object(result);
-                let mut encoder = #krate::EncodedData::new();
-                <#krate::object_store::ObjectHandle as #krate::BinaryEncode>::encode(handle, &mut encoder);
-                Ok(encoder)
+    let method_body = if method.is_async {
+        generate_async_method_body(method, &decode_args, &arg_names, krate)
+    } else {
+        match &method.kind {
+            ExportMethodKind::Constructor => {
+                // Constructor: create new instance and store in object store
+                quote_spanned! {span=>
+                    #decode_args
+                    let result = #class::#rust_name(#(#arg_names),*);
+                    let handle = #krate::object_store::insert_object(result);
+                    let mut encoder = #krate::EncodedData::new();
+                    <#krate::object_store::ObjectHandle as #krate::BinaryEncode>::encode(handle, &mut encoder);
+                    Ok(encoder)
+                }
             }
-        }
-        ExportMethodKind::Method { self_ty } => {
-            // Instance method: get object from store, call method
-            let call = match self_ty {
-                SelfType::RefShared => {
+            ExportMethodKind::Method { self_ty } => {
+                // Instance method: get object from store, call method
+                let call = match self_ty {
+                    SelfType::RefShared => {
+                        quote_spanned! {span=>
+                            #krate::object_store::with_object::<#class, _>(handle, |obj| {
+                                obj.#rust_name(#(#arg_names),*)
+                            })
+                        }
+                    }
+                    SelfType::RefMutable => {
+                        quote_spanned! {span=>
+                            #krate::object_store::with_object_mut::<#class, _>(handle, |obj| {
+                                obj.#rust_name(#(#arg_names),*)
+                            })
+                        }
+                    }
+                    SelfType::ByValue => {
+                        // Consuming method: remove from store
+                        quote_spanned! {span=>
+                            {
+                                let obj = #krate::object_store::remove_object::<#class>(handle);
+                                obj.#rust_name(#(#arg_names),*)
+                            }
+                        }
+                    }
+                };
+
+                if let Some(ret_ty) = &ret_ty {
                     quote_spanned! {span=>
-                        #krate::object_store::with_object::<#class, _>(handle, |obj| {
-                            obj.#rust_name(#(#arg_names),*)
-                        })
+                        let handle = <#krate::object_store::ObjectHandle as #krate::BinaryDecode>::decode(decoder)?;
+                        #decode_args
+                        let result = #call;
+                        let mut encoder = #krate::EncodedData::new();
+                        <#ret_ty as #krate::BinaryEncode>::encode(result, &mut encoder);
+                        Ok(encoder)
                     }
-                }
-                SelfType::RefMutable => {
+                } else {
                     quote_spanned! {span=>
-                        #krate::object_store::with_object_mut::<#class, _>(handle, |obj| {
-                            obj.#rust_name(#(#arg_names),*)
-                        })
+                        let handle = <#krate::object_store::ObjectHandle as #krate::BinaryDecode>::decode(decoder)?;
+                        #decode_args
+                        #call;
+                        Ok(#krate::EncodedData::new())
                     }
                 }
-                SelfType::ByValue => {
-                    // Consuming method: remove from store
+            }
+            ExportMethodKind::StaticMethod => {
+                // Static method: just call directly
+                if let Some(ret_ty) = &ret_ty {
                     quote_spanned! {span=>
-                        {
-                            let obj = #krate::object_store::remove_object::<#class>(handle);
-                            obj.#rust_name(#(#arg_names),*)
-                        }
+                        #decode_args
+                        let result = #class::#rust_name(#(#arg_names),*);
+                        let mut encoder = #krate::EncodedData::new();
+                        <#ret_ty as #krate::BinaryEncode>::encode(result, &mut encoder);
+                        Ok(encoder)
+                    }
+                } else {
+                    quote_spanned! {span=>
+                        #decode_args
+                        #class::#rust_name(#(#arg_names),*);
+                        Ok(#krate::EncodedData::new())
                     }
                 }
-            };
-
-            if method.ret.is_some() {
-                let ret_ty = method.ret.as_ref().unwrap();
-                quote_spanned! {span=>
-                    let handle = <#krate::object_store::ObjectHandle as #krate::BinaryDecode>::decode(decoder)?;
-                    #decode_args
-                    let result = #call;
-                    let mut encoder = #krate::EncodedData::new();
-                    <#ret_ty as #krate::BinaryEncode>::encode(result, &mut encoder);
-                    Ok(encoder)
-                }
-            } else {
-                quote_spanned! {span=>
-                    let handle = <#krate::object_store::ObjectHandle as #krate::BinaryDecode>::decode(decoder)?;
-                    #decode_args
-                    #call;
-                    Ok(#krate::EncodedData::new())
+            }
+            ExportMethodKind::Getter { property: _ } => {
+                // Property getter: call the getter method
+                if let Some(ret_ty) = &ret_ty {
+                    quote_spanned! {span=>
+                        let handle = <#krate::object_store::ObjectHandle as #krate::BinaryDecode>::decode(decoder)?;
+                        #krate::object_store::with_object::<#class, _>(handle, |obj| {
+                            let result = obj.#rust_name();
+                            let mut encoder = #krate::EncodedData::new();
+                            <#ret_ty as #krate::BinaryEncode>::encode(result, &mut encoder);
+                            Ok(encoder)
+                        })
+                    }
+                } else {
+                    return Err(syn::Error::new(span, "getter must have a return type"));
                 }
             }
-        }
-        ExportMethodKind::StaticMethod => {
-            // Static method: just call directly
-            if let Some(ret_ty) = &method.ret {
-                quote_spanned! {span=>
-                    #decode_args
-                    let result = #class::#rust_name(#(#arg_names),*);
-                    let mut encoder = #krate::EncodedData::new();
-                    <#ret_ty as #krate::BinaryEncode>::encode(result, &mut encoder);
-                    Ok(encoder)
+            ExportMethodKind::Setter { property: _ } => {
+                // Property setter: call the setter method
+                if method.arguments.is_empty() {
+                    return Err(syn::Error::new(span, "setter must have an argument"));
                 }
-            } else {
+
                 quote_spanned! {span=>
+                    let handle = <#krate::object_store::ObjectHandle as #krate::BinaryDecode>::decode(decoder)?;
                     #decode_args
-                    #class::#rust_name(#(#arg_names),*);
+                    #krate::object_store::with_object_mut::<#class, _>(handle, |obj| {
+                        obj.#rust_name(#(#arg_names),*);
+                    });
                     Ok(#krate::EncodedData::new())
                 }
             }
         }
-        ExportMethodKind::Getter { property: _ } => {
-            // Property getter: call the getter method
-            if let Some(ret_ty) = &method.ret {
-                quote_spanned! {span=>
-                    let handle = <#krate::object_store::ObjectHandle as #krate::BinaryDecode>::decode(decoder)?;
-                    #krate::object_store::with_object::<#class, _>(handle, |obj| {
-                        let result = obj.#rust_name();
-                        let mut encoder = #krate::EncodedData::new();
-                        <#ret_ty as #krate::BinaryEncode>::encode(result, &mut encoder);
-                        Ok(encoder)
-                    })
-                }
-            } else {
-                return Err(syn::Error::new(span, "getter must have a return type"));
-            }
-        }
-        ExportMethodKind::Setter { property: _ } => {
-            // Property setter: call the setter method
-            let arg_ty = method
-                .arguments
-                .first()
-                .map(|a| &a.ty)
-                .ok_or_else(|| syn::Error::new(span, "setter must have an argument"))?;
-            let arg_name = method.arguments.first().map(|a| &a.name).unwrap();
-
-            quote_spanned! {span=>
-                let handle = <#krate::object_store::ObjectHandle as #krate::BinaryDecode>::decode(decoder)?;
-                let #arg_name = <#arg_ty as #krate::BinaryDecode>::decode(decoder)?;
-                #krate::object_store::with_object_mut::<#class, _>(handle, |obj| {
-                    obj.#rust_name(#arg_name);
-                });
-                Ok(#krate::EncodedData::new())
-            }
-        }
     };
 
     // Generate the actual impl method
@@ -1570,6 +1626,7 @@ fn generate_export_method(method: &ExportMethod, krate: &TokenStream) -> syn::Re
         None => quote_spanned! {span=> },
     };
 
+    let asyncness = method.is_async.then(|| quote_spanned! {span=> async });
     let allows = clippy_allows();
     let method_impl = match &method.kind {
         ExportMethodKind::Constructor | ExportMethodKind::StaticMethod => {
@@ -1578,7 +1635,7 @@ fn generate_export_method(method: &ExportMethod, krate: &TokenStream) -> syn::Re
                 impl #class {
                     #allows
                     #rust_attrs
-                    #vis fn #rust_name(#(#fn_args),*) #ret_type #body
+                    #vis #asyncness fn #rust_name(#(#fn_args),*) #ret_type #body
                 }
             }
         }
@@ -1597,7 +1654,7 @@ fn generate_export_method(method: &ExportMethod, krate: &TokenStream) -> syn::Re
                 impl #class {
                     #allows
                     #rust_attrs
-                    #vis fn #rust_name(#fn_args_with_self) #ret_type #body
+                    #vis #asyncness fn #rust_name(#fn_args_with_self) #ret_type #body
                 }
             }
         }
@@ -1646,6 +1703,19 @@ fn generate_export_method(method: &ExportMethod, krate: &TokenStream) -> syn::Re
         ),
     };
 
+    // The types JS encodes the arguments and decodes the result with. Constructors
+    // return the handle of the new object, which JS wraps in the class, and async
+    // methods return a `Promise`
+    let signature_arg_count = arg_count as u8;
+    let signature_ret = match (&method.kind, &ret_ty) {
+        (ExportMethodKind::Constructor, _) => {
+            quote_spanned! {span=> #krate::object_store::ObjectHandle }
+        }
+        _ if method.is_async => quote_spanned! {span=> #krate::JsValue },
+        (ExportMethodKind::Setter { .. }, _) | (_, None) => quote_spanned! {span=> () },
+        (_, Some(ret_ty)) => ret_ty.clone(),
+    };
+
     let js_class_member_spec = quote_spanned! {span=>
         const _: () = {
             #[allow(non_upper_case_globals)]
@@ -1654,7 +1724,14 @@ fn generate_export_method(method: &ExportMethod, krate: &TokenStream) -> syn::Re
                 #member_name,
                 #export_name,
                 #arg_count,
-                #member_kind
+                #member_kind,
+                |buf| {
+                    buf.push(#signature_arg_count);
+                    #(
+                        #type_defs
+                    )*
+                    <#signature_ret as #krate::EncodeTypeDef>::encode_type_def(buf);
+                },
             );
 
             #krate::inventory::submit! {
@@ -1666,6 +1743,7 @@ fn generate_export_method(method: &ExportMethod, krate: &TokenStream) -> syn::Re
     Ok(quote_spanned! {span=>
         #method_impl
 
+        #allows
         const _: () = {
             #[allow(non_upper_case_globals)]
             static __EXPORT_SPEC: #krate::JsExportSpec = #krate::JsExportSpec::new(
@@ -1684,6 +1762,282 @@ fn generate_export_method(method: &ExportMethod, krate: &TokenStream) -> syn::Re
     })
 }
 
+/// Generate the handler of an async method, which starts the method's future and
+/// returns a `Promise` for its result. Methods that take `&self` or `&mut self`
+/// keep the object borrowed until the future finishes.
+fn generate_async_method_body(
+    method: &ExportMethod,
+    decode_args: &TokenStream,
+    arg_names: &[TokenStream],
+    krate: &TokenStream,
+) -> TokenStream {
+    let class = &method.class;
+    let rust_name = &method.rust_name;
+    let span = rust_name.span();
+
+    let take_handle = quote_spanned! {span=>
+        let handle = <#krate::object_store::ObjectHandle as #krate::BinaryDecode>::decode(decoder)?;
+    };
+    let (take_object, borrow, receiver) = match &method.kind {
+        ExportMethodKind::Method {
+            self_ty: SelfType::RefShared,
+        } => (
+            quote_spanned! {span=>
+                #take_handle
+                let obj = #krate::object_store::share_object::<#class>(handle);
+            },
+            quote_spanned! {span=> let obj = #krate::object_store::borrow_object(&obj); },
+            quote_spanned! {span=> obj },
+        ),
+        ExportMethodKind::Method {
+            self_ty: SelfType::RefMutable,
+        } => (
+            quote_spanned! {span=>
+                #take_handle
+                let obj = #krate::object_store::share_object::<#class>(handle);
+            },
+            quote_spanned! {span=> let mut obj = #krate::object_store::borrow_object_mut(&obj); },
+            quote_spanned! {span=> obj },
+        ),
+        ExportMethodKind::Method {
+            self_ty: SelfType::ByValue,
+        } => (
+            quote_spanned! {span=>
+                #take_handle
+                let obj = #krate::object_store::remove_object::<#class>(handle);
+            },
+            TokenStream::new(),
+            quote_spanned! {span=> obj },
+        ),
+        _ => (
+            TokenStream::new(),
+            TokenStream::new(),
+            quote_spanned! {span=> #class },
+        ),
+    };
+    let call = match &method.kind {
+        ExportMethodKind::Method { .. } => {
+            quote_spanned! {span=> #receiver.#rust_name(#(#arg_names),*).await }
+        }
+        _ => quote_spanned! {span=> #receiver::#rust_name(#(#arg_names),*).await },
+    };
+
+    // The promise rejects with the `Err` value of methods that return a `Result`
+    let future = if method
+        .ret
+        .as_ref()
+        .and_then(extract_result_ok_type)
+        .is_some()
+    {
+        quote_spanned! {span=> async move { #borrow #call } }
+    } else {
+        quote_spanned! {span=> async move { #borrow ::core::result::Result::Ok::<_, ()>(#call) } }
+    };
+
+    quote_spanned! {span=>
+        #take_object
+        #decode_args
+        let promise = #krate::__rt::future_to_promise(#future);
+        let mut encoder = #krate::EncodedData::new();
+        <#krate::JsValue as #krate::BinaryEncode>::encode(promise, &mut encoder);
+        Ok(encoder)
+    }
+}
+
+/// Generate code for an exported free function
+fn generate_export_function(
+    function: &ExportFunction,
+    krate: &TokenStream,
+) -> syn::Result<TokenStream> {
+    let rust_name = &function.rust_name;
+    let js_name = &function.js_name;
+    let span = rust_name.span();
+
+    // Decode each argument and pass it to the function
+    let arg_names: Vec<_> = function.arguments.iter().map(|a| &a.name).collect();
+    let decodes: Vec<_> = function
+        .arguments
+        .iter()
+        .map(|arg| export_arg_decode(arg, krate))
+        .collect();
+    let type_defs: Vec<_> = function
+        .arguments
+        .iter()
+        .map(|arg| export_arg_type_def(arg, krate))
+        .collect();
+    let bindings: Vec<_> = function.arguments.iter().map(export_arg_binding).collect();
+    let call_args: Vec<_> = function.arguments.iter().map(export_arg_call).collect();
+    let arg_count = function.arguments.len() as u8;
+    let ret_ty = match &function.ret {
+        Some(ty) => quote_spanned! {span=> #ty },
+        None => quote_spanned! {span=> () },
+    };
+
+    // Generate the function itself
+    let vis = &function.vis;
+    let body = &function.body;
+    let rust_attrs = function.fn_rust_attrs();
+    let arg_types: Vec<_> = function.arguments.iter().map(|a| &a.ty).collect();
+    let ret_type = match &function.ret {
+        Some(ty) => quote_spanned! {span=> -> #ty },
+        None => quote_spanned! {span=> },
+    };
+    let allows = clippy_allows();
+
+    Ok(quote_spanned! {span=>
+        #allows
+        #rust_attrs
+        #vis fn #rust_name(#(#arg_names: #arg_types),*) #ret_type #body
+
+        const _: () = {
+            #[allow(non_upper_case_globals)]
+            static __EXPORT_SPEC: #krate::JsExportSpec = #krate::JsExportSpec::new(
+                #js_name,
+                |decoder| {
+                    #(
+                        let #bindings = #decodes;
+                    )*
+                    let result = #rust_name(#(#call_args),*);
+                    let mut encoder = #krate::EncodedData::new();
+                    <#ret_ty as #krate::BinaryEncode>::encode(result, &mut encoder);
+                    Ok(encoder)
+                }
+            )
+            .with_signature(|buf| {
+                buf.push(#arg_count);
+                #(
+                    #type_defs
+                )*
+                <#ret_ty as #krate::EncodeTypeDef>::encode_type_def(buf);
+            });
+
+            #krate::inventory::submit! {
+                __EXPORT_SPEC
+            }
+        };
+    })
+}
+
+/// Generate code for a function marked with `#[wasm_bindgen(start)]`
+fn generate_start_function(start: &StartFunction, krate: &TokenStream) -> TokenStream {
+    let rust_name = &start.rust_name;
+    let function = &start.function;
+    let span = rust_name.span();
+
+    let call = if start.is_async {
+        quote_spanned! {span=> #rust_name().await }
+    } else {
+        quote_spanned! {span=> #rust_name() }
+    };
+
+    quote_spanned! {span=>
+        #function
+
+        const _: () = {
+            #[allow(non_upper_case_globals)]
+            static __START_SPEC: #krate::JsStartSpec = #krate::JsStartSpec::new(|| {
+                #krate::alloc::boxed::Box::pin(async {
+                    #krate::__rt::Start::start(#call);
+                })
+            });
+
+            #krate::inventory::submit! {
+                __START_SPEC
+            }
+        };
+    }
+}
+
+/// The owned type an exported function decodes an argument as. Mutable references
+/// are decoded as the owned value, `&str` as a `String` and `&[T]` as a `Vec<T>`.
+fn export_arg_decode_type(arg: &FunctionArg, krate: &TokenStream) -> TokenStream {
+    let syn::Type::Reference(reference) = &arg.ty else {
+        return arg.ty.to_token_stream();
+    };
+    match &*reference.elem {
+        syn::Type::Path(path) if path.path.is_ident("str") => {
+            quote! { #krate::alloc::string::String }
+        }
+        syn::Type::Slice(slice) => {
+            let elem = &slice.elem;
+            quote! { #krate::alloc::vec::Vec<#elem> }
+        }
+        elem => elem.to_token_stream(),
+    }
+}
+
+/// The type behind a shared reference argument that JS passes by reference, like
+/// `&JsValue` or `&ExportedStruct`.
+fn export_arg_borrowed_type(arg: &FunctionArg) -> Option<&syn::Type> {
+    let syn::Type::Reference(reference) = &arg.ty else {
+        return None;
+    };
+    match &*reference.elem {
+        _ if reference.mutability.is_some() => None,
+        syn::Type::Path(path) if path.path.is_ident("str") => None,
+        syn::Type::Slice(_) => None,
+        elem => Some(elem),
+    }
+}
+
+/// The expression an exported function decodes an argument with.
+fn export_arg_decode(arg: &FunctionArg, krate: &TokenStream) -> TokenStream {
+    if let Some(elem) = export_arg_borrowed_type(arg) {
+        return quote! { <#elem as #krate::convert::RefFromBinaryDecode>::ref_decode(decoder)? };
+    }
+    let ty = export_arg_decode_type(arg, krate);
+    quote! { <#ty as #krate::BinaryDecode>::decode(decoder)? }
+}
+
+/// The statement that encodes the type JS passes an argument of an exported function as.
+fn export_arg_type_def(arg: &FunctionArg, krate: &TokenStream) -> TokenStream {
+    if let Some(elem) = export_arg_borrowed_type(arg) {
+        return quote! { <#elem as #krate::convert::RefFromBinaryDecode>::encode_ref_type_def(buf); };
+    }
+    let ty = export_arg_decode_type(arg, krate);
+    quote! { <#ty as #krate::EncodeTypeDef>::encode_type_def(buf); }
+}
+
+/// The binding an exported function decodes an argument into.
+fn export_arg_binding(arg: &FunctionArg) -> TokenStream {
+    let name = &arg.name;
+    match &arg.ty {
+        syn::Type::Reference(reference) if reference.mutability.is_some() => quote! { mut #name },
+        _ => quote! { #name },
+    }
+}
+
+/// The expression an exported function passes for an argument decoded with
+/// [`export_arg_decode`].
+fn export_arg_call(arg: &FunctionArg) -> TokenStream {
+    let name = &arg.name;
+    match &arg.ty {
+        syn::Type::Reference(reference) if reference.mutability.is_some() => quote! { &mut #name },
+        syn::Type::Reference(_) if export_arg_borrowed_type(arg).is_some() => quote! { &*#name },
+        syn::Type::Reference(_) => quote! { &#name },
+        _ => quote! { #name },
+    }
+}
+
+/// Replace `Self` with the class name, for types used outside the impl block.
+fn replace_self(tokens: TokenStream, class: &syn::Ident) -> TokenStream {
+    tokens
+        .into_iter()
+        .map(|token| match token {
+            proc_macro2::TokenTree::Ident(ident) if ident == "Self" => {
+                proc_macro2::TokenTree::Ident(class.clone())
+            }
+            proc_macro2::TokenTree::Group(group) => {
+                let mut replaced =
+                    proc_macro2::Group::new(group.delimiter(), replace_self(group.stream(), class));
+                replaced.set_span(group.span());
+                proc_macro2::TokenTree::Group(replaced)
+            }
+            token => token,
+        })
+        .collect()
+}
+
 /// Extract the Ok type from a Result<T, E> type, or None if not a Result
 fn extract_result_ok_type(ty: &syn::Type) -> Option<syn::Type> {
     if let syn::Type::Path(type_path) = ty {
@@ -78,6 +78,8 @@ pub struct BindgenAttrs {
     pub getter_with_clone: Option<Span>,
     /// The `module` attribute - path to external JS module file (read at compile time)
     pub module: Option<(Span, String)>,
+    /// The `start` attribute - run the function when the app starts
+    pub start: Option<Span>,
 }
 
 impl BindgenAttrs {
@@ -177,6 +179,7 @@ enum BindgenAttr {
     Skip(Span),
     GetterWithClone(Span),
     Module(Span, String),
+    Start(Span),
 }
 
 impl Parse for BindgenAttr {
@@ -303,6 +306,7 @@ impl Parse for BindgenAttr {
             "inspectable" => Ok(BindgenAttr::Inspectable(span)),
             "skip" => Ok(BindgenAttr::Skip(span)),
             "getter_with_clone" => Ok(BindgenAttr::GetterWithClone(span)),
+            "start" => Ok(BindgenAttr::Start(span)),
 
             "module" => {
                 input.parse::<Token![=]>()?;
@@ -553,6 +557,12 @@ pub fn parse_attrs(attr: TokenStream) -> syn::Result<BindgenAttrs> {
                 }
                 result.module = Some((span, path));
             }
+            BindgenAttr::Start(span) => {
+                if result.start.is_some() {
+                    return Err(syn::Error::new(span, "duplicate `start` attribute"));
+                }
+                result.start = Some(span);
+            }
         }
     }
 
@@ -3,19 +3,87 @@
 //! This module provides the batching infrastructure that allows multiple
 //! JS operations to be grouped together for efficient execution.
 
-use alloc::collections::BTreeMap;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::rc::Rc;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec;
 use alloc::vec::Vec;
 use core::any::Any;
-use core::cell::{Ref, RefCell, RefMut};
+use core::cell::{Cell, Ref, RefCell, RefMut};
+use core::fmt;
+use core::panic::{AssertUnwindSafe, Location};
+use core::pin::Pin;
+use core::task::{Poll, Waker, ready};
+use core::time::Duration;
+use futures_util::future::Either;
 use std::boxed::Box;
 
+use crate::deferred::{Deferred, ResolveDeferred};
 use crate::encode::{BatchableResult, BinaryDecode};
+use crate::function_registry::FUNCTION_REGISTRY;
 use crate::ipc::DecodedData;
-use crate::ipc::{EncodedData, IPCMessage, MessageType};
+use crate::ipc::{EncodedData, EvaluateHeader, HeapCheck, IPCMessage, MessageType};
 use crate::lazy::ThreadLocalKey;
 use crate::runtime::WryIPC;
+use crate::send::ForeignDrops;
 use crate::value::{JSIDX_OFFSET, JSIDX_RESERVED};
 
+/// An exported object Rust lent to a JS call, see [`Runtime::lend_object`].
+///
+/// The pointer is valid while the object is in the store: the [`LendScope`] of
+/// the call removes it before the borrow it came from ends.
+struct LentObject<T>(*const T);
+
+/// Takes back the objects lent to a JS call when the call returns or unwinds.
+///
+/// Only synchronous calls lend objects, since `call_async` doesn't take
+/// [`BorrowedForCall`](crate::BorrowedForCall) arguments. Those calls nest, and
+/// so do their scopes. Dropping one removes the objects lent after it was
+/// created, including those of calls nested in it.
+struct LendScope {
+    lent: usize,
+}
+
+impl LendScope {
+    fn new() -> Self {
+        Self {
+            lent: with_runtime(|state| state.lent_object_count()),
+        }
+    }
+}
+
+impl Drop for LendScope {
+    fn drop(&mut self) {
+        RUNTIME.with(|state| {
+            // If the runtime is gone or busy, the objects went with it, or the
+            // unwind of the call from JS it is part of releases them
+            if let Ok(mut state) = state.try_borrow_mut()
+                && let Some(runtime) = state.last_mut()
+            {
+                runtime.release_lent_objects(self.lent);
+            }
+        });
+    }
+}
+
+/// A shared reference to an exported object in the store.
+pub(crate) enum ObjectRef<'a, T> {
+    Owned(Ref<'a, T>),
+    Lent(&'a T),
+}
+
+impl<T> core::ops::Deref for ObjectRef<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        match self {
+            ObjectRef::Owned(obj) => obj,
+            ObjectRef::Lent(obj) => obj,
+        }
+    }
+}
+
 /// State for batching operations and object storage.
 /// Every evaluation is a batch - it may just have one operation.
 ///
@@ -24,8 +92,16 @@ use crate::value::{JSIDX_OFFSET, JSIDX_RESERVED};
 pub struct Runtime {
     /// The encoder accumulating batched operations
     encoder: EncodedData,
-    /// Stack of freed IDs available for reuse
+    /// The operations in the encoder, reported if JS throws or times out
+    operations: Vec<PendingOperation>,
+    /// Decoders for the [`Deferred`] results of operations in the encoder, in order
+    deferred: Vec<ResolveDeferred>,
+    /// Stack of freed IDs available for reuse. JS keeps the same stack, see [`EvaluateHeader`]
     free_ids: Vec<u64>,
+    /// IDs released since the last message was sent. JS frees them when it runs that message
+    unsent_frees: Vec<u64>,
+    /// IDs JS confirmed it freed, reused after the next message tells JS to reuse them too
+    confirmed_frees: Vec<u64>,
     /// Next ID to allocate if free_ids is empty
     max_id: u64,
     /// A stack of ongoing function encodings with the ids
@@ -45,8 +121,10 @@ pub struct Runtime {
     type_cache: BTreeMap<Vec<u8>, u32>,
     /// Next type ID to assign
     next_type_id: u32,
-    /// Exported Rust structs stored by handle
+    /// Exported Rust structs stored by handle, shared with the async methods running on them
     objects: BTreeMap<u32, Box<dyn Any>>,
+    /// Handles of the objects Rust lent to the JS calls that are running, innermost last
+    lent_objects: Vec<u32>,
     /// Next handle to assign for exported objects
     next_object_handle: u32,
     /// The ipc layer used to communicate with the JS runtime
@@ -55,13 +133,137 @@ pub struct Runtime {
     webview_id: u64,
     /// Thread locals associated with the runtime
     thread_locals: BTreeMap<ThreadLocalKey<'static>, Box<dyn Any>>,
+    /// Futures started with `spawn_local` that the runtime hasn't picked up yet
+    local_tasks: VecDeque<Pin<Box<dyn Future<Output = ()>>>>,
+    /// How many calls from JS into Rust are currently running
+    js_call_depth: u32,
+    /// How long a flush waits without hearing from JS before giving up
+    flush_timeout: Option<Duration>,
+    /// The timeout of the innermost [`batch_with_timeout`], which returns an error instead of panicking
+    batch_timeout: Option<Duration>,
+    /// Responses to flushes that timed out, skipped if JS sends them late
+    abandoned_responses: u32,
+    /// How many times the webview reloaded. Heap values from older page loads are invalid
+    generation: u32,
+    /// Callbacks to run after the webview reloads
+    reload_listeners: Vec<Box<dyn FnMut()>>,
+    /// Values whose [`SendJsValue`](crate::SendJsValue) was dropped on another thread
+    foreign_drops: Arc<ForeignDrops>,
+    /// Where each live heap value was created, if a leak check is running
+    leak_check: Option<BTreeMap<u64, Option<&'static Location<'static>>>>,
+    /// The call heap values allocated right now are attributed to
+    alloc_site: Option<&'static Location<'static>>,
+    /// The batch sent by [`run_js_async`] that JS has not responded to yet
+    in_flight: Option<InFlight>,
+    /// IDs released while an async call was in flight, dropped in JS once it finishes
+    pending_drops: Vec<u64>,
+}
+
+/// Decodes the result of an async call from a Respond message, or takes the
+/// exception if JS threw.
+type RespondAsync = Box<dyn FnOnce(Result<&mut DecodedData, String>)>;
+
+/// A batch sent by [`run_js_async`] that JS has not responded to yet.
+///
+/// Only one batch is in flight at a time, so JS allocates heap IDs for it in
+/// the same order as Rust.
+pub(crate) struct InFlight {
+    /// The operations in the batch, reported if JS throws
+    pub(crate) operations: Vec<PendingOperation>,
+    /// The IDs the batch drops
+    freed: Vec<u64>,
+    /// Decoders for the [`Deferred`] results of operations in the batch, in order
+    deferred: Vec<ResolveDeferred>,
+    /// Decodes the result of the call, or takes the exception if JS threw
+    respond: RespondAsync,
+    /// Futures waiting for JS to respond
+    wakers: Vec<Waker>,
+}
+
+impl InFlight {
+    /// Finish the batch with the response from JS, or the message of the exception JS threw.
+    pub(crate) fn resolve(self, mut response: Result<&mut DecodedData, String>) {
+        if let Ok(data) = &mut response {
+            for resolve in self.deferred {
+                resolve(data);
+            }
+            with_runtime(|state| state.confirm_frees(self.freed));
+        }
+        (self.respond)(response);
+        for waker in self.wakers {
+            waker.wake();
+        }
+    }
+}
+
+/// Runtime state saved before running code that may unwind, restored if it does.
+pub(crate) struct UnwindFrame {
+    borrow_frames: usize,
+    ids_to_free: usize,
+    lent_objects: usize,
+    is_batching: bool,
+    batch_timeout: Option<Duration>,
+    alloc_site: Option<&'static Location<'static>>,
+}
+
+/// An operation in a batch sent to JS.
+#[derive(Debug, Clone, Copy)]
+pub struct PendingOperation {
+    /// The id of the JS function the operation calls
+    pub fn_id: u32,
+    /// Where the operation was called from
+    pub location: &'static Location<'static>,
 }
 
+/// JS did not respond to a batch within the flush timeout.
+///
+/// Flushes panic with this error unless they run inside [`batch_with_timeout`],
+/// which returns it instead. The event loop also receives it as a
+/// [`WryBindgenEvent`](crate::runtime::WryBindgenEvent).
+#[derive(Debug, Clone)]
+pub struct FlushTimeout {
+    /// The webview the batch was sent to
+    pub webview_id: u64,
+    /// How long Rust waited without hearing from JS
+    pub timeout: Duration,
+    /// The operations in the batch JS did not respond to
+    pub operations: Vec<PendingOperation>,
+}
+
+impl fmt::Display for FlushTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "JS did not respond to a batch of {} operations in webview {} within {:?}",
+            self.operations.len(),
+            self.webview_id,
+            self.timeout
+        )?;
+        for operation in &self.operations {
+            let js_code = FUNCTION_REGISTRY
+                .js_code(operation.fn_id)
+                .unwrap_or_else(|| "<unknown function>".to_string());
+            write!(
+                f,
+                "\n  JS function {} called from {}: {js_code}",
+                operation.fn_id, operation.location
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl core::error::Error for FlushTimeout {}
+
 impl Runtime {
     pub(crate) fn new(ipc: WryIPC, webview_id: u64) -> Self {
         Self {
             encoder: Self::new_encoder_for_evaluate(),
+            operations: Vec::new(),
+            deferred: Vec::new(),
             free_ids: Vec::new(),
+            unsent_frees: Vec::new(),
+            confirmed_frees: Vec::new(),
             // Start allocating heap IDs from JSIDX_RESERVED to match JS heap
             max_id: JSIDX_RESERVED,
             ids_to_free: Vec::new(),
@@ -78,11 +280,24 @@ impl Runtime {
             next_type_id: 0,
             // Object store starts empty
             objects: BTreeMap::new(),
-            // Object handles start at 0
-            next_object_handle: 0,
+            lent_objects: Vec::new(),
+            // Object handles start at 1, JS marks wrappers that no longer own an object with 0
+            next_object_handle: 1,
             ipc,
             webview_id,
             thread_locals: BTreeMap::new(),
+            local_tasks: VecDeque::new(),
+            js_call_depth: 0,
+            flush_timeout: None,
+            batch_timeout: None,
+            abandoned_responses: 0,
+            generation: 0,
+            reload_listeners: Vec::new(),
+            foreign_drops: Arc::default(),
+            leak_check: None,
+            alloc_site: None,
+            in_flight: None,
+            pending_drops: Vec::new(),
         }
     }
 
@@ -95,8 +310,14 @@ impl Runtime {
     /// Get the next heap ID for placeholder allocation.
     /// Uses free-list strategy: reuses freed IDs first, then allocates new ones.
     pub fn get_next_heap_id(&mut self) -> u64 {
-        let id = self.max_id;
-        self.max_id += 1;
+        let id = self.free_ids.pop().unwrap_or_else(|| {
+            let id = self.max_id;
+            self.max_id += 1;
+            id
+        });
+        if let Some(sites) = &mut self.leak_check {
+            sites.insert(id, self.alloc_site);
+        }
         id
     }
 
@@ -138,7 +359,163 @@ impl Runtime {
         }
     }
 
-    /// Release a heap ID back to the free-list and queue it for JS drop.
+    /// Save the state to restore if the code that runs next unwinds.
+    pub(crate) fn unwind_frame(&self) -> UnwindFrame {
+        UnwindFrame {
+            borrow_frames: self.borrow_frame_stack.len(),
+            ids_to_free: self.ids_to_free.len(),
+            lent_objects: self.lent_objects.len(),
+            is_batching: self.is_batching,
+            batch_timeout: self.batch_timeout,
+            alloc_site: self.alloc_site,
+        }
+    }
+
+    /// Restore the state saved by [`Runtime::unwind_frame`] after the code unwound.
+    /// Returns the ids that need to be dropped in JS.
+    pub(crate) fn unwind_to(&mut self, frame: UnwindFrame) -> Vec<u64> {
+        self.is_batching = frame.is_batching;
+        self.batch_timeout = frame.batch_timeout;
+        self.alloc_site = frame.alloc_site;
+        // Restore the borrow stack pointer saved by the first frame pushed after the save
+        if let Some(&saved_pointer) = self.borrow_frame_stack.get(frame.borrow_frames) {
+            self.borrow_stack_pointer = saved_pointer;
+        }
+        self.borrow_frame_stack.truncate(frame.borrow_frames);
+        // The calls the objects were lent to unwound, so their borrows ended
+        self.release_lent_objects(frame.lent_objects);
+        let mut to_free = Vec::new();
+        while self.ids_to_free.len() > frame.ids_to_free {
+            to_free.extend(self.pop_and_release_ids());
+        }
+        to_free
+    }
+
+    /// Record that JS called into Rust, saving the state to restore if the call unwinds.
+    pub(crate) fn enter_js_call(&mut self) -> UnwindFrame {
+        self.js_call_depth += 1;
+        let frame = self.unwind_frame();
+        // A batch_with_timeout outside the call can't catch errors across the JS boundary
+        self.batch_timeout = None;
+        // Values JS passes into the call weren't created by the Rust call waiting on JS
+        self.alloc_site = None;
+        frame
+    }
+
+    /// Record that a call from JS returned normally.
+    pub(crate) fn exit_js_call(&mut self, frame: UnwindFrame) {
+        self.js_call_depth -= 1;
+        self.batch_timeout = frame.batch_timeout;
+        self.alloc_site = frame.alloc_site;
+    }
+
+    /// Check if Rust is currently running a call from JS.
+    pub(crate) fn in_js_call(&self) -> bool {
+        self.js_call_depth > 0
+    }
+
+    /// Restore the state saved by [`Runtime::enter_js_call`] after the call unwound.
+    /// Returns the ids that need to be dropped in JS.
+    pub(crate) fn unwind_js_call(&mut self, frame: UnwindFrame) -> Vec<u64> {
+        self.js_call_depth -= 1;
+        self.unwind_to(frame)
+    }
+
+    /// Set how long a flush waits without hearing from JS before it panics.
+    /// `None` waits forever.
+    pub(crate) fn set_flush_timeout(&mut self, timeout: Option<Duration>) {
+        self.flush_timeout = timeout;
+    }
+
+    /// The timeout for the next flush, and whether it runs inside [`batch_with_timeout`].
+    fn current_flush_timeout(&self) -> Option<(Duration, bool)> {
+        match self.batch_timeout {
+            Some(timeout) => Some((timeout, true)),
+            None => self.flush_timeout.map(|timeout| (timeout, false)),
+        }
+    }
+
+    /// Skip the response to a flush that timed out if JS sends it late.
+    pub(crate) fn abandon_response(&mut self) {
+        self.abandoned_responses += 1;
+    }
+
+    /// Check if the next response from JS belongs to a flush that timed out.
+    pub(crate) fn take_abandoned_response(&mut self) -> bool {
+        let abandoned = self.abandoned_responses > 0;
+        self.abandoned_responses = self.abandoned_responses.saturating_sub(1);
+        abandoned
+    }
+
+    /// The number of times the webview reloaded before the current page.
+    pub(crate) fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// Forget everything tied to the page that was loaded before the webview
+    /// reloaded, and start over with the new page's empty heap and type cache.
+    ///
+    /// Returns the stale exported objects, callbacks and thread locals. They
+    /// must be dropped after the runtime borrow is released because dropping
+    /// them can drop `JsValue`s.
+    pub(crate) fn reset_for_reload(&mut self, generation: u32) -> Vec<Box<dyn Any>> {
+        self.generation = generation;
+        // Anything not flushed yet was meant for the old page
+        self.encoder = Self::new_encoder_for_evaluate();
+        self.operations.clear();
+        self.deferred.clear();
+        self.reserved_placeholder_count = 0;
+        self.free_ids.clear();
+        self.unsent_frees.clear();
+        self.confirmed_frees.clear();
+        self.max_id = JSIDX_RESERVED;
+        for ids in &mut self.ids_to_free {
+            ids.clear();
+        }
+        self.type_cache.clear();
+        self.next_type_id = 0;
+        // Values from the old page are gone, not leaked
+        if let Some(sites) = &mut self.leak_check {
+            sites.clear();
+        }
+        // The old page will never respond to a flush that timed out
+        self.abandoned_responses = 0;
+        self.pending_drops.clear();
+
+        self.lent_objects.clear();
+        let mut stale: Vec<Box<dyn Any>> = vec![Box::new(core::mem::take(&mut self.objects))];
+        // Or to an async call. Its future finds no result and panics.
+        if let Some(mut in_flight) = self.in_flight.take() {
+            for waker in core::mem::take(&mut in_flight.wakers) {
+                waker.wake();
+            }
+            stale.push(Box::new(in_flight));
+        }
+        stale.extend(core::mem::take(&mut self.thread_locals).into_values());
+        stale.push(Box::new(core::mem::take(&mut self.local_tasks)));
+        stale
+    }
+
+    /// Register a callback to run after the webview reloads.
+    pub(crate) fn add_reload_listener(&mut self, listener: Box<dyn FnMut()>) {
+        self.reload_listeners.push(listener);
+    }
+
+    /// Take the reload callbacks out of the runtime so they can run without borrowing it.
+    pub(crate) fn take_reload_listeners(&mut self) -> Vec<Box<dyn FnMut()>> {
+        core::mem::take(&mut self.reload_listeners)
+    }
+
+    /// Put back the reload callbacks taken with [`Runtime::take_reload_listeners`],
+    /// before any that were registered while they ran.
+    pub(crate) fn restore_reload_listeners(&mut self, mut listeners: Vec<Box<dyn FnMut()>>) {
+        listeners.append(&mut self.reload_listeners);
+        self.reload_listeners = listeners;
+    }
+
+    /// Release a heap ID and queue it for JS drop.
+    ///
+    /// The ID is reused once JS confirms it ran the drop, see `Runtime::confirm_frees`.
     pub fn release_heap_id(&mut self, id: u64) -> Option<u64> {
         // Never release reserved IDs
         if id < JSIDX_RESERVED {
@@ -146,16 +523,22 @@ impl Runtime {
         }
 
         debug_assert!(
-            !self.free_ids.contains(&id) && !self.ids_to_free.iter().any(|ids| ids.contains(&id)),
+            !self.free_ids.contains(&id)
+                && !self.unsent_frees.contains(&id)
+                && !self.confirmed_frees.contains(&id)
+                && !self.ids_to_free.iter().any(|ids| ids.contains(&id)),
             "Double-free detected for heap ID {id}"
         );
+        if let Some(sites) = &mut self.leak_check {
+            sites.remove(&id);
+        }
         match self.ids_to_free.last_mut() {
             Some(ids) => {
                 ids.push(id);
                 None
             }
             None => {
-                self.free_ids.push(id);
+                self.unsent_frees.push(id);
                 Some(id)
             }
         }
@@ -163,12 +546,95 @@ impl Runtime {
 
     /// Take the message data and reset the batch for reuse.
     /// Includes any pending drops at the start of the message.
-    /// Prepends the reserved placeholder count so JS can skip those IDs during nested allocations.
-    pub(crate) fn take_message(&mut self) -> IPCMessage {
-        let reserved_count = self.take_reserved_placeholder_count();
+    /// Prepends the [`EvaluateHeader`] so JS reserves the placeholder IDs and
+    /// recycles the same freed IDs as Rust.
+    ///
+    /// Also returns the IDs the message drops. Pass them to
+    /// [`Runtime::confirm_frees`] once JS responds to the message.
+    pub(crate) fn take_message(&mut self) -> (IPCMessage, Vec<u64>) {
+        let reserved = self.take_reserved_placeholder_count();
+        // Placeholders in this message were already taken from the free list, so
+        // JS reserves them before recycling
+        let recycled = core::mem::take(&mut self.confirmed_frees);
+        self.free_ids.extend_from_slice(&recycled);
+        let header = EvaluateHeader {
+            reserved,
+            recycled,
+            check: cfg!(debug_assertions).then(|| HeapCheck::new(&self.free_ids, self.max_id)),
+        };
         let mut encoder = self.take_encoder();
-        encoder.prepend_u32(reserved_count);
-        IPCMessage::new(encoder.to_bytes())
+        header.prepend_to(&mut encoder);
+        (
+            IPCMessage::new(encoder.to_bytes()),
+            core::mem::take(&mut self.unsent_frees),
+        )
+    }
+
+    /// The number of heap values Rust holds. Values whose drop JS hasn't run yet
+    /// are not counted.
+    pub(crate) fn live_heap_ids(&self) -> usize {
+        let allocated = (self.max_id - JSIDX_RESERVED) as usize;
+        let released = self.free_ids.len()
+            + self.unsent_frees.len()
+            + self.confirmed_frees.len()
+            + self.ids_to_free.iter().map(Vec::len).sum::<usize>();
+        allocated - released
+    }
+
+    /// The number of exported Rust structs and callbacks in the object store.
+    pub(crate) fn object_count(&self) -> usize {
+        self.objects.len()
+    }
+
+    /// Start recording where heap values are created, forgetting any earlier records.
+    pub(crate) fn start_leak_check(&mut self) {
+        self.leak_check = Some(BTreeMap::new());
+    }
+
+    /// Stop recording where heap values are created. Returns where each value
+    /// created since [`Runtime::start_leak_check`] that is still alive was created.
+    pub(crate) fn finish_leak_check(
+        &mut self,
+    ) -> Option<BTreeMap<u64, Option<&'static Location<'static>>>> {
+        self.leak_check.take()
+    }
+
+    /// Attribute the heap values allocated from now on to a call. Returns the previous call.
+    pub(crate) fn set_alloc_site(
+        &mut self,
+        site: Option<&'static Location<'static>>,
+    ) -> Option<&'static Location<'static>> {
+        core::mem::replace(&mut self.alloc_site, site)
+    }
+
+    /// Check if an async call is waiting for JS to respond.
+    pub(crate) fn has_in_flight(&self) -> bool {
+        self.in_flight.is_some()
+    }
+
+    /// Wake `waker` when the async call in flight finishes. Returns `false` if
+    /// no call is in flight.
+    pub(crate) fn wait_for_in_flight(&mut self, waker: &Waker) -> bool {
+        let Some(in_flight) = &mut self.in_flight else {
+            return false;
+        };
+        if !in_flight.wakers.iter().any(|w| w.will_wake(waker)) {
+            in_flight.wakers.push(waker.clone());
+        }
+        true
+    }
+
+    /// Take the async call in flight to resolve it with the response from JS.
+    pub(crate) fn take_in_flight(&mut self) -> Option<InFlight> {
+        self.in_flight.take()
+    }
+
+    /// Reuse the IDs dropped by a message JS responded to.
+    ///
+    /// If JS threw or never responded, some drops may not have run, so the IDs
+    /// are never reused.
+    pub(crate) fn confirm_frees(&mut self, ids: Vec<u64>) {
+        self.confirmed_frees.extend(ids);
     }
 
     pub(crate) fn is_empty(&self) -> bool {
@@ -210,6 +676,17 @@ impl Runtime {
         core::mem::replace(&mut self.encoder, Self::new_encoder_for_evaluate())
     }
 
+    /// Decode the result of the next operation added to the encoder into a [`Deferred`]
+    /// when JS responds to the batch.
+    pub(crate) fn push_deferred(&mut self, resolve: ResolveDeferred) {
+        self.deferred.push(resolve);
+    }
+
+    /// Take the operations in the current encoder.
+    pub(crate) fn take_operations(&mut self) -> Vec<PendingOperation> {
+        core::mem::take(&mut self.operations)
+    }
+
     pub(crate) fn extend_encoder(&mut self, other: &EncodedData) {
         // Manually extend to avoid adding an extra message type byte
         self.encoder.u8_buf.extend_from_slice(&other.u8_buf[1..]);
@@ -235,10 +712,39 @@ impl Runtime {
     pub(crate) fn insert_object<T: 'static>(&mut self, obj: T) -> u32 {
         let handle = self.next_object_handle;
         self.next_object_handle = self.next_object_handle.wrapping_add(1);
-        self.objects.insert(handle, Box::new(RefCell::new(obj)));
+        self.objects
+            .insert(handle, Box::new(Rc::new(RefCell::new(obj))));
         handle
     }
 
+    /// Lend an exported object to JS until [`Runtime::release_lent_objects`] and
+    /// return its handle.
+    ///
+    /// `obj` must be borrowed for the whole synchronous JS call it is passed to.
+    /// The call's [`LendScope`] releases the handle when the call ends, so JS
+    /// can't reach the object after that.
+    pub(crate) fn lend_object<T: 'static>(&mut self, obj: *const T) -> u32 {
+        let handle = self.next_object_handle;
+        self.next_object_handle = self.next_object_handle.wrapping_add(1);
+        self.objects.insert(handle, Box::new(LentObject(obj)));
+        self.lent_objects.push(handle);
+        handle
+    }
+
+    /// The number of objects lent to JS calls that are running.
+    pub(crate) fn lent_object_count(&self) -> usize {
+        self.lent_objects.len()
+    }
+
+    /// Take back the objects lent to JS after the first `count`, once the call
+    /// they were lent to returned.
+    pub(crate) fn release_lent_objects(&mut self, count: usize) {
+        let count = count.min(self.lent_objects.len());
+        for handle in self.lent_objects.split_off(count) {
+            self.objects.remove(&handle);
+        }
+    }
+
     /// Get a thread-local variable.
     pub(crate) fn take_thread_local<T: 'static>(&mut self, key: ThreadLocalKey<'static>) -> T {
         *self
@@ -264,24 +770,91 @@ impl Runtime {
     }
 
     /// Get a reference to an exported object.
-    pub(crate) fn get_object<T: 'static>(&self, handle: u32) -> Ref<'_, T> {
+    pub(crate) fn get_object<T: 'static>(&self, handle: u32) -> ObjectRef<'_, T> {
         let boxed = self.objects.get(&handle).expect("invalid handle");
-        let cell = boxed.downcast_ref::<RefCell<T>>().expect("type mismatch");
-        cell.borrow()
+        if let Some(lent) = boxed.downcast_ref::<LentObject<T>>() {
+            // SAFETY: The object is in the store, so the call it was lent to is still
+            // running and the borrow is alive. The reference borrows the runtime, so
+            // the object can't be released while it is used
+            return ObjectRef::Lent(unsafe { &*lent.0 });
+        }
+        let cell = boxed
+            .downcast_ref::<Rc<RefCell<T>>>()
+            .expect("type mismatch");
+        ObjectRef::Owned(crate::object_store::borrow_object(cell))
     }
 
     /// Get a mutable reference to an exported object.
     pub(crate) fn get_object_mut<T: 'static>(&self, handle: u32) -> RefMut<'_, T> {
         let boxed = self.objects.get(&handle).expect("invalid handle");
-        let cell = boxed.downcast_ref::<RefCell<T>>().expect("type mismatch");
-        cell.borrow_mut()
+        assert!(
+            !boxed.is::<LentObject<T>>(),
+            "cannot mutate a Rust object lent to JS"
+        );
+        let cell = boxed
+            .downcast_ref::<Rc<RefCell<T>>>()
+            .expect("type mismatch");
+        crate::object_store::borrow_object_mut(cell)
     }
 
     /// Remove an exported object and return it.
     pub(crate) fn remove_object<T: 'static>(&mut self, handle: u32) -> T {
+        let boxed = self.objects.get(&handle).expect("invalid handle");
+        assert!(
+            !boxed.is::<LentObject<T>>(),
+            "cannot take ownership of a Rust object lent to JS"
+        );
         let boxed = self.objects.remove(&handle).expect("invalid handle");
-        let cell = boxed.downcast::<RefCell<T>>().expect("type mismatch");
-        cell.into_inner()
+        let cell = boxed.downcast::<Rc<RefCell<T>>>().expect("type mismatch");
+        Rc::try_unwrap(*cell)
+            .unwrap_or_else(|_| {
+                panic!("cannot take ownership of a Rust object an async method is using")
+            })
+            .into_inner()
+    }
+
+    /// Share an exported object with an async method, which keeps it alive
+    /// until the method's future finishes.
+    pub(crate) fn share_object<T: 'static>(&self, handle: u32) -> Rc<RefCell<T>> {
+        let boxed = self.objects.get(&handle).expect("invalid handle");
+        assert!(
+            !boxed.is::<LentObject<T>>(),
+            "cannot call an async method on a Rust object lent to JS"
+        );
+        boxed
+            .downcast_ref::<Rc<RefCell<T>>>()
+            .expect("type mismatch")
+            .clone()
+    }
+
+    /// Take an exported object out of the store while Rust borrows it, see
+    /// [`ObjectAnchor`](crate::object_store::ObjectAnchor). Lent objects stay in
+    /// the store and are returned as a pointer.
+    pub(crate) fn take_object_for_borrow<T: 'static>(
+        &mut self,
+        handle: u32,
+    ) -> Result<T, *const T> {
+        let boxed = self.objects.get(&handle).expect("invalid handle");
+        if let Some(lent) = boxed.downcast_ref::<LentObject<T>>() {
+            return Err(lent.0);
+        }
+        Ok(self.remove_object(handle))
+    }
+
+    /// Put an object taken with [`Runtime::take_object_for_borrow`] back under its handle.
+    pub(crate) fn restore_object<T: 'static>(&mut self, handle: u32, obj: T) {
+        self.objects
+            .insert(handle, Box::new(Rc::new(RefCell::new(obj))));
+    }
+
+    /// Queue a future for [`crate::runtime::spawn_local`] to start.
+    pub(crate) fn push_local_task(&mut self, task: Pin<Box<dyn Future<Output = ()>>>) {
+        self.local_tasks.push_back(task);
+    }
+
+    /// Take the oldest future queued with [`Runtime::push_local_task`].
+    pub(crate) fn pop_local_task(&mut self) -> Option<Pin<Box<dyn Future<Output = ()>>>> {
+        self.local_tasks.pop_front()
     }
 
     /// Remove an exported object without returning it.
@@ -298,6 +871,11 @@ impl Runtime {
     pub(crate) fn webview_id(&self) -> u64 {
         self.webview_id
     }
+
+    /// The queue of values dropped off this runtime's thread.
+    pub(crate) fn foreign_drops(&self) -> &Arc<ForeignDrops> {
+        &self.foreign_drops
+    }
 }
 
 thread_local! {
@@ -334,6 +912,21 @@ pub(crate) fn with_runtime<R>(f: impl FnOnce(&mut Runtime) -> R) -> R {
     })
 }
 
+/// Check if Rust is currently running a call from JS, like a callback or an export.
+pub(crate) fn in_js_call() -> bool {
+    RUNTIME.with(|state| state.borrow().last().is_some_and(Runtime::in_js_call))
+}
+
+/// The page load values created on this thread belong to, or 0 outside of a runtime.
+pub(crate) fn current_generation() -> u32 {
+    RUNTIME.with(|state| state.borrow().last().map_or(0, Runtime::generation))
+}
+
+/// The id of the webview whose runtime is running on this thread, if any.
+pub(crate) fn current_webview_id() -> Option<u64> {
+    RUNTIME.with(|state| state.borrow().last().map(Runtime::webview_id))
+}
+
 /// Check if we're currently inside a batch() call
 pub fn is_batching() -> bool {
     with_runtime(|state| state.is_batching())
@@ -341,7 +934,9 @@ pub fn is_batching() -> bool {
 
 /// Queue a JS drop operation for a heap ID.
 /// This is called when a JsValue is dropped.
-pub(crate) fn queue_js_drop(id: u64) {
+///
+/// Values from before the webview reloaded are already gone in JS, so they are ignored.
+pub(crate) fn queue_js_drop(id: u64, generation: u32) {
     debug_assert!(
         id >= JSIDX_RESERVED,
         "Attempted to drop reserved JS heap ID {id}"
@@ -353,12 +948,79 @@ pub(crate) fn queue_js_drop(id: u64) {
         return;
     }
 
-    let id = with_runtime(|state| state.release_heap_id(id));
+    let id = with_runtime(|state| {
+        if state.generation() != generation {
+            return None;
+        }
+        let id = state.release_heap_id(id)?;
+        // Sending the drop now would block until JS responds to the async call
+        if state.in_flight.is_some() && !state.in_js_call() {
+            state.pending_drops.push(id);
+            return None;
+        }
+        Some(id)
+    });
     if let Some(id) = id {
         crate::js_helpers::js_drop_heap_ref(id);
     }
 }
 
+/// Block until JS responds to the async call in flight, then send the drops
+/// queued while it was. New batches can't be sent before that, or JS would
+/// allocate heap IDs in a different order than Rust.
+///
+/// JS is waiting on Rust during a call from JS, so nothing is sent there until it returns.
+fn finish_async_call() {
+    if in_js_call() {
+        return;
+    }
+    crate::runtime::finish_in_flight();
+    let ids = with_runtime(|state| core::mem::take(&mut state.pending_drops));
+    for id in ids {
+        crate::js_helpers::js_drop_heap_ref(id);
+    }
+}
+
+/// Get the runtime ready to send a new operation to JS.
+fn prepare_js_call() {
+    finish_async_call();
+    // Calls made after the webview reloaded go to the new page
+    crate::runtime::check_reload();
+    // Free values whose handles were dropped on other threads
+    crate::send::apply_foreign_drops();
+}
+
+/// Encode an operation into the current batch.
+///
+/// Returns `true` if an argument needs the batch to be sent before the call returns.
+fn encode_operation(
+    location: &'static Location<'static>,
+    fn_id: u32,
+    add_args: impl FnOnce(&mut EncodedData),
+) -> bool {
+    // We take the current encoder out of the thread-local state to avoid borrowing issues
+    // and then put it back after adding the operation. Drops or other calls may happen while
+    // we are encoding, but they should be queued after this operation.
+    let (mut batch, mut operations) = with_runtime(|state| {
+        // Push a new operation into the batch
+        state.push_ids_to_free();
+        (state.take_encoder(), state.take_operations())
+    });
+    add_operation(&mut batch, fn_id, add_args);
+    operations.push(PendingOperation { fn_id, location });
+
+    // Check if any encoded argument requires immediate flush (e.g., stack-allocated callbacks)
+    let needs_flush = batch.needs_flush;
+
+    with_runtime(|state| {
+        let encoded_during_op = core::mem::replace(&mut state.encoder, batch);
+        state.extend_encoder(&encoded_during_op);
+        let added_during_op = core::mem::replace(&mut state.operations, operations);
+        state.operations.extend(added_during_op);
+    });
+    needs_flush
+}
+
 /// Add an operation to the current batch.
 pub(crate) fn add_operation(
     encoder: &mut EncodedData,
@@ -375,28 +1037,32 @@ pub(crate) fn add_operation(
 /// 1. Encode the current evaluate message into the current batch
 /// 2. If the return value is needed immediately, flush the batch and return the result
 /// 3. Otherwise get the pending result from BatchableResult
+///
+/// If JS throws while running the operation, this panics with the JS exception
+/// and the location of the caller.
+#[track_caller]
 pub(crate) fn run_js_sync<R: BatchableResult>(
     fn_id: u32,
     add_args: impl FnOnce(&mut EncodedData),
 ) -> R {
-    // Step 1: Encode the operation into the batch and get placeholder for non-flush types
-    // We take the current encoder out of the thread-local state to avoid borrowing issues
-    // and then put it back after adding the operation. Drops or other calls may happen while
-    // we are encoding, but they should be queued after this operation.
-    let mut batch = with_runtime(|state| {
-        // Push a new operation into the batch
-        state.push_ids_to_free();
-        state.take_encoder()
-    });
-    add_operation(&mut batch, fn_id, add_args);
+    run_js_at(Location::caller(), fn_id, add_args)
+}
 
-    // Check if any encoded argument requires immediate flush (e.g., stack-allocated callbacks)
-    let needs_flush = batch.needs_flush;
+/// [`run_js_sync`] for a call from `location`.
+pub(crate) fn run_js_at<R: BatchableResult>(
+    location: &'static Location<'static>,
+    fn_id: u32,
+    add_args: impl FnOnce(&mut EncodedData),
+) -> R {
+    prepare_js_call();
+    // Values created by the call are attributed to the caller in leak checks
+    let outer_site = with_runtime(|state| state.set_alloc_site(Some(location)));
 
-    with_runtime(|state| {
-        let encoded_during_op = core::mem::replace(&mut state.encoder, batch);
-        state.extend_encoder(&encoded_during_op);
-    });
+    // Objects lent to this call are taken back once it returns or unwinds
+    let lent = LendScope::new();
+
+    // Step 1: Encode the operation into the batch and get placeholder for non-flush types
+    let needs_flush = encode_operation(location, fn_id, add_args);
 
     // Try to get a placeholder for opaque types that don't need flush
     // This also increments opaque_count to keep heap IDs in sync
@@ -419,7 +1085,11 @@ pub(crate) fn run_js_sync<R: BatchableResult>(
     };
 
     // After running, free any queued IDs for this operation
-    let ids = with_runtime(|state| state.pop_and_release_ids());
+    let ids = with_runtime(|state| {
+        state.set_alloc_site(outer_site);
+        state.pop_and_release_ids()
+    });
+    drop(lent);
     for id in ids {
         crate::js_helpers::js_drop_heap_ref(id);
     }
@@ -427,6 +1097,114 @@ pub(crate) fn run_js_sync<R: BatchableResult>(
     result
 }
 
+/// Add an operation to the batch without flushing, and decode its result into a
+/// [`Deferred`] when the batch is sent.
+#[track_caller]
+pub(crate) fn run_js_deferred<R: BinaryDecode + 'static>(
+    fn_id: u32,
+    add_args: impl FnOnce(&mut EncodedData),
+) -> Deferred<R> {
+    let mut deferred = None;
+    run_js_sync::<()>(fn_id, |encoder| {
+        add_args(encoder);
+        // Reserve the slot once nothing else can be added to the batch before the operation
+        deferred = Some(Deferred::reserve());
+    });
+    deferred.expect("The operation was encoded")
+}
+
+/// Like [`run_js_sync`], but send the batch right away and wait for JS to respond
+/// without blocking the thread.
+///
+/// Only one async call is in flight at a time. Other async calls wait for it
+/// before they are sent, and sync calls block until JS responds to it, so JS
+/// runs everything in the order Rust sent it. Callbacks JS makes meanwhile run
+/// while the future is polled. There is no timeout, the future waits until JS
+/// responds or the webview reloads.
+pub(crate) async fn run_js_async<R: BatchableResult + 'static>(
+    location: &'static Location<'static>,
+    fn_id: u32,
+    add_args: impl FnOnce(&mut EncodedData),
+) -> R {
+    // JS is waiting on this thread and can't respond to anything until the call from JS returns
+    if in_js_call() {
+        return run_js_at(location, fn_id, add_args);
+    }
+    core::future::poll_fn(crate::runtime::poll_in_flight).await;
+    prepare_js_call();
+
+    // Encode the call like a batched operation, so a JS object result takes a
+    // reserved placeholder ID
+    let (outer_site, was_batching) = with_runtime(|state| {
+        let was_batching = state.is_batching();
+        state.set_batching(true);
+        (state.set_alloc_site(Some(location)), was_batching)
+    });
+    let lent = with_runtime(|state| state.lent_object_count());
+    encode_operation(location, fn_id, add_args);
+    debug_assert_eq!(
+        lent,
+        with_runtime(|state| state.lent_object_count()),
+        "async calls can't borrow objects for the call"
+    );
+    let placeholder = with_runtime(|state| R::try_placeholder(state));
+    // Values passed by value are dropped in the same batch, after the call
+    let ids = with_runtime(|state| state.pop_and_release_ids());
+    for id in ids {
+        crate::js_helpers::js_drop_heap_ref(id);
+    }
+
+    let result = Rc::new(RefCell::new(None));
+    let respond: RespondAsync = Box::new({
+        let result = result.clone();
+        move |response: Result<&mut DecodedData, String>| {
+            let response = response.map(|data| {
+                let value = placeholder
+                    .unwrap_or_else(|| R::decode(data).expect("Failed to decode return value"));
+                assert!(
+                    data.is_empty(),
+                    "Extra data remaining after decoding response"
+                );
+                value
+            });
+            *result.borrow_mut() = Some(response);
+        }
+    });
+    let (message, pending) = with_runtime(|state| {
+        state.set_batching(was_batching);
+        state.set_alloc_site(outer_site);
+        let (message, freed) = state.take_message();
+        let operations = state.take_operations();
+        let pending = operations.len();
+        state.in_flight = Some(InFlight {
+            operations,
+            freed,
+            deferred: core::mem::take(&mut state.deferred),
+            respond,
+            wakers: Vec::new(),
+        });
+        (message, pending)
+    });
+    let (proxy, webview_id) =
+        with_runtime(|runtime| (runtime.ipc().proxy.clone(), runtime.webview_id()));
+    proxy(crate::runtime::WryBindgenEvent::ipc(webview_id, message));
+
+    let response = core::future::poll_fn(|cx| {
+        if let Some(response) = result.borrow_mut().take() {
+            return Poll::Ready(Some(response));
+        }
+        ready!(crate::runtime::poll_in_flight(cx));
+        // The runtime was reset for a new page if the call finished without a result
+        Poll::Ready(result.borrow_mut().take())
+    })
+    .await;
+    match response {
+        Some(Ok(value)) => value,
+        Some(Err(exception)) => panic!("{exception}"),
+        None => crate::runtime::panic_reloaded(pending),
+    }
+}
+
 /// Flush the current batch and return the decoded result.
 pub(crate) fn flush_and_return<R: BinaryDecode>() -> R {
     flush_and_then(|mut data| {
@@ -442,15 +1220,56 @@ pub(crate) fn flush_and_return<R: BinaryDecode>() -> R {
 pub(crate) fn flush_and_then<R>(then: impl for<'a> Fn(DecodedData<'a>) -> R) -> R {
     use crate::runtime::WryBindgenEvent;
 
-    let batch_msg = with_runtime(|state| state.take_message());
+    finish_async_call();
+    let pending = with_runtime(|state| state.operations.len());
+    if crate::runtime::check_reload() {
+        // Everything in the batch was meant for the old page
+        crate::runtime::panic_reloaded(pending);
+    }
 
-    // Send and wait for result
-    with_runtime(|runtime| {
-        (runtime.ipc().proxy)(WryBindgenEvent::ipc(runtime.webview_id(), batch_msg))
+    let ((batch_msg, freed), operations, deferred, timeout) = with_runtime(|state| {
+        (
+            state.take_message(),
+            state.take_operations(),
+            core::mem::take(&mut state.deferred),
+            state.current_flush_timeout(),
+        )
     });
+    // The results of deferred operations come before the result the flush waits for
+    let deferred = Cell::new(deferred);
+    let then = |mut data: DecodedData| {
+        for resolve in deferred.take() {
+            resolve(&mut data);
+        }
+        then(data)
+    };
+
+    // Send and wait for result. The proxy is called outside of the runtime borrow so
+    // in-process backends can call back into Rust while handling the message.
+    let (proxy, webview_id) =
+        with_runtime(|runtime| (runtime.ipc().proxy.clone(), runtime.webview_id()));
+    proxy(WryBindgenEvent::ipc(webview_id, batch_msg));
     loop {
-        if let Some(result) = crate::runtime::progress_js_with(&then) {
-            return result;
+        match crate::runtime::progress_js_with(&operations, timeout.map(|(t, _)| t), then) {
+            Ok(Some(result)) => {
+                with_runtime(|state| state.confirm_frees(freed));
+                return result;
+            }
+            Ok(None) => {}
+            Err(timeout_after) => {
+                let error = FlushTimeout {
+                    webview_id,
+                    timeout: timeout_after,
+                    operations,
+                };
+                with_runtime(|state| state.abandon_response());
+                proxy(WryBindgenEvent::flush_timed_out(webview_id, error.clone()));
+                if let Some((_, true)) = timeout {
+                    // batch_with_timeout catches this and returns the error
+                    std::panic::resume_unwind(Box::new(error));
+                }
+                panic!("{error}");
+            }
         }
     }
 }
@@ -477,6 +1296,50 @@ pub fn batch<R, F: FnOnce() -> R>(f: F) -> R {
     result
 }
 
+/// Like [`batch`], but fail with a [`FlushTimeout`] instead of hanging if JS does not
+/// respond to a flush inside the batch within `timeout`.
+///
+/// The timeout is the longest JS may go without sending Rust any message, so
+/// callbacks JS runs during a flush do not count against it. If JS responds
+/// after the timeout, the late response is ignored.
+pub fn batch_with_timeout<R>(timeout: Duration, f: impl FnOnce() -> R) -> Result<R, FlushTimeout> {
+    let frame = with_runtime(|state| {
+        let frame = state.unwind_frame();
+        state.batch_timeout = Some(timeout);
+        frame
+    });
+
+    let result = std::panic::catch_unwind(AssertUnwindSafe(|| batch(f)));
+
+    match result {
+        Ok(result) => {
+            with_runtime(|state| state.batch_timeout = frame.batch_timeout);
+            Ok(result)
+        }
+        Err(payload) => match payload.downcast::<FlushTimeout>() {
+            Ok(error) => {
+                let ids = with_runtime(|state| state.unwind_to(frame));
+                for id in ids {
+                    crate::js_helpers::js_drop_heap_ref(id);
+                }
+                Err(*error)
+            }
+            Err(payload) => {
+                with_runtime(|state| state.batch_timeout = frame.batch_timeout);
+                std::panic::resume_unwind(payload)
+            }
+        },
+    }
+}
+
+/// Set how long flushes on the current runtime wait without hearing from JS before
+/// they panic with a [`FlushTimeout`]. `None` waits forever, which is the default.
+///
+/// Use [`batch_with_timeout`] to handle the timeout as an error instead.
+pub fn set_flush_timeout(timeout: Option<Duration>) {
+    with_runtime(|state| state.set_flush_timeout(timeout));
+}
+
 /// Like `batch`, but async.
 pub fn batch_async<'a, R, F: core::future::Future<Output = R> + 'a>(
     f: F,
@@ -485,6 +1348,22 @@ pub fn batch_async<'a, R, F: core::future::Future<Output = R> + 'a>(
     std::future::poll_fn(move |ctx| batch(|| f.as_mut().poll(ctx)))
 }
 
+/// Run the app future, batching the operations of each poll like [`batch_async`]
+/// if `auto_batch` is set.
+///
+/// Operations that return placeholder types are sent when the poll ends, or
+/// earlier if the app needs a result from JS.
+pub(crate) fn auto_batch<'a, F: core::future::Future<Output = ()> + 'a>(
+    app: F,
+    auto_batch: bool,
+) -> impl core::future::Future<Output = ()> + 'a {
+    if auto_batch {
+        Either::Left(batch_async(app))
+    } else {
+        Either::Right(app)
+    }
+}
+
 pub fn force_flush() {
     let has_pending = with_runtime(|state| !state.is_empty());
     if has_pending {
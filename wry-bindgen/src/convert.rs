@@ -119,6 +119,11 @@ pub trait RefFromBinaryDecode {
 
     /// Decode a reference anchor from binary data.
     fn ref_decode(decoder: &mut DecodedData) -> Result<Self::Anchor, DecodeError>;
+
+    /// Encode the type definition JS uses to pass a borrowed value.
+    fn encode_ref_type_def(buf: &mut alloc::vec::Vec<u8>) {
+        buf.push(crate::encode::TypeTag::BorrowedRef as u8);
+    }
 }
 
 /// Anchor type for JsCast references.
@@ -0,0 +1,85 @@
+//! Results of JS calls that are read after the batch they are in is sent.
+
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use core::cell::RefCell;
+use core::fmt;
+
+use crate::batch::{force_flush, with_runtime};
+use crate::encode::BinaryDecode;
+use crate::ipc::DecodedData;
+
+/// Decodes a deferred result from a Respond message into its [`Deferred`].
+pub(crate) type ResolveDeferred = Box<dyn FnOnce(&mut DecodedData)>;
+
+/// The result of a JS call that doesn't make the batch flush.
+///
+/// Calls that return values like numbers, bools or strings normally flush the
+/// current batch to get the result. [`JSFunction::call_deferred`](crate::JSFunction)
+/// queues the call like any other batched operation instead, and the result is
+/// filled in when the batch is sent. Many reads can be queued and resolved in
+/// one round trip.
+///
+/// Calls that return JS objects already batch without flushing, use the normal
+/// `call` for those.
+///
+/// # Example
+///
+/// ```ignore
+/// let (width, height) = batch(|| {
+///     let width = GET_WIDTH.call_deferred(&element);
+///     let height = GET_HEIGHT.call_deferred(&element);
+///     (width, height)
+/// });
+/// // Both were read in the same message
+/// set_size(width.get() * 2.0, height.get() * 2.0);
+/// ```
+pub struct Deferred<T> {
+    value: Rc<RefCell<Option<T>>>,
+}
+
+impl<T: BinaryDecode + 'static> Deferred<T> {
+    /// Reserve a slot for the result of the next operation added to the batch.
+    pub(crate) fn reserve() -> Self {
+        let value = Rc::new(RefCell::new(None));
+        let resolve: ResolveDeferred = Box::new({
+            let value = value.clone();
+            move |data| {
+                let result = T::decode(data).expect("Failed to decode deferred value");
+                *value.borrow_mut() = Some(result);
+            }
+        });
+        with_runtime(|runtime| runtime.push_deferred(resolve));
+        Self { value }
+    }
+}
+
+impl<T> Deferred<T> {
+    /// Check if the batch with the call was sent and the result is available.
+    pub fn is_resolved(&self) -> bool {
+        self.value.borrow().is_some()
+    }
+
+    /// Get the result, flushing the current batch if it hasn't been sent yet.
+    ///
+    /// Panics if the batch with the call threw, timed out, or was sent to a page
+    /// that reloaded before it responded.
+    #[track_caller]
+    pub fn get(self) -> T {
+        if let Some(value) = self.value.take() {
+            return value;
+        }
+        force_flush();
+        self.value
+            .take()
+            .expect("The batch with this deferred call failed before JS responded")
+    }
+}
+
+impl<T> fmt::Debug for Deferred<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Deferred")
+            .field("resolved", &self.is_resolved())
+            .finish()
+    }
+}
@@ -18,10 +18,21 @@ use crate::value::JsValue;
 
 /// Trait for encoding Rust values into the binary protocol.
 /// Each type specifies how to serialize itself.
+#[diagnostic::on_unimplemented(
+    note = "exported structs passed by reference and closures on the stack can't be passed to `call_async`, since JS borrows them for the whole call"
+)]
 pub trait BinaryEncode<P = ()> {
     fn encode(self, encoder: &mut EncodedData);
 }
 
+/// The [`BinaryEncode`] marker of arguments JS can only use until the call they
+/// are passed to returns: exported structs passed by reference, and closures
+/// on the stack.
+///
+/// Only calls that finish before returning to Rust take them, so
+/// [`JSFunction::call_async`](crate::JSFunction::call_async) doesn't.
+pub struct BorrowedForCall;
+
 /// Trait for decoding values from the binary protocol.
 /// Each type specifies how to deserialize itself.
 pub trait BinaryDecode: Sized {
@@ -61,6 +72,12 @@ pub(crate) const TYPE_CACHED: u8 = 0xFF;
 /// Format: [TYPE_FULL] [type_id: u32] [param_count: u8] [param TypeDefs...] [return TypeDef]
 pub(crate) const TYPE_FULL: u8 = 0xFE;
 
+/// Marker sent in place of a string's length when the string was interned with
+/// [`intern`](crate::intern). The heap id of the interned JS string follows it.
+/// Format: [INTERNED_STR: u32] [heap_id: u64]
+#[cfg(any(feature = "enable-interning", feature = "fake"))]
+pub(crate) const INTERNED_STR: u32 = u32::MAX;
+
 /// Type tags for the binary type definition protocol.
 /// Used to encode type information that JavaScript can parse to create TypeClass instances.
 #[repr(u8)]
@@ -104,6 +121,45 @@ pub enum TypeTag {
     /// Format: [StringEnum tag] [variant_count: u8] [for each: string_len: u32, string_bytes...]
     /// Values encode as u32 discriminant. JS decodes using the lookup array.
     StringEnum = 24,
+    /// Exported Rust struct: encodes as the u32 handle of the object in the store.
+    /// Format: [RustObject tag] [borrowed: u8] [class_name_len: u32, class_name_bytes...]
+    /// JS wraps the handle in an instance of the exported class.
+    RustObject = 25,
+}
+
+impl TypeTag {
+    /// Get the tag for a type definition byte.
+    pub(crate) fn from_u8(tag: u8) -> Option<Self> {
+        const TAGS: [TypeTag; 26] = [
+            TypeTag::Null,
+            TypeTag::Bool,
+            TypeTag::U8,
+            TypeTag::U16,
+            TypeTag::U32,
+            TypeTag::U64,
+            TypeTag::U128,
+            TypeTag::I8,
+            TypeTag::I16,
+            TypeTag::I32,
+            TypeTag::I64,
+            TypeTag::I128,
+            TypeTag::F32,
+            TypeTag::F64,
+            TypeTag::Usize,
+            TypeTag::Isize,
+            TypeTag::String,
+            TypeTag::HeapRef,
+            TypeTag::Callback,
+            TypeTag::Option,
+            TypeTag::Result,
+            TypeTag::Array,
+            TypeTag::BorrowedRef,
+            TypeTag::U8Clamped,
+            TypeTag::StringEnum,
+            TypeTag::RustObject,
+        ];
+        TAGS.get(tag as usize).copied()
+    }
 }
 
 /// Trait for types that can encode their type definition into the binary protocol.
@@ -439,8 +495,19 @@ impl<T: EncodeTypeDef> EncodeTypeDef for &T {
 
 impl BinaryEncode for &str {
     fn encode(self, encoder: &mut EncodedData) {
-        encoder.push_str(self);
+        encode_str(self, encoder);
+    }
+}
+
+/// Encode a string, or a reference to the JS string if it was interned.
+fn encode_str(value: &str, encoder: &mut EncodedData) {
+    #[cfg(feature = "enable-interning")]
+    if let Some(id) = crate::intern::unsafe_get_str(value) {
+        encoder.push_u32(INTERNED_STR);
+        encoder.push_u64(id);
+        return;
     }
+    encoder.push_str(value);
 }
 
 impl EncodeTypeDef for String {
@@ -451,7 +518,7 @@ impl EncodeTypeDef for String {
 
 impl BinaryEncode for String {
     fn encode(self, encoder: &mut EncodedData) {
-        encoder.push_str(&self);
+        encode_str(&self, encoder);
     }
 }
 
@@ -520,6 +587,22 @@ impl<T: BinaryDecode, E: BinaryDecode> BinaryDecode for Result<T, E> {
 
 impl<T: BinaryDecode, E: BinaryDecode> BatchableResult for Result<T, E> {}
 
+// Encoding for Result<T, E> returned from callbacks. JS throws the Err value.
+impl<T: BinaryEncode<P>, E: BinaryEncode, P> BinaryEncode<P> for Result<T, E> {
+    fn encode(self, encoder: &mut EncodedData) {
+        match self {
+            Ok(val) => {
+                encoder.push_u8(1);
+                val.encode(encoder);
+            }
+            Err(err) => {
+                encoder.push_u8(0);
+                err.encode(encoder);
+            }
+        }
+    }
+}
+
 impl EncodeTypeDef for JsValue {
     fn encode_type_def(buf: &mut Vec<u8>) {
         buf.push(TypeTag::HeapRef as u8);
@@ -528,7 +611,7 @@ impl EncodeTypeDef for JsValue {
 
 impl BinaryEncode for JsValue {
     fn encode(self, encoder: &mut EncodedData) {
-        encoder.push_u64(self.id());
+        encoder.push_u64(self.js_id());
     }
 }
 
@@ -537,14 +620,22 @@ impl BinaryDecode for JsValue {
         // JS value is always in sync with the dom. We should never need to decode it.
         // Use get_next_heap_id() (NOT get_next_placeholder_id()) because decode() is
         // called for callback parameters from JS, not for return value placeholders.
-        with_runtime(|runtime| Ok(JsValue::from_id(runtime.get_next_heap_id())))
+        with_runtime(|runtime| {
+            Ok(JsValue::from_heap_id(
+                runtime.get_next_heap_id(),
+                runtime.generation(),
+            ))
+        })
     }
 }
 
 impl BatchableResult for JsValue {
     fn try_placeholder(batch: &mut Runtime) -> Option<Self> {
         // Use get_next_placeholder_id() to track reserved slots for JS
-        Some(JsValue::from_id(batch.get_next_placeholder_id()))
+        Some(JsValue::from_heap_id(
+            batch.get_next_placeholder_id(),
+            batch.generation(),
+        ))
     }
 }
 
@@ -597,7 +688,7 @@ impl<T: BinaryEncode + CloneForEncode> BinaryEncode for &T {
 // When encoding JsValue references, encode the underlying ID
 impl BinaryEncode for &JsValue {
     fn encode(self, encoder: &mut EncodedData) {
-        encoder.push_u64(self.id());
+        encoder.push_u64(self.js_id());
     }
 }
 
@@ -785,7 +876,7 @@ macro_rules! impl_closure_ref_encode {
         }
 
         // Implement BinaryEncode for &mut dyn FnMut(...) -> R
-        impl<R, $($arg,)*> BinaryEncode for &mut dyn FnMut($($arg),*) -> R
+        impl<R, $($arg,)*> BinaryEncode<BorrowedForCall> for &mut dyn FnMut($($arg),*) -> R
             where
             $($arg: BinaryDecode + EncodeTypeDef + 'static, )*
             R: BinaryEncode + EncodeTypeDef + 'static,
@@ -848,7 +939,7 @@ macro_rules! impl_closure_ref_encode {
         }
 
         // Implement BinaryEncode for &dyn Fn(...) -> R (supports reentrant calls)
-        impl<R, $($arg,)*> BinaryEncode for &dyn Fn($($arg),*) -> R
+        impl<R, $($arg,)*> BinaryEncode<BorrowedForCall> for &dyn Fn($($arg),*) -> R
             where
             $($arg: BinaryDecode + EncodeTypeDef + 'static, )*
             R: BinaryEncode + EncodeTypeDef + 'static,
@@ -911,7 +1002,7 @@ macro_rules! impl_closure_ref_encode {
         }
 
         // Implement BinaryEncode for &mut dyn Fn(...) -> R (supports reentrant calls)
-        impl<R, $($arg,)*> BinaryEncode for &mut dyn Fn($($arg),*) -> R
+        impl<R, $($arg,)*> BinaryEncode<BorrowedForCall> for &mut dyn Fn($($arg),*) -> R
             where
             $($arg: BinaryDecode + EncodeTypeDef + 'static, )*
             R: BinaryEncode + EncodeTypeDef + 'static,
@@ -982,7 +1073,7 @@ macro_rules! impl_fnmut_stub_ref {
         #[allow(coherence_leak_check)]
         impl<R, $first, $($rest,)*> EncodeTypeDef for CallbackKey<fn(&$first, $($rest),*) -> R>
             where
-            $first: EncodeTypeDef + 'static,
+            $first: RefFromBinaryDecode + 'static,
             $($rest: EncodeTypeDef + 'static, )*
             R: EncodeTypeDef + 'static,
         {
@@ -997,7 +1088,7 @@ macro_rules! impl_fnmut_stub_ref {
                 )*
                 buf.push(count);
                 // Encode each argument type
-                buf.push(TypeTag::BorrowedRef as u8);
+                <$first as RefFromBinaryDecode>::encode_ref_type_def(buf);
                 $(<$rest as EncodeTypeDef>::encode_type_def(buf);)*
                 // Encode return type
                 <R as EncodeTypeDef>::encode_type_def(buf);
@@ -1077,7 +1168,7 @@ macro_rules! impl_fnmut_stub_ref {
             #[allow(non_snake_case)]
             #[allow(unused)]
             fn into_closure(self) -> crate::Closure<dyn Fn(&$first, $($rest),*) -> R> {
-                crate::Closure::wrap_encode_decode::<fn($first, $($rest),*) -> R>(
+                crate::Closure::wrap_encode_decode::<fn(&$first, $($rest),*) -> R>(
                     move |decoder: &mut DecodedData, encoder: &mut EncodedData| {
                         let anchor = <$first as RefFromBinaryDecode>::ref_decode(decoder).unwrap();
                         $(let $rest = <$rest as BinaryDecode>::decode(decoder).unwrap();)*
@@ -1262,9 +1353,9 @@ impl<T: BinaryDecode> BinaryDecode for Vec<T> {
 
 impl<T: BinaryDecode> BatchableResult for Vec<T> {}
 
-impl<T> BinaryEncode for &[T]
+impl<T, P> BinaryEncode<P> for &[T]
 where
-    for<'a> &'a T: BinaryEncode,
+    for<'a> &'a T: BinaryEncode<P>,
 {
     fn encode(self, encoder: &mut EncodedData) {
         encoder.push_u32(self.len() as u32);
@@ -1274,9 +1365,9 @@ where
     }
 }
 
-impl<T> BinaryEncode for &mut [T]
+impl<T, P> BinaryEncode<P> for &mut [T]
 where
-    for<'a> &'a T: BinaryEncode,
+    for<'a> &'a T: BinaryEncode<P>,
 {
     fn encode(self, encoder: &mut EncodedData) {
         encoder.push_u32(self.len() as u32);
@@ -0,0 +1,1219 @@
+//! An in-process fake of the JavaScript side for headless tests.
+//!
+//! [`FakeJs`] plugs into the same IPC layer as a webview, but answers every
+//! `Evaluate` message in Rust. Calls to imports are answered by closures
+//! registered against their [`JsFunctionSpec`] or import name, and arguments are
+//! decoded with the same type definitions the JS side parses. The fake heap
+//! mirrors `JSHeap`, so batching, placeholders and heap id allocation behave the
+//! same way they do in a webview.
+//!
+//! Each [`FakeJs::run`] gets its own runtime on the calling thread, so tests
+//! using the fake can run in parallel.
+//!
+//! ```ignore
+//! #[wasm_bindgen]
+//! extern "C" {
+//!     fn add(a: u32, b: u32) -> u32;
+//! }
+//!
+//! #[test]
+//! fn adds() {
+//!     let fake = FakeJs::new();
+//!     fake.on_import("add", |args| {
+//!         Ok(FakeValue::from(args[0].as_f64().unwrap() + args[1].as_f64().unwrap()))
+//!     });
+//!     fake.run(|| assert_eq!(add(1, 2), 3));
+//! }
+//! ```
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::fmt;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::batch::{Runtime, in_runtime};
+use crate::encode::INTERNED_STR;
+use crate::function::CALL_EXPORT_FN_ID;
+use crate::function_registry::{
+    FUNCTION_REGISTRY, InlineJsModule, JsClassMemberKind, JsClassMemberSpec, JsExportSpec,
+    JsFunctionSpec,
+};
+use crate::ipc::{
+    DecodeError, DecodedData, DecodedVariant, EncodedData, EvaluateHeader, HeapCheck, IPCMessage,
+    MessageType,
+};
+use crate::runtime::{AppEventVariant, IPCSenders, WryBindgenEvent, WryIPC};
+use crate::type_def::{FunctionType, TypeCache, TypeDef, decode_number, encode_number};
+use crate::value::{
+    JSIDX_FALSE, JSIDX_NULL, JSIDX_OFFSET, JSIDX_RESERVED, JSIDX_TRUE, JSIDX_UNDEFINED, JsValue,
+};
+
+/// A closure that answers calls to a JS function.
+///
+/// Returning `Err` is the fake equivalent of throwing. Imports marked with
+/// `catch` receive the error as their `Err` value, any other import panics.
+type Handler = Arc<dyn Fn(&[FakeValue]) -> Result<FakeValue, FakeValue> + Send + Sync>;
+
+/// A JS value on the fake side.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum FakeValue {
+    #[default]
+    Undefined,
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<FakeValue>),
+    Object(FakeObject),
+    Function(FakeFunction),
+}
+
+impl FakeValue {
+    /// Get the number if this is a number.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            FakeValue::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// Get the boolean if this is a boolean.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            FakeValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// Get the string if this is a string.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            FakeValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Get the object if this is an object.
+    pub fn as_object(&self) -> Option<&FakeObject> {
+        match self {
+            FakeValue::Object(obj) => Some(obj),
+            _ => None,
+        }
+    }
+
+    /// Get the function if this is a Rust callback.
+    pub fn as_function(&self) -> Option<&FakeFunction> {
+        match self {
+            FakeValue::Function(f) => Some(f),
+            _ => None,
+        }
+    }
+
+    /// The result of `typeof` for this value.
+    pub fn type_of(&self) -> &'static str {
+        match self {
+            FakeValue::Undefined => "undefined",
+            FakeValue::Bool(_) => "boolean",
+            FakeValue::Number(_) => "number",
+            FakeValue::String(_) => "string",
+            FakeValue::Null | FakeValue::Array(_) | FakeValue::Object(_) => "object",
+            FakeValue::Function(_) => "function",
+        }
+    }
+
+    /// Whether JS would treat this value as true.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            FakeValue::Undefined | FakeValue::Null => false,
+            FakeValue::Bool(b) => *b,
+            FakeValue::Number(n) => *n != 0.0 && !n.is_nan(),
+            FakeValue::String(s) => !s.is_empty(),
+            FakeValue::Array(_) | FakeValue::Object(_) | FakeValue::Function(_) => true,
+        }
+    }
+
+    /// Convert to a number like JS's `Number(value)`.
+    fn to_number(&self) -> f64 {
+        match self {
+            FakeValue::Null => 0.0,
+            FakeValue::Bool(b) => *b as u8 as f64,
+            FakeValue::Number(n) => *n,
+            FakeValue::String(s) if s.trim().is_empty() => 0.0,
+            FakeValue::String(s) => s.trim().parse().unwrap_or(f64::NAN),
+            _ => f64::NAN,
+        }
+    }
+
+    /// Convert to a string like JS's `String(value)`.
+    fn to_js_string(&self) -> String {
+        match self {
+            FakeValue::Undefined => "undefined".to_string(),
+            FakeValue::Null => "null".to_string(),
+            FakeValue::Bool(b) => b.to_string(),
+            FakeValue::Number(n) if n.is_nan() => "NaN".to_string(),
+            FakeValue::Number(n) if n.is_infinite() => {
+                let sign = if *n < 0.0 { "-" } else { "" };
+                format!("{sign}Infinity")
+            }
+            FakeValue::Number(n) => n.to_string(),
+            FakeValue::String(s) => s.clone(),
+            FakeValue::Array(items) => items
+                .iter()
+                .map(FakeValue::to_js_string)
+                .collect::<Vec<_>>()
+                .join(","),
+            FakeValue::Object(obj) if obj.is_error() => {
+                format!("Error: {}", obj.get("message").to_js_string())
+            }
+            FakeValue::Object(_) => "[object Object]".to_string(),
+            FakeValue::Function(_) => "function () { [native code] }".to_string(),
+        }
+    }
+
+    /// Compare like JS's `==`.
+    fn loose_eq(&self, other: &FakeValue) -> bool {
+        use FakeValue::*;
+        match (self, other) {
+            (Undefined | Null, Undefined | Null) => true,
+            (Undefined | Null, _) | (_, Undefined | Null) => false,
+            (Bool(_) | Number(_) | String(_), Bool(_) | Number(_) | String(_))
+                if core::mem::discriminant(self) != core::mem::discriminant(other) =>
+            {
+                self.to_number() == other.to_number()
+            }
+            _ => self == other,
+        }
+    }
+}
+
+macro_rules! fake_value_from_number {
+    ($($ty:ty),*) => {
+        $(
+            impl From<$ty> for FakeValue {
+                fn from(value: $ty) -> Self {
+                    FakeValue::Number(value as f64)
+                }
+            }
+        )*
+    };
+}
+
+fake_value_from_number!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, f32, f64);
+
+impl From<()> for FakeValue {
+    fn from(_: ()) -> Self {
+        FakeValue::Undefined
+    }
+}
+
+impl From<bool> for FakeValue {
+    fn from(value: bool) -> Self {
+        FakeValue::Bool(value)
+    }
+}
+
+impl From<&str> for FakeValue {
+    fn from(value: &str) -> Self {
+        FakeValue::String(value.to_string())
+    }
+}
+
+impl From<String> for FakeValue {
+    fn from(value: String) -> Self {
+        FakeValue::String(value)
+    }
+}
+
+impl From<Vec<FakeValue>> for FakeValue {
+    fn from(value: Vec<FakeValue>) -> Self {
+        FakeValue::Array(value)
+    }
+}
+
+impl From<FakeObject> for FakeValue {
+    fn from(value: FakeObject) -> Self {
+        FakeValue::Object(value)
+    }
+}
+
+impl From<FakeFunction> for FakeValue {
+    fn from(value: FakeFunction) -> Self {
+        FakeValue::Function(value)
+    }
+}
+
+impl<T: Into<FakeValue>> From<Option<T>> for FakeValue {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(FakeValue::Null, Into::into)
+    }
+}
+
+/// A JS object on the fake side.
+///
+/// Clones share the same properties, like references to an object in JS.
+#[derive(Clone, Default)]
+pub struct FakeObject {
+    properties: Arc<Mutex<BTreeMap<String, FakeValue>>>,
+}
+
+impl FakeObject {
+    /// Create an empty object.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an `Error` with the given message.
+    pub fn error(message: &str) -> Self {
+        let error = Self::new();
+        error.set("name", "Error");
+        error.set("message", message);
+        error
+    }
+
+    /// Get a property, or `undefined` if it is not set.
+    pub fn get(&self, name: &str) -> FakeValue {
+        lock(&self.properties)
+            .get(name)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Set a property.
+    pub fn set(&self, name: &str, value: impl Into<FakeValue>) {
+        lock(&self.properties).insert(name.to_string(), value.into());
+    }
+
+    /// Check if a property is set.
+    pub fn has(&self, name: &str) -> bool {
+        lock(&self.properties).contains_key(name)
+    }
+
+    fn is_error(&self) -> bool {
+        self.has("message") && self.get("name").as_str() == Some("Error")
+    }
+}
+
+impl PartialEq for FakeObject {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.properties, &other.properties)
+    }
+}
+
+impl fmt::Debug for FakeObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(lock(&self.properties).iter())
+            .finish()
+    }
+}
+
+/// A Rust callback that was passed to the fake JS side.
+#[derive(Clone)]
+pub struct FakeFunction {
+    key: u32,
+    ty: Arc<FunctionType>,
+    state: Weak<Mutex<FakeState>>,
+}
+
+impl FakeFunction {
+    /// Call the Rust callback the way JS would, and return its result.
+    ///
+    /// This must be called on the thread running [`FakeJs::run`], either from the
+    /// test itself or from inside a handler.
+    ///
+    /// # Panics
+    /// Panics if the callback throws. Use [`FakeFunction::try_call`] to get the thrown value.
+    pub fn call(&self, args: &[FakeValue]) -> FakeValue {
+        self.try_call(args)
+            .unwrap_or_else(|error| panic!("The Rust callback threw {error:?}"))
+    }
+
+    /// Call the Rust callback the way JS would.
+    ///
+    /// Returns the value JS would catch if the callback panicked, called `throw_val`
+    /// or returned `Err`. Handlers can use `?` to let the exception propagate.
+    pub fn try_call(&self, args: &[FakeValue]) -> Result<FakeValue, FakeValue> {
+        let state = self
+            .state
+            .upgrade()
+            .expect("The FakeJs this function was passed to was dropped");
+        call_rust(&state, &self.ty, args, |encoder| {
+            // Call argument function
+            encoder.push_u32(0);
+            encoder.push_u32(self.key);
+        })
+    }
+}
+
+impl PartialEq for FakeFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl fmt::Debug for FakeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("FakeFunction").field(&self.key).finish()
+    }
+}
+
+/// An in-process fake JS backend.
+///
+/// Handles are cheap to clone and share the same heap and handlers.
+#[derive(Clone)]
+pub struct FakeJs {
+    state: Arc<Mutex<FakeState>>,
+}
+
+impl Default for FakeJs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FakeJs {
+    /// Create a fake with handlers for the JS helpers wry-bindgen itself uses.
+    pub fn new() -> Self {
+        let state = Arc::new_cyclic(|this| Mutex::new(FakeState::new(this.clone())));
+        {
+            let helpers = format!(
+                "module_{}.",
+                InlineJsModule::new(include_str!("./js/convert.js")).hash()
+            );
+            let weak = Arc::downgrade(&state);
+            let mut state = lock(&state);
+            for (id, spec) in FUNCTION_REGISTRY.function_specs().iter().enumerate() {
+                if let Some(handler) = builtin(&(spec.js_code())(), &helpers, &weak) {
+                    state.handlers.insert(id as u32, handler);
+                }
+            }
+        }
+        Self { state }
+    }
+
+    /// Answer calls to the function registered with `spec`.
+    pub fn on(
+        &self,
+        spec: JsFunctionSpec,
+        handler: impl Fn(&[FakeValue]) -> Result<FakeValue, FakeValue> + Send + Sync + 'static,
+    ) -> &Self {
+        let id = FUNCTION_REGISTRY
+            .get_function::<()>(spec)
+            .unwrap_or_else(|| panic!("Function not found for code: {}", (spec.js_code())()))
+            .id();
+        lock(&self.state).handlers.insert(id, Arc::new(handler));
+        self
+    }
+
+    /// Answer calls to every `extern "C"` import with the given JS name.
+    ///
+    /// Methods, getters, setters and static methods are matched by their member
+    /// name, so a getter and setter for the same property share a handler. The
+    /// receiver is passed as the first argument.
+    pub fn on_import(
+        &self,
+        name: &str,
+        handler: impl Fn(&[FakeValue]) -> Result<FakeValue, FakeValue> + Send + Sync + 'static,
+    ) -> &Self {
+        self.on_matching(
+            |code| import_name(code) == Some(name),
+            &format!("No JS import named `{name}` is registered"),
+            Arc::new(handler),
+        )
+    }
+
+    /// Answer calls to the function with exactly this JS code, such as the code
+    /// passed to `__wry_call_js_function!`.
+    pub fn on_js(
+        &self,
+        code: &str,
+        handler: impl Fn(&[FakeValue]) -> Result<FakeValue, FakeValue> + Send + Sync + 'static,
+    ) -> &Self {
+        self.on_matching(
+            |js_code| js_code == code,
+            &format!("No JS function with code `{code}` is registered"),
+            Arc::new(handler),
+        )
+    }
+
+    fn on_matching(
+        &self,
+        matches: impl Fn(&str) -> bool,
+        missing: &str,
+        handler: Handler,
+    ) -> &Self {
+        let ids: Vec<u32> = FUNCTION_REGISTRY
+            .function_specs()
+            .iter()
+            .enumerate()
+            .filter(|(_, spec)| matches(&(spec.js_code())()))
+            .map(|(id, _)| id as u32)
+            .collect();
+        assert!(!ids.is_empty(), "{missing}");
+        let mut state = lock(&self.state);
+        for id in ids {
+            state.handlers.insert(id, handler.clone());
+        }
+        self
+    }
+
+    /// Run `f` with a runtime connected to this fake on the current thread.
+    pub fn run<R>(&self, f: impl FnOnce() -> R) -> R {
+        let state = self.state.clone();
+        let (ipc, senders) = WryIPC::new(Arc::new(move |event| handle_event(&state, event)));
+        lock(&self.state).senders = Some(senders);
+        let (_, out) = in_runtime(Runtime::new(ipc, 0), f);
+        lock(&self.state).senders = None;
+        out
+    }
+
+    /// Stop answering Rust, like JS stuck in a long running function.
+    ///
+    /// Messages Rust sends are queued until [`FakeJs::resume`].
+    pub fn pause(&self) {
+        lock(&self.state).paused.get_or_insert_with(Vec::new);
+    }
+
+    /// Answer the messages queued since [`FakeJs::pause`] and keep answering new ones.
+    pub fn resume(&self) {
+        let queued = lock(&self.state).paused.take().unwrap_or_default();
+        for message in queued {
+            evaluate_and_respond(&self.state, message);
+        }
+    }
+
+    /// Reload the page, like `location.reload()`.
+    ///
+    /// The fake starts over with an empty heap and type cache, the runtime is
+    /// reset for the new page and the [`on_reload`](crate::runtime::on_reload)
+    /// callbacks run. This must be called inside [`FakeJs::run`].
+    pub fn reload(&self) {
+        let senders = {
+            let mut state = lock(&self.state);
+            state.heap = FakeHeap::new();
+            state.types = TypeCache::default();
+            state.calling.clear();
+            state.response = None;
+            state.paused = None;
+            state.senders.clone().expect("FakeJs is not running")
+        };
+        senders.reload();
+        crate::runtime::handle_reload();
+    }
+
+    /// Call an exported Rust function the way JS would call
+    /// `window.__wbg_exports[name]`, and return its result or the value it threw.
+    ///
+    /// Members of exported classes are called by their export name, such as
+    /// `"Counter::increment"`. Members called on an object take the object's
+    /// handle as the first argument, and constructors return the new handle.
+    ///
+    /// This must be called inside [`FakeJs::run`].
+    pub fn call_export(&self, name: &str, args: &[FakeValue]) -> Result<FakeValue, FakeValue> {
+        let (signature, handle, args) = match inventory::iter::<JsExportSpec>()
+            .find(|export| export.name == name)
+            .and_then(JsExportSpec::signature)
+        {
+            Some(signature) => (signature, None, args),
+            None => {
+                let member = inventory::iter::<JsClassMemberSpec>()
+                    .find(|member| member.export_name() == name)
+                    .unwrap_or_else(|| panic!("No exported function named `{name}` is registered"));
+                match member.kind() {
+                    JsClassMemberKind::Method
+                    | JsClassMemberKind::Getter
+                    | JsClassMemberKind::Setter => {
+                        let (handle, args) = args
+                            .split_first()
+                            .expect("Methods take the object's handle as the first argument");
+                        (member.signature(), Some(handle.to_number() as u32), args)
+                    }
+                    _ => (member.signature(), None, args),
+                }
+            }
+        };
+        let mut types = Vec::new();
+        signature(&mut types);
+        let ty = FunctionType::parse(&mut DecodedData::from_u8s(&types))
+            .expect("Failed to parse the signature of the export");
+        call_rust(&self.state, &Arc::new(ty), args, |encoder| {
+            encoder.push_u32(CALL_EXPORT_FN_ID);
+            encoder.push_str(name);
+            if let Some(handle) = handle {
+                encoder.push_u32(handle);
+            }
+        })
+    }
+
+    /// Get the fake side of a JS value.
+    pub fn value_of(&self, value: &JsValue) -> FakeValue {
+        lock(&self.state).heap.get(value.id())
+    }
+
+    /// The number of values alive in the fake heap, like `jsHeap.heapObjectsAlive()`.
+    pub fn heap_objects_alive(&self) -> usize {
+        lock(&self.state).heap.live_count()
+    }
+}
+
+/// State shared between a [`FakeJs`], its IPC proxy and the functions it hands out.
+struct FakeState {
+    this: Weak<Mutex<FakeState>>,
+    heap: FakeHeap,
+    handlers: BTreeMap<u32, Handler>,
+    types: TypeCache,
+    /// The types of the callbacks the fake is currently calling, innermost last
+    calling: Vec<Arc<FunctionType>>,
+    /// The result of the innermost callback, or the value it threw
+    response: Option<Result<FakeValue, FakeValue>>,
+    /// Evaluate messages queued while the fake is paused
+    paused: Option<Vec<IPCMessage>>,
+    senders: Option<IPCSenders>,
+}
+
+impl FakeState {
+    fn new(this: Weak<Mutex<FakeState>>) -> Self {
+        Self {
+            this,
+            heap: FakeHeap::new(),
+            handlers: BTreeMap::new(),
+            types: TypeCache::default(),
+            calling: Vec::new(),
+            response: None,
+            paused: None,
+            senders: None,
+        }
+    }
+
+    fn handler(&self, fn_id: u32) -> Handler {
+        self.handlers.get(&fn_id).cloned().unwrap_or_else(|| {
+            panic!(
+                "No fake registered for JS function {fn_id}: {}",
+                js_code(fn_id)
+            )
+        })
+    }
+
+    /// Decode the response to the innermost callback the fake is calling.
+    fn callback_response(
+        &mut self,
+        message: &IPCMessage,
+    ) -> Result<Result<FakeValue, FakeValue>, DecodeError> {
+        let ty = self
+            .calling
+            .last()
+            .cloned()
+            .expect("Rust responded while the fake was not calling a callback");
+        Ok(match message.decoded()? {
+            DecodedVariant::Respond { mut data } => match &ty.ret {
+                // Callbacks returning Result throw the Err value
+                TypeDef::Result(ok, err) => match data.take_u8()? {
+                    1 => Ok(self.decode(ok, &mut data)?),
+                    _ => Err(self.decode(err, &mut data)?),
+                },
+                ret => Ok(self.decode(ret, &mut data)?),
+            },
+            // The callback panicked or threw
+            DecodedVariant::Throw { mut data } => Err(self.heap.get(data.take_u64()?)),
+            DecodedVariant::Evaluate { .. } => {
+                return Err(DecodeError::Custom(
+                    "expected a callback response".to_string(),
+                ));
+            }
+        })
+    }
+
+    /// Decode a value Rust encoded with the given type.
+    fn decode(&mut self, ty: &TypeDef, data: &mut DecodedData) -> Result<FakeValue, DecodeError> {
+        Ok(match ty {
+            TypeDef::Null => FakeValue::Null,
+            TypeDef::Bool => FakeValue::Bool(data.take_u8()? != 0),
+            TypeDef::Number(tag) => FakeValue::Number(decode_number(*tag, data)?),
+            // Strings Rust interned are sent as the heap id of the JS string
+            TypeDef::String if data.peek_u32() == Some(INTERNED_STR) => {
+                data.take_u32()?;
+                self.heap.get(data.take_u64()?)
+            }
+            TypeDef::String => FakeValue::String(data.take_str()?.to_string()),
+            TypeDef::HeapRef | TypeDef::BorrowedRef => self.heap.get(data.take_u64()?),
+            TypeDef::Callback(ty) => FakeValue::Function(FakeFunction {
+                key: data.take_u32()?,
+                ty: ty.clone(),
+                state: self.this.clone(),
+            }),
+            TypeDef::Option(inner) => match data.take_u8()? {
+                0 => FakeValue::Null,
+                _ => self.decode(inner, data)?,
+            },
+            TypeDef::Result(ok, err) => {
+                let result = FakeObject::new();
+                match data.take_u8()? {
+                    1 => result.set("ok", self.decode(ok, data)?),
+                    _ => result.set("err", self.decode(err, data)?),
+                }
+                FakeValue::Object(result)
+            }
+            TypeDef::Array(element) => {
+                let len = data.take_u32()?;
+                let mut items = Vec::with_capacity(len as usize);
+                for _ in 0..len {
+                    items.push(self.decode(element, data)?);
+                }
+                FakeValue::Array(items)
+            }
+            TypeDef::U8Clamped => {
+                let len = data.take_u32()?;
+                let mut items = Vec::with_capacity(len as usize);
+                for _ in 0..len {
+                    items.push(FakeValue::from(data.take_u8()?));
+                }
+                FakeValue::Array(items)
+            }
+            TypeDef::StringEnum(variants) => variants
+                .get(data.take_u32()? as usize)
+                .map_or(FakeValue::Undefined, |variant| {
+                    FakeValue::String(variant.clone())
+                }),
+            // Wrap the handle like `createWrapper`, objects Rust lends are marked as not owned
+            TypeDef::RustObject { class, .. } => {
+                let wrapper = FakeObject::new();
+                wrapper.set("__handle", data.take_u32()?);
+                wrapper.set("__className", class.as_str());
+                wrapper.set("__owned", data.take_u8()? != 0);
+                FakeValue::Object(wrapper)
+            }
+        })
+    }
+
+    /// Encode a value for Rust with the given type.
+    fn encode(&mut self, ty: &TypeDef, value: FakeValue, encoder: &mut EncodedData) {
+        match (ty, value) {
+            (TypeDef::Null, _) => {}
+            (TypeDef::Bool, value) => encoder.push_u8(value.is_truthy() as u8),
+            (TypeDef::Number(tag), value) => encode_number(*tag, value.to_number(), encoder),
+            (TypeDef::String, FakeValue::String(s)) => encoder.push_str(&s),
+            (TypeDef::HeapRef, value) => {
+                // Insert into the heap without encoding the id, Rust allocates the same id
+                self.heap.insert(value);
+            }
+            (TypeDef::BorrowedRef, value) => self.heap.add_borrowed_ref(value),
+            (TypeDef::Callback(_), FakeValue::Function(f)) => encoder.push_u32(f.key),
+            (TypeDef::Option(_), FakeValue::Undefined | FakeValue::Null) => encoder.push_u8(0),
+            (TypeDef::Option(inner), value) => {
+                encoder.push_u8(1);
+                self.encode(inner, value, encoder);
+            }
+            (TypeDef::Result(ok, _), FakeValue::Object(result)) if result.has("ok") => {
+                encoder.push_u8(1);
+                self.encode(ok, result.get("ok"), encoder);
+            }
+            (TypeDef::Result(_, err), FakeValue::Object(result)) if result.has("err") => {
+                encoder.push_u8(0);
+                self.encode(err, result.get("err"), encoder);
+            }
+            (TypeDef::Array(element), FakeValue::Array(items)) => {
+                encoder.push_u32(items.len() as u32);
+                for item in items {
+                    self.encode(element, item, encoder);
+                }
+            }
+            (TypeDef::U8Clamped, FakeValue::Array(items)) => {
+                encoder.push_u32(items.len() as u32);
+                for item in items {
+                    encoder.push_u8(item.to_number().clamp(0.0, 255.0) as u8);
+                }
+            }
+            (TypeDef::StringEnum(variants), FakeValue::String(s)) => {
+                // Unknown variants map to the __Invalid variant, like in JS
+                let index = variants.iter().position(|v| *v == s);
+                encoder.push_u32(index.unwrap_or(variants.len()) as u32);
+            }
+            (TypeDef::RustObject { borrowed, class }, FakeValue::Object(wrapper))
+                if wrapper.get("__className").as_str() == Some(class) =>
+            {
+                let handle = wrapper.get("__handle").to_number() as u32;
+                assert!(handle != 0, "{class} was already moved to Rust or freed");
+                // Passing the object by value moves it to Rust
+                if !borrowed {
+                    wrapper.set("__handle", 0);
+                }
+                encoder.push_u32(handle);
+            }
+            (ty, value) => panic!("Cannot encode {value:?} as {ty:?}"),
+        }
+    }
+}
+
+/// Call into Rust the way JS would, and return the result or the value Rust threw.
+///
+/// `target` encodes what to call, and the arguments are encoded with the params of `ty`.
+fn call_rust(
+    state: &Arc<Mutex<FakeState>>,
+    ty: &Arc<FunctionType>,
+    args: &[FakeValue],
+    target: impl FnOnce(&mut EncodedData),
+) -> Result<FakeValue, FakeValue> {
+    let message = {
+        let mut state = lock(state);
+        state.heap.push_borrow_frame();
+        state.calling.push(ty.clone());
+        let mut encoder = EncodedData::new();
+        encoder.push_u8(MessageType::Evaluate as u8);
+        target(&mut encoder);
+        for (i, param) in ty.params.iter().enumerate() {
+            state.encode(
+                param,
+                args.get(i).cloned().unwrap_or_default(),
+                &mut encoder,
+            );
+        }
+        IPCMessage::new(encoder.to_bytes())
+    };
+
+    let Ok(DecodedVariant::Evaluate { mut data }) = message.decoded() else {
+        unreachable!("the call message is always an evaluate message")
+    };
+    // Rust may call back into the fake while running the call
+    crate::runtime::handle_rust_callback(&mut data);
+
+    let mut state = lock(state);
+    let response = state
+        .response
+        .take()
+        .expect("Rust did not respond to the call");
+    state.calling.pop();
+    state.heap.pop_borrow_frame();
+    response
+}
+
+/// Run every operation in an Evaluate message, like `runEvaluate` in JS.
+fn evaluate(
+    state: &Arc<Mutex<FakeState>>,
+    data: &mut DecodedData,
+) -> Result<IPCMessage, DecodeError> {
+    let header = EvaluateHeader::decode(data)?;
+    let reserved_count = header.reserved;
+    {
+        let mut state = lock(state);
+        state.heap.push_reservation_scope(reserved_count);
+        state.heap.recycle(&header.recycled);
+        if let Some(check) = header.check
+            && let Err(error) = state.heap.check(check)
+        {
+            state.heap.pop_reservation_scope();
+            return Ok(throw_message(u32::MAX, u32::MAX, &FakeValue::from(error)));
+        }
+        state.heap.push_borrow_frame();
+    }
+
+    let mut encoder = EncodedData::new();
+    encoder.push_u8(MessageType::Respond as u8);
+
+    for op_index in 0u32.. {
+        if !data.has_more_u32() {
+            break;
+        }
+        let fn_id = data.take_u32()?;
+        let (ty, handler, args) = {
+            let mut state = lock(state);
+            let ty = state.types.parse_type_info(fn_id, data)?;
+            let args = ty
+                .params
+                .iter()
+                .map(|param| state.decode(param, data))
+                .collect::<Result<Vec<_>, _>>()?;
+            (ty, state.handler(fn_id), args)
+        };
+
+        // The lock is released while the handler runs so it can call Rust callbacks
+        let result = handler(&args);
+
+        let mut state = lock(state);
+        match (&ty.ret, result) {
+            (TypeDef::Result(ok, _), Ok(value)) => {
+                encoder.push_u8(1);
+                state.encode(ok, value, &mut encoder);
+            }
+            (TypeDef::Result(_, err), Err(error)) => {
+                encoder.push_u8(0);
+                state.encode(err, error, &mut encoder);
+            }
+            (_, Err(error)) => {
+                state.heap.pop_borrow_frame();
+                state.heap.pop_reservation_scope();
+                return Ok(throw_message(fn_id, op_index, &error));
+            }
+            // Fill the id Rust already handed out as a placeholder
+            (TypeDef::HeapRef, Ok(value)) if reserved_count > 0 => {
+                state.heap.fill_next_reserved(value)
+            }
+            (ret, Ok(value)) => state.encode(ret, value, &mut encoder),
+        }
+    }
+
+    let mut state = lock(state);
+    state.heap.pop_borrow_frame();
+    state.heap.pop_reservation_scope();
+
+    Ok(IPCMessage::new(encoder.to_bytes()))
+}
+
+/// Encode the Throw message JS sends when an operation throws.
+fn throw_message(fn_id: u32, op_index: u32, error: &FakeValue) -> IPCMessage {
+    let message = match error {
+        FakeValue::Object(object) if object.has("message") => object.get("message").to_js_string(),
+        error => error.to_js_string(),
+    };
+    let mut encoder = EncodedData::new();
+    encoder.push_u8(MessageType::Throw as u8);
+    encoder.push_u32(fn_id);
+    encoder.push_u32(op_index);
+    encoder.push_str(&message);
+    encoder.push_str("");
+    IPCMessage::new(encoder.to_bytes())
+}
+
+/// Run an Evaluate message Rust sent and send the response.
+fn evaluate_and_respond(state: &Arc<Mutex<FakeState>>, message: IPCMessage) {
+    let Ok(DecodedVariant::Evaluate { mut data }) = message.decoded() else {
+        unreachable!("only evaluate messages are run")
+    };
+    let response = evaluate(state, &mut data).expect("Failed to decode evaluate message");
+    let senders = lock(state).senders.clone().expect("FakeJs is not running");
+    senders.start_send(response);
+}
+
+/// Handle a message Rust sent to the fake.
+fn handle_event(state: &Arc<Mutex<FakeState>>, event: WryBindgenEvent) {
+    let AppEventVariant::Ipc(message) = event.into_variant() else {
+        return;
+    };
+    match message.ty().expect("Rust sent an invalid message") {
+        MessageType::Evaluate => {
+            if let Some(queued) = &mut lock(state).paused {
+                queued.push(message);
+                return;
+            }
+            evaluate_and_respond(state, message);
+        }
+        // Rust finished running a callback the fake called. Like JS, decode the result
+        // right away since Rust drops the values it returned once the response is sent.
+        MessageType::Respond | MessageType::Throw => {
+            let mut state = lock(state);
+            let response = state
+                .callback_response(&message)
+                .expect("Rust sent an invalid response to the callback");
+            state.response = Some(response);
+        }
+    }
+}
+
+/// Mirror of `JSHeap` in `ts/heap.ts`.
+struct FakeHeap {
+    slots: BTreeMap<u64, FakeValue>,
+    /// Freed ids Rust said to reuse
+    free_ids: Vec<u64>,
+    max_id: u64,
+    /// Borrow stack pointer - uses indices 1-127, growing downward from JSIDX_OFFSET
+    borrow_stack_pointer: u64,
+    borrow_frame_stack: Vec<u64>,
+    reservation_stack: Vec<Reservation>,
+}
+
+/// Ids Rust reserved for placeholders in one Evaluate message.
+struct Reservation {
+    ids: Vec<u64>,
+    next_index: usize,
+}
+
+impl FakeHeap {
+    fn new() -> Self {
+        Self {
+            slots: BTreeMap::new(),
+            free_ids: Vec::new(),
+            max_id: JSIDX_RESERVED,
+            borrow_stack_pointer: JSIDX_OFFSET,
+            borrow_frame_stack: Vec::new(),
+            reservation_stack: Vec::new(),
+        }
+    }
+
+    fn alloc_id(&mut self) -> u64 {
+        self.free_ids.pop().unwrap_or_else(|| {
+            let id = self.max_id;
+            self.max_id += 1;
+            id
+        })
+    }
+
+    fn insert(&mut self, value: FakeValue) -> u64 {
+        let id = self.alloc_id();
+        self.slots.insert(id, value);
+        id
+    }
+
+    fn push_reservation_scope(&mut self, count: u32) {
+        let ids = (0..count).map(|_| self.alloc_id()).collect();
+        self.reservation_stack
+            .push(Reservation { ids, next_index: 0 });
+    }
+
+    fn recycle(&mut self, ids: &[u64]) {
+        self.free_ids.extend_from_slice(ids);
+    }
+
+    /// Compare the free list with the one Rust sent, like `checkFreeList` in JS.
+    fn check(&self, check: HeapCheck) -> Result<(), String> {
+        let ours = HeapCheck::new(&self.free_ids, self.max_id);
+        if ours == check {
+            Ok(())
+        } else {
+            Err(format!(
+                "The JS heap is out of sync with Rust: JS has {ours:?}, Rust has {check:?}"
+            ))
+        }
+    }
+
+    fn pop_reservation_scope(&mut self) {
+        self.reservation_stack.pop();
+    }
+
+    fn fill_next_reserved(&mut self, value: FakeValue) {
+        let scope = self
+            .reservation_stack
+            .last_mut()
+            .filter(|scope| scope.next_index < scope.ids.len())
+            .expect("No reserved slots available");
+        let id = scope.ids[scope.next_index];
+        scope.next_index += 1;
+        self.slots.insert(id, value);
+    }
+
+    fn get(&self, id: u64) -> FakeValue {
+        match id {
+            JSIDX_UNDEFINED => FakeValue::Undefined,
+            JSIDX_NULL => FakeValue::Null,
+            JSIDX_TRUE => FakeValue::Bool(true),
+            JSIDX_FALSE => FakeValue::Bool(false),
+            _ => self
+                .slots
+                .get(&id)
+                .cloned()
+                .unwrap_or_else(|| panic!("Heap id {id} is not alive in the fake JS heap")),
+        }
+    }
+
+    fn remove(&mut self, id: u64) {
+        // Never remove reserved slots. The id is reused once Rust recycles it
+        if id >= JSIDX_RESERVED {
+            self.slots.remove(&id);
+        }
+    }
+
+    fn live_count(&self) -> usize {
+        self.slots.range(JSIDX_RESERVED..).count()
+    }
+
+    fn add_borrowed_ref(&mut self, value: FakeValue) {
+        if self.borrow_stack_pointer <= 1 {
+            panic!("Borrow stack overflow: too many borrowed references in a single operation");
+        }
+        self.borrow_stack_pointer -= 1;
+        self.slots.insert(self.borrow_stack_pointer, value);
+    }
+
+    fn push_borrow_frame(&mut self) {
+        self.borrow_frame_stack.push(self.borrow_stack_pointer);
+    }
+
+    fn pop_borrow_frame(&mut self) {
+        if let Some(saved_pointer) = self.borrow_frame_stack.pop() {
+            // Clear refs from this frame only
+            for id in self.borrow_stack_pointer..saved_pointer {
+                self.slots.remove(&id);
+            }
+            self.borrow_stack_pointer = saved_pointer;
+        }
+    }
+}
+
+/// Lock a mutex, ignoring poisoning from a panicking handler.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn js_code(fn_id: u32) -> String {
+    FUNCTION_REGISTRY
+        .function_specs()
+        .get(fn_id as usize)
+        .map_or_else(String::new, |spec| (spec.js_code())())
+}
+
+/// Find the name of the import a generated function calls, e.g. `alert` for
+/// `(a0) => window.alert(a0)` or `count` for `(obj) => obj.count`.
+fn import_name(code: &str) -> Option<&str> {
+    let (_, body) = code.split_once("=> ")?;
+    let body = body.strip_prefix("{ try { return { ok: ").unwrap_or(body);
+    let body = body
+        .strip_prefix("new ")
+        .or_else(|| body.strip_prefix("delete "))
+        .unwrap_or(body);
+    let callee = &body[..body.find(['(', ' ', '[']).unwrap_or(body.len())];
+    callee.rsplit('.').next().filter(|name| !name.is_empty())
+}
+
+fn arg(args: &[FakeValue], index: usize) -> FakeValue {
+    args.get(index).cloned().unwrap_or_default()
+}
+
+/// Convert to an i32 like JS's bitwise operators.
+fn to_int32(value: &FakeValue) -> i32 {
+    let n = value.to_number();
+    if n.is_finite() {
+        (n.trunc() % 4294967296.0) as i64 as u32 as i32
+    } else {
+        0
+    }
+}
+
+/// Handlers for the JS functions wry-bindgen itself calls.
+fn builtin(code: &str, helpers: &str, state: &Weak<Mutex<FakeState>>) -> Option<Handler> {
+    match code {
+        // wbg_cast
+        "(a0) => a0" => return Some(Arc::new(|args| Ok(arg(args, 0)))),
+        // JsError::new
+        "(msg) => new Error(msg)" => {
+            return Some(Arc::new(|args| {
+                Ok(FakeObject::error(&arg(args, 0).to_js_string()).into())
+            }));
+        }
+        // Promises for async exports are objects with a `state` and a `value`
+        // once they settle. Their resolve and reject functions are objects that
+        // point back at the promise.
+        "(executor) => new Promise(executor)" => {
+            return Some(Arc::new(|args| {
+                let promise = FakeObject::new();
+                promise.set("state", "pending");
+                let settle = |state: &str| {
+                    let settle = FakeObject::new();
+                    settle.set("promise", promise.clone());
+                    settle.set("state", state);
+                    FakeValue::from(settle)
+                };
+                let executor = arg(args, 0);
+                let executor = executor.as_function().expect("executor is a function");
+                executor.try_call(&[settle("fulfilled"), settle("rejected")])?;
+                Ok(promise.into())
+            }));
+        }
+        "(settle, value) => settle(value)" => {
+            return Some(Arc::new(|args| {
+                let settle = arg(args, 0);
+                let settle = settle.as_object().expect("settle is a promise function");
+                let FakeValue::Object(promise) = settle.get("promise") else {
+                    panic!("settle is a promise function");
+                };
+                if promise.get("state").as_str() == Some("pending") {
+                    promise.set("state", settle.get("state"));
+                    promise.set("value", arg(args, 1));
+                }
+                Ok(FakeValue::Undefined)
+            }));
+        }
+        _ => {}
+    }
+
+    // Everything else is from convert.js
+    let (_, body) = code.split_once("=> ")?;
+    if !body.starts_with(helpers) {
+        return None;
+    }
+    let name = import_name(code)?;
+
+    if let "clone_heap_ref" | "drop_heap_ref" | "heap_objects_alive" = name {
+        let state = state.clone();
+        let name = name.to_string();
+        return Some(Arc::new(move |args| {
+            let state = state.upgrade().expect("FakeJs was dropped");
+            let mut state = lock(&state);
+            match name.as_str() {
+                "drop_heap_ref" => {
+                    state.heap.remove(arg(args, 0).to_number() as u64);
+                    Ok(FakeValue::Undefined)
+                }
+                "clone_heap_ref" => Ok(state.heap.get(arg(args, 0).to_number() as u64)),
+                _ => Ok((state.heap.live_count() as f64).into()),
+            }
+        }));
+    }
+
+    type Op = fn(&FakeValue, &FakeValue) -> FakeValue;
+    let op: Op = match name {
+        "is_undefined" => |a, _| (*a == FakeValue::Undefined).into(),
+        "is_null" => |a, _| (*a == FakeValue::Null).into(),
+        "is_true" => |a, _| (*a == FakeValue::Bool(true)).into(),
+        "is_false" => |a, _| (*a == FakeValue::Bool(false)).into(),
+        "get_typeof" => |a, _| a.type_of().into(),
+        "is_falsy" => |a, _| (!a.is_truthy()).into(),
+        "is_truthy" => |a, _| a.is_truthy().into(),
+        "is_object" => |a, _| (a.type_of() == "object" && *a != FakeValue::Null).into(),
+        "is_function" => |a, _| matches!(a, FakeValue::Function(_)).into(),
+        "is_string" => |a, _| matches!(a, FakeValue::String(_)).into(),
+        "is_symbol" | "is_bigint" => |_, _| false.into(),
+        "as_string" => |a, _| a.as_str().into(),
+        "as_f64" => |a, _| a.as_f64().into(),
+        "debug_string" => |a, _| a.to_js_string().into(),
+        "js_checked_div" | "js_div" => |a, b| (a.to_number() / b.to_number()).into(),
+        "js_pow" => |a, b| a.to_number().powf(b.to_number()).into(),
+        "js_add" => |a, b| match (a, b) {
+            (FakeValue::String(_), _) | (_, FakeValue::String(_)) => {
+                (a.to_js_string() + &b.to_js_string()).into()
+            }
+            _ => (a.to_number() + b.to_number()).into(),
+        },
+        "js_sub" => |a, b| (a.to_number() - b.to_number()).into(),
+        "js_mul" => |a, b| (a.to_number() * b.to_number()).into(),
+        "js_rem" => |a, b| (a.to_number() % b.to_number()).into(),
+        "js_neg" => |a, _| (-a.to_number()).into(),
+        "js_bit_and" => |a, b| (to_int32(a) & to_int32(b)).into(),
+        "js_bit_or" => |a, b| (to_int32(a) | to_int32(b)).into(),
+        "js_bit_xor" => |a, b| (to_int32(a) ^ to_int32(b)).into(),
+        "js_bit_not" => |a, _| (!to_int32(a)).into(),
+        "js_shl" => |a, b| to_int32(a).wrapping_shl(to_int32(b) as u32 & 31).into(),
+        "js_shr" => |a, b| to_int32(a).wrapping_shr(to_int32(b) as u32 & 31).into(),
+        "js_unsigned_shr" => |a, b| ((to_int32(a) as u32) >> (to_int32(b) as u32 & 31)).into(),
+        "js_lt" => |a, b| compare(a, b, |o| o.is_lt()).into(),
+        "js_le" => |a, b| compare(a, b, |o| o.is_le()).into(),
+        "js_gt" => |a, b| compare(a, b, |o| o.is_gt()).into(),
+        "js_ge" => |a, b| compare(a, b, |o| o.is_ge()).into(),
+        "js_loose_eq" => |a, b| a.loose_eq(b).into(),
+        "js_in" => |prop, obj| match obj {
+            FakeValue::Object(obj) => obj.has(&prop.to_js_string()).into(),
+            FakeValue::Array(items) => (prop.to_number() < items.len() as f64).into(),
+            _ => false.into(),
+        },
+        "is_error" => |a, _| a.as_object().is_some_and(FakeObject::is_error).into(),
+        "create_rust_object_wrapper" => |handle, class_name| {
+            let wrapper = FakeObject::new();
+            wrapper.set("__handle", handle.clone());
+            wrapper.set("__className", class_name.clone());
+            wrapper.into()
+        },
+        "extract_rust_handle" => |obj, _| match obj.as_object().map(|obj| obj.get("__handle")) {
+            Some(handle @ FakeValue::Number(_)) => handle,
+            _ => FakeValue::Null,
+        },
+        _ => return None,
+    };
+    Some(Arc::new(move |args| Ok(op(&arg(args, 0), &arg(args, 1)))))
+}
+
+/// Compare two values like JS's relational operators.
+fn compare(a: &FakeValue, b: &FakeValue, test: fn(core::cmp::Ordering) -> bool) -> bool {
+    match (a, b) {
+        (FakeValue::String(a), FakeValue::String(b)) => test(a.cmp(b)),
+        _ => a.to_number().partial_cmp(&b.to_number()).is_some_and(test),
+    }
+}
@@ -10,8 +10,10 @@
 use alloc::vec::Vec;
 use core::cell::RefCell;
 use core::marker::PhantomData;
+use core::panic::Location;
 
-use crate::batch::{force_flush, run_js_sync, with_runtime};
+use crate::batch::{force_flush, run_js_async, run_js_deferred, run_js_sync, with_runtime};
+use crate::deferred::Deferred;
 use crate::encode::{BatchableResult, BinaryEncode, EncodeTypeDef, TYPE_CACHED, TYPE_FULL};
 use crate::ipc::DecodedData;
 use crate::ipc::EncodedData;
@@ -57,6 +59,8 @@ fn encode_function_types(encoder: &mut EncodedData, encode_types: impl FnOnce(&m
 /// Arguments and return values are serialized using the binary protocol.
 pub struct JSFunction<T> {
     id: u32,
+    /// Whether JS got the function's types from the init script
+    registered_types: bool,
     function: PhantomData<T>,
 }
 
@@ -64,10 +68,17 @@ impl<T> JSFunction<T> {
     pub const fn new(id: u32) -> Self {
         Self {
             id,
+            registered_types: false,
             function: PhantomData,
         }
     }
 
+    /// Skip the type information in calls, because JS got the types from the registry.
+    pub(crate) const fn with_registered_types(mut self) -> Self {
+        self.registered_types = true;
+        self
+    }
+
     /// Get the function ID.
     pub fn id(&self) -> u32 {
         self.id
@@ -78,13 +89,46 @@ macro_rules! impl_js_function_call {
     // Base case: zero arguments
     (0,) => {
         impl<R: BatchableResult + EncodeTypeDef> JSFunction<fn() -> R> {
+            /// Encode the parameter count, parameter types and return type of the function.
+            #[doc(hidden)]
+            pub fn encode_signature(&self, buf: &mut Vec<u8>) {
+                buf.push(0);
+                R::encode_type_def(buf);
+            }
+
+            /// Encode the types of a call, unless JS got them from the registry.
+            fn encode_types(&self, encoder: &mut EncodedData) {
+                if !self.registered_types {
+                    encode_function_types(encoder, |buf| self.encode_signature(buf));
+                }
+            }
+
+            #[track_caller]
             pub fn call(&self) -> R {
-                run_js_sync::<R>(self.id, |encoder| {
-                    encode_function_types(encoder, |buf| {
-                        buf.push(0);
-                        R::encode_type_def(buf);
-                    });
-                })
+                run_js_sync::<R>(self.id, |encoder| self.encode_types(encoder))
+            }
+
+            /// Call the function without flushing the batch. The result is
+            /// available once the batch is sent, see [`Deferred`].
+            #[track_caller]
+            pub fn call_deferred(&self) -> Deferred<R>
+            where
+                R: 'static,
+            {
+                run_js_deferred::<R>(self.id, |encoder| self.encode_types(encoder))
+            }
+
+            /// Call the function without blocking the thread while JS runs it.
+            ///
+            /// The batch is sent right away. Callbacks JS makes before it
+            /// responds run while the future is polled, see
+            /// [`call_async`](JSFunction::call_async) for details.
+            #[track_caller]
+            pub fn call_async(&self) -> impl Future<Output = R>
+            where
+                R: 'static,
+            {
+                run_js_async::<R>(Location::caller(), self.id, |encoder| self.encode_types(encoder))
             }
         }
     };
@@ -93,16 +137,70 @@ macro_rules! impl_js_function_call {
         impl<$($T: EncodeTypeDef,)+ R: BatchableResult + EncodeTypeDef>
             JSFunction<fn($($T),+) -> R>
         {
+            /// Encode the parameter count, parameter types and return type of the function.
+            #[doc(hidden)]
+            pub fn encode_signature(&self, buf: &mut Vec<u8>) {
+                buf.push($n);
+                $($T::encode_type_def(buf);)+
+                R::encode_type_def(buf);
+            }
+
+            /// Encode the types of a call, unless JS got them from the registry.
+            fn encode_types(&self, encoder: &mut EncodedData) {
+                if !self.registered_types {
+                    encode_function_types(encoder, |buf| self.encode_signature(buf));
+                }
+            }
+
+            #[track_caller]
             pub fn call<$($P),+>(&self, $($arg: $T),+) -> R
             where
                 $($T: BinaryEncode<$P>,)+
             {
                 run_js_sync::<R>(self.id, |encoder| {
-                    encode_function_types(encoder, |buf| {
-                        buf.push($n);
-                        $($T::encode_type_def(buf);)+
-                        R::encode_type_def(buf);
-                    });
+                    self.encode_types(encoder);
+                    $($arg.encode(encoder);)+
+                })
+            }
+
+            /// Call the function without flushing the batch. The result is
+            /// available once the batch is sent, see [`Deferred`].
+            #[track_caller]
+            pub fn call_deferred<$($P),+>(&self, $($arg: $T),+) -> Deferred<R>
+            where
+                R: 'static,
+                $($T: BinaryEncode<$P>,)+
+            {
+                run_js_deferred::<R>(self.id, |encoder| {
+                    self.encode_types(encoder);
+                    $($arg.encode(encoder);)+
+                })
+            }
+
+            /// Call the function without blocking the thread while JS runs it.
+            ///
+            /// The current batch and the call are sent right away, and the
+            /// future resolves when JS responds. Callbacks JS makes before it
+            /// responds run while the future is polled, so long running JS work
+            /// doesn't stall other tasks on the thread.
+            ///
+            /// Only one async call is in flight at a time. Synchronous calls
+            /// made before it finishes block until it does, so JS runs calls in
+            /// the order Rust made them. Inside a call from JS the function
+            /// runs synchronously. Panics like [`call`](JSFunction::call) if JS
+            /// throws, or if the webview reloads before JS responds.
+            ///
+            /// The future can be dropped or forgotten while JS runs the call, so
+            /// it doesn't take arguments JS borrows for the call, like exported
+            /// structs passed by reference. See [`BorrowedForCall`](crate::BorrowedForCall).
+            #[track_caller]
+            pub fn call_async(&self, $($arg: $T),+) -> impl Future<Output = R>
+            where
+                R: 'static,
+                $($T: BinaryEncode,)+
+            {
+                run_js_async::<R>(Location::caller(), self.id, move |encoder| {
+                    self.encode_types(encoder);
                     $($arg.encode(encoder);)+
                 })
             }
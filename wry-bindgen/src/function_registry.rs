@@ -3,11 +3,14 @@
 //! This module provides the registry system that collects JS function specifications,
 //! inline JS modules, and exported Rust types via the `inventory` crate.
 
+use alloc::boxed::Box;
 use alloc::collections::BTreeMap;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 use core::fmt::Write;
+use core::future::Future;
 use core::ops::Deref;
+use core::pin::Pin;
 use once_cell::sync::{Lazy, OnceCell};
 
 use crate::function::JSFunction;
@@ -18,11 +21,36 @@ use crate::ipc::{DecodedData, EncodedData};
 pub struct JsFunctionSpec {
     /// Function that generates the JS code
     js_code: fn() -> String,
+    /// Function that encodes the parameter count, parameter types and return type,
+    /// if the signature is known without calling the function
+    signature: Option<fn(&mut Vec<u8>)>,
+    /// The path of the Rust module the function was declared in
+    module_path: &'static str,
 }
 
 impl JsFunctionSpec {
     pub const fn new(js_code: fn() -> String) -> Self {
-        Self { js_code }
+        Self {
+            js_code,
+            signature: None,
+            module_path: "",
+        }
+    }
+
+    /// Add the signature of the function to the spec.
+    ///
+    /// The registry sends the types to JS in the init script, so calls only
+    /// send the function id and arguments.
+    pub const fn with_signature(mut self, signature: fn(&mut Vec<u8>)) -> Self {
+        self.signature = Some(signature);
+        self
+    }
+
+    /// Set the path of the Rust module the function was declared in, used to
+    /// pick the functions to preload.
+    pub const fn with_module_path(mut self, module_path: &'static str) -> Self {
+        self.module_path = module_path;
+        self
     }
 
     /// Get the JS code generator function
@@ -30,6 +58,23 @@ impl JsFunctionSpec {
         self.js_code
     }
 
+    /// Get the signature encoder, if the spec has one
+    pub const fn signature(&self) -> Option<fn(&mut Vec<u8>)> {
+        self.signature
+    }
+
+    /// Get the path of the Rust module the function was declared in
+    pub const fn module_path(&self) -> &'static str {
+        self.module_path
+    }
+
+    /// Whether the function was declared in `module` or one of its submodules.
+    pub fn is_in_module(&self, module: &str) -> bool {
+        self.module_path
+            .strip_prefix(module)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+    }
+
     pub const fn resolve_as<F>(&self) -> LazyJsFunction<F> {
         LazyJsFunction {
             spec: *self,
@@ -133,15 +178,23 @@ pub struct JsClassMemberSpec {
     arg_count: usize,
     /// Type of member
     kind: JsClassMemberKind,
+    /// Function that encodes the parameter count, parameter types and return type,
+    /// not counting the handle of the object the member is called on
+    signature: fn(&mut Vec<u8>),
 }
 
 impl JsClassMemberSpec {
+    /// Create the spec of a class member.
+    ///
+    /// The generated class encodes arguments and decodes the result with the
+    /// types from `signature`, like calls to imported JS functions.
     pub const fn new(
         class_name: &'static str,
         member_name: &'static str,
         export_name: &'static str,
         arg_count: usize,
         kind: JsClassMemberKind,
+        signature: fn(&mut Vec<u8>),
     ) -> Self {
         Self {
             class_name,
@@ -149,9 +202,15 @@ impl JsClassMemberSpec {
             export_name,
             arg_count,
             kind,
+            signature,
         }
     }
 
+    /// Get the signature encoder
+    pub const fn signature(&self) -> fn(&mut Vec<u8>) {
+        self.signature
+    }
+
     /// Get the class name this member belongs to
     pub const fn class_name(&self) -> &'static str {
         self.class_name
@@ -182,14 +241,17 @@ inventory::collect!(JsClassMemberSpec);
 
 /// Specification for an exported Rust function/method callable from JavaScript.
 ///
-/// This is used by the `#[wasm_bindgen]` macro when exporting structs and impl blocks.
-/// Each export is registered via inventory and collected at runtime.
+/// This is used by the `#[wasm_bindgen]` macro when exporting structs, impl blocks
+/// and free functions. Each export is registered via inventory and collected at runtime.
 #[derive(Clone, Copy)]
 pub struct JsExportSpec {
-    /// The export name (e.g., "MyStruct::new", "MyStruct::method")
+    /// The export name (e.g., "MyStruct::new", "MyStruct::method", "greet")
     pub name: &'static str,
     /// Handler function that decodes arguments, calls the Rust function, and encodes the result
     pub handler: fn(&mut DecodedData) -> Result<EncodedData, alloc::string::String>,
+    /// Function that encodes the parameter count, parameter types and return type of
+    /// a free function
+    signature: Option<fn(&mut Vec<u8>)>,
 }
 
 impl JsExportSpec {
@@ -197,16 +259,71 @@ impl JsExportSpec {
         name: &'static str,
         handler: fn(&mut DecodedData) -> Result<EncodedData, alloc::string::String>,
     ) -> Self {
-        Self { name, handler }
+        Self {
+            name,
+            handler,
+            signature: None,
+        }
+    }
+
+    /// Add the signature of a free function to the spec.
+    ///
+    /// Exports with a signature are free functions, and the registry exposes them
+    /// to JS as `window.__wbg_exports[name]`.
+    pub const fn with_signature(mut self, signature: fn(&mut Vec<u8>)) -> Self {
+        self.signature = Some(signature);
+        self
+    }
+
+    /// Get the signature encoder, if the export is a free function
+    pub const fn signature(&self) -> Option<fn(&mut Vec<u8>)> {
+        self.signature
     }
 }
 
 inventory::collect!(JsExportSpec);
 
+/// Specification for a function marked with `#[wasm_bindgen(start)]`.
+///
+/// The macro registers each start function via inventory, and launchers run them
+/// with [`run_start_functions`] once the runtime is up.
+#[derive(Clone, Copy)]
+pub struct JsStartSpec {
+    /// Creates the future that runs the start function
+    start: fn() -> Pin<Box<dyn Future<Output = ()>>>,
+}
+
+impl JsStartSpec {
+    pub const fn new(start: fn() -> Pin<Box<dyn Future<Output = ()>>>) -> Self {
+        Self { start }
+    }
+
+    /// Create the future that runs the start function
+    pub fn start(&self) -> Pin<Box<dyn Future<Output = ()>>> {
+        (self.start)()
+    }
+}
+
+inventory::collect!(JsStartSpec);
+
+/// Run every function marked with `#[wasm_bindgen(start)]`, one after another.
+///
+/// This must be called from inside the runtime, since start functions usually
+/// call into JS.
+pub async fn run_start_functions() {
+    for spec in inventory::iter::<JsStartSpec>() {
+        spec.start().await;
+    }
+}
+
 /// Registry of JS functions collected via inventory
 pub(crate) struct FunctionRegistry {
     functions: String,
     function_specs: Vec<JsFunctionSpec>,
+    /// The distinct signatures of the functions. A type's id is its index
+    types: Vec<Vec<u8>>,
+    /// The type id of each function with a signature, by function id
+    function_types: Vec<Option<u32>>,
     /// Map of module path -> module content for inline_js modules
     modules: BTreeMap<String, &'static str>,
 }
@@ -239,6 +356,24 @@ impl FunctionRegistry {
         // Collect all function specs
         let specs: Vec<_> = inventory::iter::<JsFunctionSpec>().copied().collect();
 
+        // Assign type ids to the signatures, sharing one id between functions with the same types
+        let mut types: Vec<Vec<u8>> = Vec::new();
+        let function_types: Vec<Option<u32>> = specs
+            .iter()
+            .map(|spec| {
+                let mut signature = Vec::new();
+                (spec.signature()?)(&mut signature);
+                let id = types
+                    .iter()
+                    .position(|ty| *ty == signature)
+                    .unwrap_or_else(|| {
+                        types.push(signature);
+                        types.len() - 1
+                    });
+                Some(id as u32)
+            })
+            .collect();
+
         // Build the script - load modules from wry:// handler before setting up function registry
         let mut script = String::new();
 
@@ -262,15 +397,37 @@ impl FunctionRegistry {
             }
         }
 
-        // Now set up the function registry after all modules are loaded
-        // Store raw JS functions - type info will be passed at call time
-        script.push_str("  window.setFunctionRegistry([");
-        for (i, spec) in specs.iter().enumerate() {
+        // Now set up the function registry after all modules are loaded. Only the
+        // number of functions is sent, JS fetches the code of each function the first
+        // time it is called. Functions without a signature get type info at call time
+        write!(
+            &mut script,
+            "  window.setFunctionRegistry({}, {{",
+            specs.len()
+        )
+        .unwrap();
+        for (i, hash) in imported_modules.iter().enumerate() {
+            if i > 0 {
+                script.push_str(", ");
+            }
+            write!(&mut script, "module_{hash}").unwrap();
+        }
+        script.push_str("}, [");
+        for (i, ty) in types.iter().enumerate() {
+            if i > 0 {
+                script.push_str(", ");
+            }
+            write!(&mut script, "{ty:?}").unwrap();
+        }
+        script.push_str("], [");
+        for (i, ty) in function_types.iter().enumerate() {
             if i > 0 {
-                script.push_str(",\n");
+                script.push_str(", ");
+            }
+            match ty {
+                Some(id) => write!(&mut script, "{id}").unwrap(),
+                None => script.push_str("null"),
             }
-            let js_code = (spec.js_code())();
-            write!(&mut script, "{js_code}").unwrap();
         }
         script.push_str("]);\n");
 
@@ -285,6 +442,27 @@ impl FunctionRegistry {
 
         // Generate complete class definitions for each exported struct
         for (class_name, members) in &class_members {
+            // Create a typed call for each member. Each call starts with `{callee}`
+            // and is followed by the arguments and a closing paren
+            let mut callees = Vec::with_capacity(members.len());
+            for (i, member) in members.iter().enumerate() {
+                let mut types = Vec::new();
+                (member.signature())(&mut types);
+                let has_handle = matches!(
+                    member.kind(),
+                    JsClassMemberKind::Method
+                        | JsClassMemberKind::Getter
+                        | JsClassMemberKind::Setter
+                );
+                writeln!(
+                    &mut script,
+                    r#"  const {class_name}__{i} = window.rustExports.exportMethod("{}", {types:?}, {has_handle});"#,
+                    member.export_name()
+                )
+                .unwrap();
+                callees.push(format!("{class_name}__{i}("));
+            }
+
             // Generate class shell
             writeln!(
                 &mut script,
@@ -292,29 +470,30 @@ impl FunctionRegistry {
     constructor(handle) {{
       this.__handle = handle;
       this.__className = "{class_name}";
-      window.__wryExportRegistry.register(this, {{ handle, className: "{class_name}" }});
+      window.__wryExportRegistry.register(this, {{ handle, className: "{class_name}" }}, this);
     }}
     static __wrap(handle) {{
       const obj = Object.create({class_name}.prototype);
       obj.__handle = handle;
       obj.__className = "{class_name}";
-      window.__wryExportRegistry.register(obj, {{ handle, className: "{class_name}" }});
+      window.__wryExportRegistry.register(obj, {{ handle, className: "{class_name}" }}, obj);
       return obj;
     }}
     free() {{
       const handle = this.__handle;
       this.__handle = 0;
+      window.__wryExportRegistry.unregister(this);
       if (handle !== 0) window.__wryCallExport("{class_name}::__drop", handle);
     }}"#
             )
             .unwrap();
 
             // Track getters/setters to combine them into single property descriptors
-            let mut getters: BTreeMap<&str, &JsClassMemberSpec> = BTreeMap::new();
-            let mut setters: BTreeMap<&str, &JsClassMemberSpec> = BTreeMap::new();
+            let mut getters: BTreeMap<&str, &str> = BTreeMap::new();
+            let mut setters: BTreeMap<&str, &str> = BTreeMap::new();
 
             // Generate methods inside the class body
-            for member in members {
+            for (member, callee) in members.iter().zip(&callees) {
                 match member.kind() {
                     JsClassMemberKind::Method => {
                         // Instance method
@@ -326,19 +505,19 @@ impl FunctionRegistry {
                         };
                         writeln!(
                             &mut script,
-                            r#"    {}({}) {{ return window.__wryCallExport("{}", {}); }}"#,
+                            r#"    {}({}) {{ return {}{}); }}"#,
                             member.member_name(),
                             args,
-                            member.export_name(),
+                            callee,
                             args_with_handle
                         )
                         .unwrap();
                     }
                     JsClassMemberKind::Getter => {
-                        getters.insert(member.member_name(), member);
+                        getters.insert(member.member_name(), callee);
                     }
                     JsClassMemberKind::Setter => {
-                        setters.insert(member.member_name(), member);
+                        setters.insert(member.member_name(), callee);
                     }
                     _ => {} // Constructor and static handled separately
                 }
@@ -351,35 +530,19 @@ impl FunctionRegistry {
             property_names.extend(setters.keys());
 
             for prop_name in property_names {
-                let getter = getters.get(prop_name);
-                let setter = setters.get(prop_name);
-                match (getter, setter) {
-                    (Some(g), Some(s)) => {
-                        writeln!(
-                            &mut script,
-                            r#"    get {}() {{ return window.__wryCallExport("{}", this.__handle); }}
-    set {}(v) {{ window.__wryCallExport("{}", this.__handle, v); }}"#,
-                            prop_name, g.export_name(), prop_name, s.export_name()
-                        )
-                        .unwrap();
-                    }
-                    (Some(g), None) => {
-                        writeln!(
-                            &mut script,
-                            r#"    get {}() {{ return window.__wryCallExport("{}", this.__handle); }}"#,
-                            prop_name, g.export_name()
-                        )
-                        .unwrap();
-                    }
-                    (None, Some(s)) => {
-                        writeln!(
-                            &mut script,
-                            r#"    set {}(v) {{ window.__wryCallExport("{}", this.__handle, v); }}"#,
-                            prop_name, s.export_name()
-                        )
-                        .unwrap();
-                    }
-                    (None, None) => {}
+                if let Some(getter) = getters.get(prop_name) {
+                    writeln!(
+                        &mut script,
+                        r#"    get {prop_name}() {{ return {getter}this.__handle); }}"#
+                    )
+                    .unwrap();
+                }
+                if let Some(setter) = setters.get(prop_name) {
+                    writeln!(
+                        &mut script,
+                        r#"    set {prop_name}(v) {{ {setter}this.__handle, v); }}"#
+                    )
+                    .unwrap();
                 }
             }
 
@@ -387,33 +550,23 @@ impl FunctionRegistry {
             script.push_str("  }\n");
 
             // Add static methods and constructors outside the class
-            for member in members {
+            for (member, callee) in members.iter().zip(&callees) {
                 match member.kind() {
                     JsClassMemberKind::Constructor => {
                         let args = generate_args(member.arg_count());
-                        let args_call = if member.arg_count() > 0 { &args } else { "" };
                         writeln!(
                             &mut script,
-                            r#"  {class_name}.{method_name} = function({args}) {{ const handle = window.__wryCallExport("{export_name}", {args_call}); return {class_name}.__wrap(handle); }};"#,
-                            class_name = class_name,
+                            r#"  {class_name}.{method_name} = function({args}) {{ const handle = {callee}{args}); return {class_name}.__wrap(handle); }};"#,
                             method_name = member.member_name(),
-                            args = args,
-                            export_name = member.export_name(),
-                            args_call = args_call
                         )
                         .unwrap();
                     }
                     JsClassMemberKind::StaticMethod => {
                         let args = generate_args(member.arg_count());
-                        let args_call = if member.arg_count() > 0 { &args } else { "" };
                         writeln!(
                             &mut script,
-                            r#"  {class_name}.{method_name} = function({args}) {{ return window.__wryCallExport("{export_name}", {args_call}); }};"#,
-                            class_name = class_name,
+                            r#"  {class_name}.{method_name} = function({args}) {{ return {callee}{args}); }};"#,
                             method_name = member.member_name(),
-                            args = args,
-                            export_name = member.export_name(),
-                            args_call = args_call
                         )
                         .unwrap();
                     }
@@ -425,8 +578,23 @@ impl FunctionRegistry {
             writeln!(&mut script, "  window.{class_name} = {class_name};").unwrap();
         }
 
-        // Send a request to wry to notify that the function registry is initialized
-        script.push_str("  fetch(`/__wbg__/initialized`, { method: 'POST', body: [] });\n");
+        // Expose exported free functions on window.__wbg_exports
+        for export in inventory::iter::<JsExportSpec>() {
+            let Some(signature) = export.signature() else {
+                continue;
+            };
+            let mut types = Vec::new();
+            signature(&mut types);
+            writeln!(
+                &mut script,
+                r#"  window.__wbg_exports["{name}"] = window.rustExports.exportFunction("{name}", {types:?});"#,
+                name = export.name,
+            )
+            .unwrap();
+        }
+
+        // Notify wry that the function registry is initialized, starting the handshake
+        script.push_str("  window.notifyInitialized();\n");
 
         // Close the async IIFE
         script.push_str("})();\n");
@@ -434,17 +602,62 @@ impl FunctionRegistry {
         Self {
             functions: script,
             function_specs: specs,
+            types,
+            function_types,
             modules,
         }
     }
 
     /// Get a function by name from the registry
     pub fn get_function<F>(&self, spec: JsFunctionSpec) -> Option<JSFunction<F>> {
-        let index = self
-            .function_specs
-            .iter()
-            .position(|s| s.js_code() as usize == spec.js_code() as usize)?;
-        Some(JSFunction::new(index as _))
+        // Release builds merge identical code closures, so imports of the same JS
+        // function with different types are told apart by their signature too
+        let signature = spec.signature().map(|signature| signature as usize);
+        let index = self.function_specs.iter().position(|s| {
+            s.js_code() as usize == spec.js_code() as usize
+                && s.signature().map(|signature| signature as usize) == signature
+        })?;
+        let function = JSFunction::new(index as _);
+        Some(match self.function_types[index] {
+            Some(_) => function.with_registered_types(),
+            None => function,
+        })
+    }
+
+    /// Get the type id and signature of a function, if JS gets its types from the
+    /// init script instead of each call.
+    pub(crate) fn function_type(&self, fn_id: u32) -> Option<(u32, &[u8])> {
+        let id = (*self.function_types.get(fn_id as usize)?)?;
+        Some((id, &self.types[id as usize]))
+    }
+
+    /// Get the function specs in registry order. A function's id is its index.
+    pub fn function_specs(&self) -> &[JsFunctionSpec] {
+        &self.function_specs
+    }
+
+    /// Get the JS code of a function by id, if the id is in the registry.
+    pub(crate) fn js_code(&self, fn_id: u32) -> Option<String> {
+        let spec = self.function_specs.get(fn_id as usize)?;
+        Some((spec.js_code())())
+    }
+
+    /// Get the code of the functions with these ids as a JS object expression
+    /// mapping each id to its function, or `None` if an id is not in the registry.
+    ///
+    /// The code refers to inline_js modules as `module_{hash}`, so JS compiles the
+    /// chunk with the modules in scope.
+    pub(crate) fn function_chunk(&self, ids: impl IntoIterator<Item = u32>) -> Option<String> {
+        let mut chunk = String::from("({");
+        for (i, fn_id) in ids.into_iter().enumerate() {
+            if i > 0 {
+                chunk.push_str(",\n");
+            }
+            let js_code = self.js_code(fn_id)?;
+            write!(&mut chunk, "{fn_id}: {js_code}").unwrap();
+        }
+        chunk.push_str("})");
+        Some(chunk)
     }
 
     /// Get the initialization script
@@ -0,0 +1,126 @@
+//! Introspection of the values shared between Rust and JS, and a leak check.
+//!
+//! Every function here works on the runtime of the current thread and panics
+//! outside of one.
+//!
+//! # Example
+//!
+//! ```ignore
+//! wry_bindgen::heap::start_leak_check();
+//! render_app();
+//! let leaks = wry_bindgen::heap::finish_leak_check();
+//! assert!(leaks.is_empty(), "{leaks}");
+//! ```
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::panic::Location;
+
+use crate::batch::with_runtime;
+
+/// The number of JS heap values Rust holds.
+///
+/// This counts the values behind every live [`JsValue`](crate::JsValue),
+/// [`Closure`](crate::Closure) and [`SendJsValue`](crate::SendJsValue) of the
+/// runtime. Values dropped in Rust are not counted even if JS hasn't freed them
+/// yet, see [`externref_heap_live_count`](crate::externref_heap_live_count) for
+/// the count in JS.
+pub fn live_js_values() -> usize {
+    with_runtime(|runtime| runtime.live_heap_ids())
+}
+
+/// The number of exported Rust structs and callbacks JS holds handles to.
+pub fn live_objects() -> usize {
+    with_runtime(|runtime| runtime.object_count())
+}
+
+/// Start recording where every JS heap value is created.
+///
+/// Call [`finish_leak_check`] to get the values created since then that are
+/// still alive. Starting a leak check while one is running starts it over.
+pub fn start_leak_check() {
+    with_runtime(|runtime| runtime.start_leak_check());
+}
+
+/// Stop recording where JS heap values are created, and report the values
+/// created since [`start_leak_check`] that are still alive.
+///
+/// Values from before the webview reloaded are not reported. Panics if no leak
+/// check is running.
+#[track_caller]
+pub fn finish_leak_check() -> LeakReport {
+    let sites = with_runtime(|runtime| runtime.finish_leak_check())
+        .expect("finish_leak_check called without start_leak_check");
+    LeakReport {
+        leaks: sites
+            .into_iter()
+            .map(|(id, location)| Leak { id, location })
+            .collect(),
+    }
+}
+
+/// A JS heap value that was still alive when a leak check finished.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Leak {
+    /// The heap id of the value
+    pub id: u64,
+    /// The call to JS that created the value, or `None` if JS created it while
+    /// calling into Rust, like the arguments of a [`Closure`](crate::Closure)
+    pub location: Option<&'static Location<'static>>,
+}
+
+/// The values that were still alive when a leak check finished.
+///
+/// Displays as one line per call that created values, like
+/// `2 JsValues leaked, created at src/main.rs:12:5`.
+#[derive(Debug, Clone, Default)]
+pub struct LeakReport {
+    leaks: Vec<Leak>,
+}
+
+impl LeakReport {
+    /// The leaked values, ordered by heap id.
+    pub fn leaks(&self) -> &[Leak] {
+        &self.leaks
+    }
+
+    /// The number of leaked values.
+    pub fn len(&self) -> usize {
+        self.leaks.len()
+    }
+
+    /// Check if no values leaked.
+    pub fn is_empty(&self) -> bool {
+        self.leaks.is_empty()
+    }
+}
+
+impl fmt::Display for LeakReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.leaks.is_empty() {
+            return write!(f, "No JsValues leaked");
+        }
+        // Group the values by where they were created, in the order the groups first appear
+        let mut sites: Vec<(Option<&'static Location<'static>>, usize)> = Vec::new();
+        for leak in &self.leaks {
+            match sites.iter_mut().find(|(site, _)| *site == leak.location) {
+                Some((_, count)) => *count += 1,
+                None => sites.push((leak.location, 1)),
+            }
+        }
+        for (i, (site, count)) in sites.into_iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            let plural = if count == 1 { "JsValue" } else { "JsValues" };
+            match site {
+                Some(location) => write!(f, "{count} {plural} leaked, created at {location}")?,
+                None => write!(
+                    f,
+                    "{count} {plural} leaked, created by JS calling into Rust"
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
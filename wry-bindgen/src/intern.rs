@@ -28,7 +28,6 @@ thread_local! {
 #[cfg(feature = "enable-interning")]
 /// This returns the raw index of the cached JsValue, so you must take care
 /// so that you don't use it after it is freed.
-#[allow(dead_code)]
 pub(crate) fn unsafe_get_str(s: &str) -> Option<u64> {
     CACHE.with(|cache| {
         let cache = cache.entries.borrow();
@@ -61,6 +60,17 @@ fn unintern_str(key: &str) {
     })
 }
 
+/// Forget every interned string after the webview reloaded, since the cached
+/// values belong to the old page.
+pub(crate) fn clear_cache() {
+    #[cfg(feature = "enable-interning")]
+    {
+        // The entries are dropped after the cache borrow is released
+        let entries = CACHE.with(|cache| core::mem::take(&mut *cache.entries.borrow_mut()));
+        drop(entries);
+    }
+}
+
 /// Interns Rust strings so that it's much faster to send them to JS.
 ///
 /// Sending strings from Rust to JS is slow, because it has to do a full `O(n)`
@@ -70,7 +80,7 @@ fn unintern_str(key: &str) {
 /// If you are sending the same string multiple times, you can call this `intern`
 /// function, which simply returns its argument unchanged:
 ///
-/// ```rust
+/// ```ignore
 /// # use wry_bindgen::intern;
 /// intern("foo") // returns "foo"
 /// # ;
@@ -12,6 +12,8 @@ use alloc::vec::Vec;
 use base64::Engine;
 use core::fmt;
 
+use crate::JsValue;
+
 /// Error type for decoding binary IPC messages.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum DecodeError {
@@ -96,12 +98,14 @@ pub(crate) enum MessageType {
     Evaluate = 0,
     /// JS/Rust responding to a call
     Respond = 1,
+    /// JS/Rust responding to a call with an exception
+    Throw = 2,
 }
 
 /// A binary IPC message.
 ///
 /// Message format in the u8 buffer:
-/// - First u8: message type (0 = Evaluate, 1 = Respond)
+/// - First u8: message type (0 = Evaluate, 1 = Respond, 2 = Throw)
 /// - Remaining data depends on message type
 ///
 /// Evaluate format (supports batching - multiple operations in one message):
@@ -114,6 +118,17 @@ pub(crate) enum MessageType {
 /// - u8: message type (1)
 /// - For each operation result:
 ///   - encoded return value (varies by function)
+///
+/// Throw format from JS (sent instead of Respond when an operation throws):
+/// - u8: message type (2)
+/// - u32: function ID of the operation that threw
+/// - u32: index of the operation in the Evaluate message
+/// - str: the exception message
+/// - str: the JS stack trace, or an empty string
+///
+/// Throw format from Rust (sent instead of Respond when a callback panics or throws):
+/// - u8: message type (2)
+/// - u64: heap ID of the value JS should throw
 #[derive(Debug, Clone)]
 pub(crate) struct IPCMessage {
     data: Vec<u8>,
@@ -135,6 +150,15 @@ impl IPCMessage {
         IPCMessage::new(encoder.to_bytes())
     }
 
+    /// Create a new throw message that makes JS throw the given value.
+    pub fn new_throw(value: &JsValue) -> Self {
+        let mut encoder = EncodedData::new();
+        encoder.push_u8(MessageType::Throw as u8);
+        encoder.push_u64(value.js_id());
+
+        IPCMessage::new(encoder.to_bytes())
+    }
+
     /// Get the message type.
     pub fn ty(&self) -> Result<MessageType, DecodeError> {
         let mut decoded = DecodedData::from_bytes(&self.data)?;
@@ -142,6 +166,7 @@ impl IPCMessage {
         match message_type {
             0 => Ok(MessageType::Evaluate),
             1 => Ok(MessageType::Respond),
+            2 => Ok(MessageType::Throw),
             v => Err(DecodeError::InvalidMessageType { value: v }),
         }
     }
@@ -153,6 +178,7 @@ impl IPCMessage {
         let message_type = match message_type {
             0 => DecodedVariant::Evaluate { data: decoded },
             1 => DecodedVariant::Respond { data: decoded },
+            2 => DecodedVariant::Throw { data: decoded },
             v => return Err(DecodeError::InvalidMessageType { value: v }),
         };
         Ok(message_type)
@@ -169,6 +195,96 @@ impl IPCMessage {
     }
 }
 
+/// The start of every Evaluate message, which keeps the Rust and JS heaps in sync.
+///
+/// Both sides allocate heap ids in the same order, popping freed ids before
+/// growing the heap. JS frees a slot when it runs a drop, but only reuses the id
+/// once Rust lists it in `recycled`. Rust does that after JS responded to the
+/// message with the drop, so both sides add freed ids to their free lists at
+/// the same point.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct EvaluateHeader {
+    /// How many ids Rust handed out as placeholders for the message's return values
+    pub(crate) reserved: u32,
+    /// Freed ids both sides add to the end of their free lists, after reserving
+    pub(crate) recycled: Vec<u64>,
+    /// Rust's heap after recycling, sent by debug builds to check JS agrees
+    pub(crate) check: Option<HeapCheck>,
+}
+
+/// A summary of the heap's free list, compared by JS in debug builds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct HeapCheck {
+    pub(crate) free_ids: u32,
+    pub(crate) max_id: u64,
+    /// FNV-1a hash of the free list, see [`free_list_hash`]
+    pub(crate) hash: u32,
+}
+
+impl HeapCheck {
+    pub(crate) fn new(free_ids: &[u64], max_id: u64) -> Self {
+        Self {
+            free_ids: free_ids.len() as u32,
+            max_id,
+            hash: free_list_hash(free_ids),
+        }
+    }
+}
+
+/// Hash the free list in order. Must match `freeListHash` in `ts/heap.ts`.
+pub(crate) fn free_list_hash(free_ids: &[u64]) -> u32 {
+    let mut hash: u32 = 2166136261;
+    for &id in free_ids {
+        for half in [id as u32, (id >> 32) as u32] {
+            hash = (hash ^ half).wrapping_mul(16777619);
+        }
+    }
+    hash
+}
+
+impl EvaluateHeader {
+    /// Add the header to the start of an Evaluate message.
+    pub(crate) fn prepend_to(&self, encoder: &mut EncodedData) {
+        let mut header = EncodedData::new();
+        header.push_u32(self.reserved);
+        header.push_u32(self.recycled.len() as u32);
+        for &id in &self.recycled {
+            header.push_u64(id);
+        }
+        match self.check {
+            Some(check) => {
+                header.push_u32(1);
+                header.push_u32(check.free_ids);
+                header.push_u64(check.max_id);
+                header.push_u32(check.hash);
+            }
+            None => header.push_u32(0),
+        }
+        encoder.prepend_u32s(&header.u32_buf);
+    }
+
+    /// Read the header from the start of an Evaluate message.
+    pub(crate) fn decode(data: &mut DecodedData) -> Result<Self, DecodeError> {
+        let reserved = data.take_u32()?;
+        let recycled = (0..data.take_u32()?)
+            .map(|_| data.take_u64())
+            .collect::<Result<_, _>>()?;
+        let check = match data.take_u32()? {
+            0 => None,
+            _ => Some(HeapCheck {
+                free_ids: data.take_u32()?,
+                max_id: data.take_u64()?,
+                hash: data.take_u32()?,
+            }),
+        };
+        Ok(Self {
+            reserved,
+            recycled,
+            check,
+        })
+    }
+}
+
 /// Decoded message variant.
 #[derive(Debug)]
 pub(crate) enum DecodedVariant<'a> {
@@ -176,6 +292,8 @@ pub(crate) enum DecodedVariant<'a> {
     Respond { data: DecodedData<'a> },
     /// Evaluation request
     Evaluate { data: DecodedData<'a> },
+    /// An operation in an evaluation request threw
+    Throw { data: DecodedData<'a> },
 }
 
 /// Decoded binary data with aligned buffer access.
@@ -188,6 +306,16 @@ pub struct DecodedData<'a> {
 }
 
 impl<'a> DecodedData<'a> {
+    /// Read data that only has u8 values, like a type definition.
+    pub(crate) fn from_u8s(u8_buf: &'a [u8]) -> Self {
+        Self {
+            u8_buf,
+            u16_buf: &[],
+            u32_buf: &[],
+            str_buf: &[],
+        }
+    }
+
     /// Parse decoded data from raw bytes.
     pub(crate) fn from_bytes(bytes: &'a [u8]) -> Result<Self, DecodeError> {
         if bytes.len() < 12 {
@@ -259,6 +387,12 @@ impl<'a> DecodedData<'a> {
         Ok(*first)
     }
 
+    /// Get the next u32 in the buffer without taking it.
+    #[cfg(feature = "fake")]
+    pub(crate) fn peek_u32(&self) -> Option<u32> {
+        self.u32_buf.first().copied()
+    }
+
     /// Take a u64 from the buffer (stored as two u32s).
     pub(crate) fn take_u64(&mut self) -> Result<u64, DecodeError> {
         let low = self.take_u32()? as u64;
@@ -290,6 +424,11 @@ impl<'a> DecodedData<'a> {
         Ok(s)
     }
 
+    /// Check if there are any u32s left to read.
+    pub(crate) fn has_more_u32(&self) -> bool {
+        !self.u32_buf.is_empty()
+    }
+
     /// Check if the decoded data is empty.
     pub(crate) fn is_empty(&self) -> bool {
         self.u8_buf.is_empty()
@@ -353,11 +492,15 @@ impl EncodedData {
     }
 
     /// Prepend a u32 to the beginning of the buffer.
-    /// Used to add the reserved placeholder count at the start of batch messages.
     pub fn prepend_u32(&mut self, value: u32) {
         self.u32_buf.insert(0, value);
     }
 
+    /// Prepend u32s to the beginning of the buffer, keeping their order.
+    pub(crate) fn prepend_u32s(&mut self, values: &[u32]) {
+        self.u32_buf.splice(0..0, values.iter().copied());
+    }
+
     /// Push a u64 to the buffer (stored as two u32s).
     pub(crate) fn push_u64(&mut self, value: u64) {
         self.push_u32((value & 0xFFFFFFFF) as u32);
@@ -1 +1,136 @@
-function is_undefined(x){return x===void 0}function is_null(x){return x===null}function is_true(x){return x===!0}function is_false(x){return x===!1}function get_typeof(x){return typeof x}function is_falsy(x){return!x}function is_truthy(x){return!!x}function is_object(x){return typeof x==="object"&&x!==null}function is_function(x){return typeof x==="function"}function is_string(x){return typeof x==="string"}function is_symbol(x){return typeof x==="symbol"}function is_bigint(x){return typeof x==="bigint"}function as_string(x){return typeof x==="string"?x:null}function as_f64(x){return typeof x==="number"?x:null}function debug_string(x){try{return x.toString()}catch{return"[unrepresentable]"}}function js_checked_div(a,b){try{return a/b}catch(e){return e}}function js_pow(a,b){return a**b}function js_add(a,b){return a+b}function js_sub(a,b){return a-b}function js_mul(a,b){return a*b}function js_div(a,b){return a/b}function js_rem(a,b){return a%b}function js_neg(a){return-a}function js_bit_and(a,b){return a&b}function js_bit_or(a,b){return a|b}function js_bit_xor(a,b){return a^b}function js_bit_not(a){return~a}function js_shl(a,b){return a<<b}function js_shr(a,b){return a>>b}function js_unsigned_shr(a,b){return a>>>b}function js_lt(a,b){return a<b}function js_le(a,b){return a<=b}function js_gt(a,b){return a>b}function js_ge(a,b){return a>=b}function js_loose_eq(a,b){return a==b}function js_in(prop,obj){return prop in obj}function is_error(x){return x instanceof Error}function clone_heap_ref(heapId){return window.jsHeap.get(heapId)}function drop_heap_ref(heapId){window.jsHeap.remove(heapId)}function create_rust_object_wrapper(handle,className){return window.rustExports.createWrapper(handle,className)}function extract_rust_handle(obj){return obj&&typeof obj.__handle==="number"?obj.__handle:null}export{js_unsigned_shr,js_sub,js_shr,js_shl,js_rem,js_pow,js_neg,js_mul,js_lt,js_loose_eq,js_le,js_in,js_gt,js_ge,js_div,js_checked_div,js_bit_xor,js_bit_or,js_bit_not,js_bit_and,js_add,is_undefined,is_truthy,is_true,is_symbol,is_string,is_object,is_null,is_function,is_falsy,is_false,is_error,is_bigint,get_typeof,extract_rust_handle,drop_heap_ref,debug_string,create_rust_object_wrapper,clone_heap_ref,as_string,as_f64};
+function is_undefined(x){
+return x===undefined;
+}
+function is_null(x){
+return x===null;
+}
+function is_true(x){
+return x===true;
+}
+function is_false(x){
+return x===false;
+}
+function get_typeof(x){
+return typeof x;
+}
+function is_falsy(x){
+return!x;
+}
+function is_truthy(x){
+return!!x;
+}
+function is_object(x){
+return typeof x==="object"&&x!==null;
+}
+function is_function(x){
+return typeof x==="function";
+}
+function is_string(x){
+return typeof x==="string";
+}
+function is_symbol(x){
+return typeof x==="symbol";
+}
+function is_bigint(x){
+return typeof x==="bigint";
+}
+function as_string(x){
+return typeof x==="string"?x:null;
+}
+function as_f64(x){
+return typeof x==="number"?x:null;
+}
+function debug_string(x){
+try{
+return x.toString();
+}catch{
+return"[unrepresentable]";
+}
+}
+function js_checked_div(a,b){
+try{
+return a/b;
+}catch(e){
+return e;
+}
+}
+function js_pow(a,b){
+return a**b;
+}
+function js_add(a,b){
+return a+b;
+}
+function js_sub(a,b){
+return a-b;
+}
+function js_mul(a,b){
+return a*b;
+}
+function js_div(a,b){
+return a/b;
+}
+function js_rem(a,b){
+return a%b;
+}
+function js_neg(a){
+return-a;
+}
+function js_bit_and(a,b){
+return a&b;
+}
+function js_bit_or(a,b){
+return a|b;
+}
+function js_bit_xor(a,b){
+return a^b;
+}
+function js_bit_not(a){
+return~a;
+}
+function js_shl(a,b){
+return a<<b;
+}
+function js_shr(a,b){
+return a>>b;
+}
+function js_unsigned_shr(a,b){
+return a>>>b;
+}
+function js_lt(a,b){
+return a<b;
+}
+function js_le(a,b){
+return a<=b;
+}
+function js_gt(a,b){
+return a>b;
+}
+function js_ge(a,b){
+return a>=b;
+}
+function js_loose_eq(a,b){
+return a==b;
+}
+function js_in(prop,obj){
+return prop in obj;
+}
+function is_error(x){
+return x instanceof Error;
+}
+function clone_heap_ref(heapId){
+return window.jsHeap.get(heapId);
+}
+function drop_heap_ref(heapId){
+window.jsHeap.remove(heapId);
+}
+function heap_objects_alive(){
+return window.jsHeap.heapObjectsAlive();
+}
+function create_rust_object_wrapper(handle,className){
+return window.rustExports.createWrapper(handle,className);
+}
+function extract_rust_handle(obj){
+return(obj&&typeof obj.__handle==='number')?obj.__handle:null;
+}
+
+export { js_unsigned_shr, js_sub, js_shr, js_shl, js_rem, js_pow, js_neg, js_mul, js_lt, js_loose_eq, js_le, js_in, js_gt, js_ge, js_div, js_checked_div, js_bit_xor, js_bit_or, js_bit_not, js_bit_and, js_add, is_undefined, is_truthy, is_true, is_symbol, is_string, is_object, is_null, is_function, is_falsy, is_false, is_error, is_bigint, heap_objects_alive, get_typeof, extract_rust_handle, drop_heap_ref, debug_string, create_rust_object_wrapper, clone_heap_ref, as_string, as_f64 };
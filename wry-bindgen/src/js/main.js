@@ -1 +1,1211 @@
-class JSHeap{slots;freeIds;maxId;borrowStackPointer;borrowFrameStack;reservationStack;constructor(){this.slots=[],this.slots[129]=null,this.slots[130]=!0,this.slots[131]=!1,this.slots[128]=void 0,this.freeIds=[],this.maxId=132,this.borrowStackPointer=128,this.borrowFrameStack=[],this.reservationStack=[]}insert(value){let id=this.maxId;return this.maxId++,this.slots[id]=value,id}pushReservationScope(count){let start=this.maxId;this.reservationStack.push({start,count,nextIndex:0}),this.maxId+=count}popReservationScope(){this.reservationStack.pop()}fillNextReserved(value){let scope=this.reservationStack[this.reservationStack.length-1];if(!scope||scope.nextIndex>=scope.count)throw new Error("No reserved slots available");let id=scope.start+scope.nextIndex;scope.nextIndex++,this.slots[id]=value}get(id){return this.slots[id]}remove(id){if(id<132)return this.slots[id];let value=this.slots[id];return delete this.slots[id],this.freeIds.push(id),value}has(id){return this.freeIds.indexOf(id)===-1&&id<this.slots.length}heapObjectsAlive(){return this.slots.length-this.freeIds.length-132}addBorrowedRef(obj){if(this.borrowStackPointer<=1)throw new Error("Borrow stack overflow: too many borrowed references in a single operation");return this.borrowStackPointer--,this.slots[this.borrowStackPointer]=obj,this.borrowStackPointer}pushBorrowFrame(){this.borrowFrameStack.push(this.borrowStackPointer)}popBorrowFrame(){let savedPointer=this.borrowFrameStack.pop();if(savedPointer!==void 0){for(let i=this.borrowStackPointer;i<savedPointer;i++)delete this.slots[i];this.borrowStackPointer=savedPointer}}getBorrowStackPointer(){return this.borrowStackPointer}}class DataEncoder{u8Buf;u16Buf;u32Buf;strBuf;constructor(){this.u8Buf=[],this.u16Buf=[],this.u32Buf=[],this.strBuf=[]}pushU8(value){this.u8Buf.push(value&255)}pushU16(value){this.u16Buf.push(value&65535)}pushU32(value){this.u32Buf.push(value>>>0)}pushU64(value){let low=value>>>0,high=Math.floor(value/4294967296)>>>0;this.pushU32(low),this.pushU32(high)}pushU128(value){let low=value>>>0,high=Math.floor(value/18446744073709552000)>>>0;this.pushU64(low),this.pushU64(high)}pushF32(value){let floatBuf=new Float32Array(1);floatBuf[0]=value;let intBuf=new Uint32Array(floatBuf.buffer);this.pushU32(intBuf[0])}pushF64(value){let floatBuf=new Float64Array(1);floatBuf[0]=value;let intBuf=new Uint32Array(floatBuf.buffer);this.pushU32(intBuf[0]),this.pushU32(intBuf[1])}pushStr(value){let encoded=new TextEncoder().encode(value);this.pushU32(encoded.length);for(let i=0;i<encoded.length;i++)this.strBuf.push(encoded[i])}finalize(){let u16Offset=12+this.u32Buf.length*4,u8Offset=u16Offset+this.u16Buf.length*2,strOffset=u8Offset+this.u8Buf.length,totalSize=strOffset+this.strBuf.length,buffer=new ArrayBuffer(totalSize),dataView=new DataView(buffer);dataView.setUint32(0,u16Offset,!0),dataView.setUint32(4,u8Offset,!0),dataView.setUint32(8,strOffset,!0);let offset=12;for(let val of this.u32Buf)dataView.setUint32(offset,val,!0),offset+=4;for(let val of this.u16Buf)dataView.setUint16(offset,val,!0),offset+=2;return new Uint8Array(buffer,u8Offset,this.u8Buf.length).set(this.u8Buf),new Uint8Array(buffer,strOffset,this.strBuf.length).set(this.strBuf),buffer}}class DataDecoder{u8Buf;u8Offset;u16Buf;u16Offset;u32Buf;u32Offset;strBuf;strOffset;constructor(data){let headerView=new DataView(data,0,12),u16ByteOffset=headerView.getUint32(0,!0),u8ByteOffset=headerView.getUint32(4,!0),strByteOffset=headerView.getUint32(8,!0),u32ByteLength=u16ByteOffset-12;this.u32Buf=new Uint32Array(data,12,u32ByteLength/4),this.u32Offset=0;let u16ByteLength=u8ByteOffset-u16ByteOffset;this.u16Buf=new Uint16Array(data,u16ByteOffset,u16ByteLength/2),this.u16Offset=0;let u8ByteLength=strByteOffset-u8ByteOffset;this.u8Buf=new Uint8Array(data,u8ByteOffset,u8ByteLength),this.u8Offset=0;let strBuf=new Uint8Array(data,strByteOffset);this.strBuf=new TextDecoder("utf-8").decode(strBuf),this.strOffset=0}takeU8(){return this.u8Buf[this.u8Offset++]}takeU16(){return this.u16Buf[this.u16Offset++]}takeU32(){return this.u32Buf[this.u32Offset++]}hasMoreU32(){return this.u32Offset<this.u32Buf.length}takeU64(){let low=this.takeU32(),high=this.takeU32();return low+high*4294967296}takeU128(){let low=this.takeU64(),high=this.takeU64();return low+high*18446744073709552000}takeF32(){let intVal=this.takeU32(),intBuf=new Uint32Array(1);return intBuf[0]=intVal,new Float32Array(intBuf.buffer)[0]}takeF64(){let low=this.takeU32(),high=this.takeU32(),intBuf=new Uint32Array(2);return intBuf[0]=low,intBuf[1]=high,new Float64Array(intBuf.buffer)[0]}takeStr(){let len=this.takeU32(),str=this.strBuf.substring(this.strOffset,this.strOffset+len);return this.strOffset+=len,str}takeI8(){let unsigned=this.takeU8();return unsigned>127?unsigned-256:unsigned}takeI16(){let unsigned=this.takeU16();return unsigned>32767?unsigned-65536:unsigned}takeI32(){return this.takeU32()|0}takeI64(){let low=this.takeU32(),signedHigh=this.takeU32()|0;return low+signedHigh*4294967296}takeI128(){let low=this.takeU64(),signedHigh=this.takeU64()|0;return low+signedHigh*18446744073709552000}getRemainingBytes(){return this.u8Buf.subarray(this.u8Offset)}skipBytes(count){this.u8Offset+=count}isEmpty(){return this.u8Offset>=this.u8Buf.length&&this.u16Offset>=this.u16Buf.length&&this.u32Offset>=this.u32Buf.length&&this.strOffset>=this.strBuf.length}}var functionRegistry=null,typeCache=new Map;function getFunctionRegistry(){return functionRegistry}function setFunctionRegistry(registry){functionRegistry=registry}function getTypeCache(){return typeCache}var nativeRefRegistry=new FinalizationRegistry((fnId)=>{let encoder=new DataEncoder;encoder.pushU8(0),encoder.pushU32(DROP_NATIVE_REF_FN_ID),encoder.pushU32(fnId);let response=sync_request_binary("/__wbg__/handler",encoder.finalize());handleBinaryResponse(response)});class RustFunction{fnId;paramTypes;returnType;constructor(fnId,paramTypes,returnType){this.fnId=fnId,this.paramTypes=paramTypes,this.returnType=returnType,nativeRefRegistry.register(this,fnId)}call(...args){window.jsHeap.pushBorrowFrame();let encoder=new DataEncoder;encoder.pushU8(0),encoder.pushU32(0),encoder.pushU32(this.fnId);for(let i=0;i<this.paramTypes.length;i++)this.paramTypes[i].encode(encoder,args[i]);let response=sync_request_binary("/__wbg__/handler",encoder.finalize()),result=handleBinaryResponse(response);window.jsHeap.popBorrowFrame();let decoded=this.returnType.decode(result);if(result&&!result.isEmpty())throw new Error("Unprocessed data remaining after RustFunction call");return decoded}}class BoolType{encode(encoder,value){encoder.pushU8(value?1:0)}decode(decoder){return decoder.takeU8()!==0}}class HeapRefType{encode(encoder,obj){window.jsHeap.insert(obj)}decode(decoder){let id=decoder.takeU64();return window.jsHeap.get(id)}}class BorrowedRefType{encode(encoder,obj){window.jsHeap.addBorrowedRef(obj)}decode(decoder){let id=decoder.takeU64();return window.jsHeap.get(id)}}class StringType{encode(encoder,value){encoder.pushStr(value)}decode(decoder){return decoder.takeStr()}}class StringEnumType{lookupArray;constructor(lookupArray){this.lookupArray=lookupArray}encode(encoder,value){let index=this.lookupArray.indexOf(value),encoded=index>=0?index:this.lookupArray.length;encoder.pushU32(encoded)}decode(decoder){let index=decoder.takeU32();return this.lookupArray[index]}}class CallbackType{paramTypes;returnType;constructor(paramTypes,returnType){this.paramTypes=paramTypes,this.returnType=returnType}encode(encoder,fnId){encoder.pushU32(fnId)}decode(decoder){let fnId=decoder.takeU32(),f=new RustFunction(fnId,this.paramTypes,this.returnType);return(...args)=>f.call(...args)}}class NullType{encode(encoder,value){}decode(decoder){return null}}class NumericType{size;constructor(size){this.size=size}encode(encoder,value){switch(this.size){case"u8":encoder.pushU8(value);break;case"u16":encoder.pushU16(value);break;case"u32":encoder.pushU32(value);break;case"u64":encoder.pushU64(value);break;case"u128":encoder.pushU128(value);break;case"i8":encoder.pushU8(value&255);break;case"i16":encoder.pushU16(value&65535);break;case"i32":encoder.pushU32(value>>>0);break;case"i64":encoder.pushU64(value);break;case"i128":encoder.pushU128(value);break;case"usize":encoder.pushU64(value);break;case"isize":encoder.pushU64(value);break;case"f32":encoder.pushF32(value);break;case"f64":encoder.pushF64(value);break}}decode(decoder){switch(this.size){case"u8":return decoder.takeU8();case"u16":return decoder.takeU16();case"u32":return decoder.takeU32();case"u64":return decoder.takeU64();case"u128":return decoder.takeU128();case"i8":return decoder.takeI8();case"i16":return decoder.takeI16();case"i32":return decoder.takeI32();case"i64":return decoder.takeI64();case"i128":return decoder.takeI128();case"usize":return decoder.takeU64();case"isize":return decoder.takeI64();case"f32":return decoder.takeF32();case"f64":return decoder.takeF64()}}}class OptionType{wrappedType;constructor(wrappedType){this.wrappedType=wrappedType}encode(encoder,value){if(value===null||value===void 0)encoder.pushU8(0);else encoder.pushU8(1),this.wrappedType.encode(encoder,value)}decode(decoder){if(decoder.takeU8()===0)return null;else return this.wrappedType.decode(decoder)}}class ResultType{okType;errType;constructor(okType,errType){this.okType=okType,this.errType=errType}encode(encoder,value){let result=value;if("ok"in result)encoder.pushU8(1),this.okType.encode(encoder,result.ok);else if("err"in result)encoder.pushU8(0),this.errType.encode(encoder,result.err);else throw new Error("Invalid RustType value: must be Ok or Err")}decode(decoder){if(decoder.takeU8()===1)return{ok:this.okType.decode(decoder)};else return{err:this.errType.decode(decoder)}}}class ArrayType{elementType;constructor(elementType){this.elementType=elementType}encode(encoder,value){encoder.pushU32(value.length);for(let element of value)this.elementType.encode(encoder,element)}decode(decoder){let length=decoder.takeU32(),result=[];for(let i=0;i<length;i++)result.push(this.elementType.decode(decoder));return result}}class U8ClampedType{encode(encoder,value){encoder.pushU32(value.length);for(let i=0;i<value.length;i++)encoder.pushU8(value[i])}decode(decoder){let length=decoder.takeU32(),result=new Uint8ClampedArray(length);for(let i=0;i<length;i++)result[i]=decoder.takeU8();return result}}var u8ClampedTypeInstance=new U8ClampedType,U8Type=new NumericType("u8"),U16Type=new NumericType("u16"),U32Type=new NumericType("u32"),U64Type=new NumericType("u64"),U128Type=new NumericType("u128"),I8Type=new NumericType("i8"),I16Type=new NumericType("i16"),I32Type=new NumericType("i32"),I64Type=new NumericType("i64"),I128Type=new NumericType("i128"),UsizeType=new NumericType("usize"),IsizeType=new NumericType("isize"),F32Type=new NumericType("f32"),F64Type=new NumericType("f64"),strType=new StringType,boolTypeInstance=new BoolType,nullTypeInstance=new NullType,heapRefTypeInstance=new HeapRefType,borrowedRefTypeInstance=new BorrowedRefType,stringTypeInstance=new StringType;function parseTypeDef(bytes,offset){let tag=bytes[offset.value++];switch(tag){case 0:return nullTypeInstance;case 1:return boolTypeInstance;case 2:return U8Type;case 3:return U16Type;case 4:return U32Type;case 5:return U64Type;case 6:return U128Type;case 7:return I8Type;case 8:return I16Type;case 9:return I32Type;case 10:return I64Type;case 11:return I128Type;case 12:return F32Type;case 13:return F64Type;case 14:return UsizeType;case 15:return IsizeType;case 16:return stringTypeInstance;case 17:return heapRefTypeInstance;case 22:return borrowedRefTypeInstance;case 18:{let paramCount=bytes[offset.value++],paramTypes=[];for(let i=0;i<paramCount;i++)paramTypes.push(parseTypeDef(bytes,offset));let returnType=parseTypeDef(bytes,offset);return new CallbackType(paramTypes,returnType)}case 19:{let innerType=parseTypeDef(bytes,offset);return new OptionType(innerType)}case 20:{let okType=parseTypeDef(bytes,offset),errType=parseTypeDef(bytes,offset);return new ResultType(okType,errType)}case 21:{let elementType=parseTypeDef(bytes,offset);return new ArrayType(elementType)}case 23:return u8ClampedTypeInstance;case 24:{let variantCount=bytes[offset.value++],lookupArray=[];for(let i=0;i<variantCount;i++){let len=bytes[offset.value]|bytes[offset.value+1]<<8|bytes[offset.value+2]<<16|bytes[offset.value+3]<<24;offset.value+=4;let strBytes=bytes.subarray(offset.value,offset.value+len);offset.value+=len,lookupArray.push(new TextDecoder().decode(strBytes))}return new StringEnumType(lookupArray)}default:throw new Error(`Unknown TypeTag: ${tag}`)}}var TYPE_CACHED=255,TYPE_FULL=254,DROP_NATIVE_REF_FN_ID=4294967295,CALL_EXPORT_FN_ID=4294967294;function sync_request_binary(endpoint,data){let xhr=new XMLHttpRequest;xhr.open("POST",endpoint,!1);let bytes=new Uint8Array(data),binary="";for(let i=0;i<bytes.length;i++)binary+=String.fromCharCode(bytes[i]);let base64=btoa(binary);if(xhr.setRequestHeader("dioxus-data",base64),xhr.send(),xhr.status===200&&xhr.responseText){let responseBinary=atob(xhr.responseText),responseBytes=new Uint8Array(responseBinary.length);for(let i=0;i<responseBinary.length;i++)responseBytes[i]=responseBinary.charCodeAt(i);return responseBytes.buffer}return null}function evaluate_from_rust_binary(dataBase64){let binary=atob(dataBase64),bytes=new Uint8Array(binary.length);for(let i=0;i<binary.length;i++)bytes[i]=binary.charCodeAt(i);if(handleBinaryResponse(bytes.buffer))throw new Error("Unprocessed data remaining after Evaluate handling")}function parseTypeInfo(decoder){let typeCache2=getTypeCache(),typeMarker=decoder.takeU8();if(typeMarker===TYPE_CACHED){let typeId=decoder.takeU32(),cached=typeCache2.get(typeId);if(!cached)throw new Error(`Unknown cached type ID: ${typeId}`);return cached}else if(typeMarker===TYPE_FULL){let typeId=decoder.takeU32(),paramCount=decoder.takeU8(),typeBytes=decoder.getRemainingBytes(),offset={value:0},paramTypes=[];for(let i=0;i<paramCount;i++)paramTypes.push(parseTypeDef(typeBytes,offset));let returnType=parseTypeDef(typeBytes,offset);decoder.skipBytes(offset.value);let cached={paramTypes,returnType};return typeCache2.set(typeId,cached),cached}else throw new Error(`Unknown type marker: ${typeMarker}`)}function handleBinaryResponse(response){if(!response||response.byteLength===0)return null;let decoder=new DataDecoder(response),msgType=decoder.takeU8();if(msgType===1)return decoder;else if(msgType===0){let reservedCount=decoder.takeU32();window.jsHeap.pushReservationScope(reservedCount);let encoder=new DataEncoder;encoder.pushU8(1),window.jsHeap.pushBorrowFrame();while(decoder.hasMoreU32()){let fnId=decoder.takeU32(),typeInfo=parseTypeInfo(decoder),jsFunction=getFunctionRegistry()[fnId];if(!jsFunction)throw new Error("Unknown function ID in response: "+fnId);let params=typeInfo.paramTypes.map((paramType)=>paramType.decode(decoder)),result=jsFunction(...params);if(typeInfo.returnType instanceof HeapRefType&&reservedCount>0)window.jsHeap.fillNextReserved(result);else typeInfo.returnType.encode(encoder,result)}window.jsHeap.popBorrowFrame(),window.jsHeap.popReservationScope();let nextResponse=sync_request_binary("/__wbg__/handler",encoder.finalize());return handleBinaryResponse(nextResponse)}if(!decoder.isEmpty())throw new Error("Unprocessed data remaining after Evaluate handling");return null}var exportRegistry=new FinalizationRegistry((info)=>{let encoder=new DataEncoder;encoder.pushU8(0),encoder.pushU32(CALL_EXPORT_FN_ID);let dropName=`${info.className}::__drop`;encoder.pushStr(dropName),encoder.pushU32(info.handle);let response=sync_request_binary("/__wbg__/handler",encoder.finalize());handleBinaryResponse(response)});function callExport(exportName,...args){window.jsHeap.pushBorrowFrame();let encoder=new DataEncoder;encoder.pushU8(0),encoder.pushU32(CALL_EXPORT_FN_ID),encoder.pushStr(exportName);for(let arg of args)if(typeof arg==="number")encoder.pushU32(arg);else throw new Error(`Unsupported argument type: ${typeof arg}`);let response=sync_request_binary("/__wbg__/handler",encoder.finalize()),decoder=handleBinaryResponse(response);if(window.jsHeap.popBorrowFrame(),decoder&&decoder.hasMoreU32())return decoder.takeI32();return}function createWrapper(handle,className){let ClassConstructor=window[className];if(ClassConstructor&&typeof ClassConstructor.__wrap==="function")return ClassConstructor.__wrap(handle);let proxy=new Proxy({__handle:handle,__className:className},{get(target,prop){if(prop==="__handle"||prop==="__className")return target[prop];if(typeof prop==="symbol"||prop==="then"||prop==="toJSON")return;return(...args)=>{let exportName=`${className}::${String(prop)}`;return callExport(exportName,handle,...args)}}});return exportRegistry.register(proxy,{handle,className}),proxy}window.__wryCallExport=callExport;window.__wryExportRegistry=exportRegistry;var rustExports={createWrapper,callExport};window.setFunctionRegistry=setFunctionRegistry;window.evaluate_from_rust_binary=evaluate_from_rust_binary;window.jsHeap=new JSHeap;window.rustExports=rustExports;
+const JSIDX_OFFSET=128;
+const JSIDX_UNDEFINED=JSIDX_OFFSET;
+const JSIDX_NULL=JSIDX_OFFSET+1;
+const JSIDX_TRUE=JSIDX_OFFSET+2;
+const JSIDX_FALSE=JSIDX_OFFSET+3;
+const JSIDX_RESERVED=JSIDX_OFFSET+4;
+function freeListHash(freeIds){
+let hash=2166136261;
+for(const id of freeIds){
+hash=Math.imul(hash^(id>>>0),16777619)>>>0;
+hash=Math.imul(hash^Math.floor(id/0x100000000),16777619)>>>0;
+}
+return hash;
+}
+class JSHeap{
+slots;
+freeIds;
+maxId;
+alive;
+borrowStackPointer;
+borrowFrameStack;
+reservationStack;
+constructor(){
+this.slots=[];
+this.slots[JSIDX_NULL]=null;
+this.slots[JSIDX_TRUE]=true;
+this.slots[JSIDX_FALSE]=false;
+this.slots[JSIDX_UNDEFINED]=undefined;
+this.freeIds=[];
+this.maxId=JSIDX_RESERVED;
+this.alive=0;
+this.borrowStackPointer=JSIDX_OFFSET;
+this.borrowFrameStack=[];
+this.reservationStack=[];
+}
+allocId(){
+return this.freeIds.pop()??this.maxId++;
+}
+set(id,value){
+if(!(id in this.slots)){
+this.alive++;
+}
+this.slots[id]=value;
+}
+insert(value){
+const id=this.allocId();
+this.set(id,value);
+return id;
+}
+pushReservationScope(count){
+const ids=[];
+for(let i=0;i<count;i++){
+ids.push(this.allocId());
+}
+this.reservationStack.push({ids,nextIndex:0});
+}
+popReservationScope(){
+this.reservationStack.pop();
+}
+fillNextReserved(value){
+const scope=this.reservationStack[this.reservationStack.length-1];
+if(!scope||scope.nextIndex>=scope.ids.length){
+throw new Error("No reserved slots available");
+}
+const id=scope.ids[scope.nextIndex];
+scope.nextIndex++;
+this.set(id,value);
+}
+recycle(ids){
+for(const id of ids){
+this.freeIds.push(id);
+}
+}
+checkFreeList(freeIds,maxId,hash){
+const ourHash=freeListHash(this.freeIds);
+if(freeIds===this.freeIds.length&&maxId===this.maxId&&hash===ourHash){
+return null;
+}
+return(
+`The JS heap is out of sync with Rust: JS has ${this.freeIds.length} free ids, `+
+`max id ${this.maxId} and hash ${ourHash}, Rust has ${freeIds} free ids, `+
+`max id ${maxId} and hash ${hash}`
+);
+}
+get(id){
+return this.slots[id];
+}
+remove(id){
+if(id<JSIDX_RESERVED){
+return this.slots[id];
+}
+const value=this.slots[id];
+if(id in this.slots){
+this.alive--;
+}
+delete this.slots[id];
+return value;
+}
+has(id){
+return id in this.slots;
+}
+heapObjectsAlive(){
+return this.alive;
+}
+addBorrowedRef(obj){
+if(this.borrowStackPointer<=1){
+throw new Error(
+"Borrow stack overflow: too many borrowed references in a single operation"
+);
+}
+this.borrowStackPointer--;
+this.slots[this.borrowStackPointer]=obj;
+return this.borrowStackPointer;
+}
+pushBorrowFrame(){
+this.borrowFrameStack.push(this.borrowStackPointer);
+}
+popBorrowFrame(){
+const savedPointer=this.borrowFrameStack.pop();
+if(savedPointer!==undefined){
+for(let i=this.borrowStackPointer;i<savedPointer;i++){
+delete this.slots[i];
+}
+this.borrowStackPointer=savedPointer;
+}
+}
+getBorrowStackPointer(){
+return this.borrowStackPointer;
+}
+}
+
+let functionRegistry=[];
+let functionCount=0;
+let moduleNames=[];
+let moduleValues=[];
+const typeCache=new Map();
+let functionTypes=[];
+function getFunction(fnId){
+const loaded=functionRegistry[fnId];
+if(loaded||fnId>=functionCount){
+return loaded;
+}
+const xhr=new XMLHttpRequest();
+xhr.open("GET",`/__wbg__/fn/${fnId}`,false);
+xhr.send();
+if(xhr.status!==200){
+throw new Error(`Failed to load the code of JS function ${fnId}`);
+}
+loadFunctions(xhr.responseText);
+return functionRegistry[fnId];
+}
+function loadFunctions(chunk){
+const functions=new Function(
+...moduleNames,
+`return ${chunk};`
+)(...moduleValues);
+for(const[fnId,jsFunction]of Object.entries(functions)){
+functionRegistry[Number(fnId)]=jsFunction;
+}
+}
+function setFunctionRegistry(
+count,
+modules={},
+types=[],
+functionTypeIds=[]
+){
+functionRegistry=[];
+functionCount=count;
+moduleNames=Object.keys(modules);
+moduleValues=Object.values(modules);
+const parsed=types.map(parseSignature);
+functionTypes=functionTypeIds.map((typeId)=>
+typeId===null?undefined:parsed[typeId]
+);
+}
+function parseSignature(signature){
+const bytes=new Uint8Array(signature);
+const offset={value:1};
+const paramTypes=[];
+for(let i=0;i<bytes[0];i++){
+paramTypes.push(parseTypeDef(bytes,offset));
+}
+const returnType=parseTypeDef(bytes,offset);
+return{paramTypes,returnType};
+}
+function getFunctionTypes(){
+return functionTypes;
+}
+function getTypeCache(){
+return typeCache;
+}
+
+const exportRegistry=new FinalizationRegistry((info)=>{
+const encoder=new DataEncoder();
+encoder.pushU8(MessageType.Evaluate);
+encoder.pushU32(CALL_EXPORT_FN_ID);
+const dropName=`${info.className}::__drop`;
+encoder.pushStr(dropName);
+encoder.pushU32(info.handle);
+const response=sync_request_binary(`/__wbg__/handler`,encoder.finalize());
+handleBinaryResponse(response);
+});
+function callExport(exportName,...args){
+window.jsHeap.pushBorrowFrame();
+const encoder=new DataEncoder();
+encoder.pushU8(MessageType.Evaluate);
+encoder.pushU32(CALL_EXPORT_FN_ID);
+encoder.pushStr(exportName);
+for(const arg of args){
+if(typeof arg==="number"){
+encoder.pushU32(arg);
+}else{
+throw new Error(`Unsupported argument type: ${typeof arg}`);
+}
+}
+let decoder;
+try{
+const response=sync_request_binary(`/__wbg__/handler`,encoder.finalize());
+decoder=handleBinaryResponse(response);
+}finally{
+window.jsHeap.popBorrowFrame();
+}
+if(decoder&&decoder.hasMoreU32()){
+return decoder.takeI32();
+}
+return undefined;
+}
+function callTypedExport(exportName,typeInfo,args,handle){
+window.jsHeap.pushBorrowFrame();
+const encoder=new DataEncoder();
+encoder.pushU8(MessageType.Evaluate);
+encoder.pushU32(CALL_EXPORT_FN_ID);
+encoder.pushStr(exportName);
+if(handle!==undefined){
+encoder.pushU32(handle);
+}
+for(let i=0;i<typeInfo.paramTypes.length;i++){
+typeInfo.paramTypes[i].encode(encoder,args[i]);
+}
+let result;
+try{
+const response=sync_request_binary(`/__wbg__/handler`,encoder.finalize());
+result=handleBinaryResponse(response);
+}finally{
+window.jsHeap.popBorrowFrame();
+}
+const decoded=typeInfo.returnType.decode(result);
+if(result&&!result.isEmpty()){
+throw new Error(`Unprocessed data remaining after calling ${exportName}`);
+}
+if(typeInfo.returnType instanceof ResultType){
+if("err"in decoded){
+throw decoded.err;
+}
+return decoded.ok;
+}
+return decoded;
+}
+function exportFunction(exportName,signature){
+const typeInfo=parseSignature(signature);
+return(...args)=>callTypedExport(exportName,typeInfo,args);
+}
+function exportMethod(exportName,signature,hasHandle){
+const typeInfo=parseSignature(signature);
+if(hasHandle){
+return(handle,...args)=>callTypedExport(exportName,typeInfo,args,handle);
+}
+return(...args)=>callTypedExport(exportName,typeInfo,args);
+}
+function createWrapper(handle,className,owned=true){
+const ClassConstructor=(window)[className];
+if(ClassConstructor&&typeof ClassConstructor.__wrap==='function'){
+const obj=ClassConstructor.__wrap(handle);
+if(!owned){
+exportRegistry.unregister(obj);
+}
+return obj;
+}
+const wrapper={
+__handle:handle,
+__className:className,
+};
+const proxy=new Proxy(wrapper,{
+get(target,prop){
+if(prop==="__handle"||prop==="__className"){
+return target[prop];
+}
+if(typeof prop==="symbol"||prop==="then"||prop==="toJSON"){
+return undefined;
+}
+return(...args)=>{
+const exportName=`${className}::${String(prop)}`;
+return callExport(exportName,handle,...args);
+};
+},
+});
+if(owned){
+exportRegistry.register(proxy,{handle,className},proxy);
+}
+return proxy;
+}
+function takeHandle(obj,className,borrowed){
+if(obj===null||typeof obj!=="object"||obj.__className!==className){
+throw new Error(`Expected an instance of ${className}`);
+}
+const handle=obj.__handle;
+if(handle===0){
+throw new Error(`${className} was already moved to Rust or freed`);
+}
+if(!borrowed){
+exportRegistry.unregister(obj);
+obj.__handle=0;
+}
+return handle;
+}
+(window).__wryCallExport=callExport;
+(window).__wryExportRegistry=exportRegistry;
+const rustExports={
+createWrapper,
+callExport,
+exportFunction,
+exportMethod,
+};
+
+var TypeTag;
+(function (TypeTag) {
+  TypeTag[TypeTag["Null"] = 0] = "Null";
+  TypeTag[TypeTag["Bool"] = 1] = "Bool";
+  TypeTag[TypeTag["U8"] = 2] = "U8";
+  TypeTag[TypeTag["U16"] = 3] = "U16";
+  TypeTag[TypeTag["U32"] = 4] = "U32";
+  TypeTag[TypeTag["U64"] = 5] = "U64";
+  TypeTag[TypeTag["U128"] = 6] = "U128";
+  TypeTag[TypeTag["I8"] = 7] = "I8";
+  TypeTag[TypeTag["I16"] = 8] = "I16";
+  TypeTag[TypeTag["I32"] = 9] = "I32";
+  TypeTag[TypeTag["I64"] = 10] = "I64";
+  TypeTag[TypeTag["I128"] = 11] = "I128";
+  TypeTag[TypeTag["F32"] = 12] = "F32";
+  TypeTag[TypeTag["F64"] = 13] = "F64";
+  TypeTag[TypeTag["Usize"] = 14] = "Usize";
+  TypeTag[TypeTag["Isize"] = 15] = "Isize";
+  TypeTag[TypeTag["String"] = 16] = "String";
+  TypeTag[TypeTag["HeapRef"] = 17] = "HeapRef";
+  TypeTag[TypeTag["Callback"] = 18] = "Callback";
+  TypeTag[TypeTag["Option"] = 19] = "Option";
+  TypeTag[TypeTag["Result"] = 20] = "Result";
+  TypeTag[TypeTag["Array"] = 21] = "Array";
+  TypeTag[TypeTag["BorrowedRef"] = 22] = "BorrowedRef";
+  TypeTag[TypeTag["U8Clamped"] = 23] = "U8Clamped";
+  TypeTag[TypeTag["StringEnum"] = 24] = "StringEnum";
+  TypeTag[TypeTag["RustObject"] = 25] = "RustObject";
+})(TypeTag || (TypeTag = {}));
+class BoolType{
+encode(encoder,value){
+encoder.pushU8(value?1:0);
+}
+decode(decoder){
+const val=decoder.takeU8();
+return val!==0;
+}
+}
+class HeapRefType{
+encode(encoder,obj){
+window.jsHeap.insert(obj);
+}
+decode(decoder){
+const id=decoder.takeU64();
+return window.jsHeap.get(id);
+}
+}
+class BorrowedRefType{
+encode(encoder,obj){
+window.jsHeap.addBorrowedRef(obj);
+}
+decode(decoder){
+const id=decoder.takeU64();
+return window.jsHeap.get(id);
+}
+}
+const INTERNED_STR=0xffffffff;
+class StringType{
+encode(encoder,value){
+encoder.pushStr(value);
+}
+decode(decoder){
+if(decoder.peekU32()===INTERNED_STR){
+decoder.takeU32();
+return window.jsHeap.get(decoder.takeU64());
+}
+return decoder.takeStr();
+}
+}
+class StringEnumType{
+lookupArray;
+constructor(lookupArray){
+this.lookupArray=lookupArray;
+}
+encode(encoder,value){
+const index=this.lookupArray.indexOf(value);
+const encoded=index>=0?index:this.lookupArray.length;
+encoder.pushU32(encoded);
+}
+decode(decoder){
+const index=decoder.takeU32();
+return this.lookupArray[index];
+}
+}
+class RustObjectType{
+className;
+borrowed;
+constructor(className,borrowed){
+this.className=className;
+this.borrowed=borrowed;
+}
+encode(encoder,value){
+encoder.pushU32(takeHandle(value,this.className,this.borrowed));
+}
+decode(decoder){
+const handle=decoder.takeU32();
+const owned=decoder.takeU8()!==0;
+return createWrapper(handle,this.className,owned);
+}
+}
+class CallbackType{
+paramTypes;
+returnType;
+constructor(paramTypes,returnType){
+this.paramTypes=paramTypes;
+this.returnType=returnType;
+}
+encode(encoder,fnId){
+encoder.pushU32(fnId);
+}
+decode(decoder){
+const fnId=decoder.takeU32();
+const f=new RustFunction(fnId,this.paramTypes,this.returnType);
+return(...args)=>f.call(...args);
+}
+}
+class NullType{
+encode(encoder,value){
+}
+decode(decoder){
+return null;
+}
+}
+class NumericType{
+size;
+constructor(size){
+this.size=size;
+}
+encode(encoder,value){
+switch(this.size){
+case"u8":
+encoder.pushU8(value);
+break;
+case"u16":
+encoder.pushU16(value);
+break;
+case"u32":
+encoder.pushU32(value);
+break;
+case"u64":
+encoder.pushU64(value);
+break;
+case"u128":
+encoder.pushU128(value);
+break;
+case"i8":
+encoder.pushU8(value&0xff);
+break;
+case"i16":
+encoder.pushU16(value&0xffff);
+break;
+case"i32":
+encoder.pushU32(value>>>0);
+break;
+case"i64":
+encoder.pushU64(value);
+break;
+case"i128":
+encoder.pushU128(value);
+break;
+case"usize":
+encoder.pushU64(value);
+break;
+case"isize":
+encoder.pushU64(value);
+break;
+case"f32":
+encoder.pushF32(value);
+break;
+case"f64":
+encoder.pushF64(value);
+break;
+}
+}
+decode(decoder){
+switch(this.size){
+case"u8":
+return decoder.takeU8();
+case"u16":
+return decoder.takeU16();
+case"u32":
+return decoder.takeU32();
+case"u64":
+return decoder.takeU64();
+case"u128":
+return decoder.takeU128();
+case"i8":
+return decoder.takeI8();
+case"i16":
+return decoder.takeI16();
+case"i32":
+return decoder.takeI32();
+case"i64":
+return decoder.takeI64();
+case"i128":
+return decoder.takeI128();
+case"usize":
+return decoder.takeU64();
+case"isize":
+return decoder.takeI64();
+case"f32":
+return decoder.takeF32();
+case"f64":
+return decoder.takeF64();
+}
+}
+}
+class OptionType{
+wrappedType;
+constructor(wrappedType){
+this.wrappedType=wrappedType;
+}
+encode(encoder,value){
+if(value===null||value===undefined){
+encoder.pushU8(0);
+}else{
+encoder.pushU8(1);
+this.wrappedType.encode(encoder,value);
+}
+}
+decode(decoder){
+const isPresent=decoder.takeU8();
+if(isPresent===0){
+return null;
+}else{
+return this.wrappedType.decode(decoder);
+}
+}
+}
+class ResultType{
+okType;
+errType;
+constructor(okType,errType){
+this.okType=okType;
+this.errType=errType;
+}
+encode(encoder,value){
+const result=value;
+if("ok"in result){
+encoder.pushU8(1);
+this.okType.encode(encoder,result.ok);
+}else if("err"in result){
+encoder.pushU8(0);
+this.errType.encode(encoder,result.err);
+}else{
+throw new Error("Invalid RustType value: must be Ok or Err");
+}
+}
+decode(decoder){
+const isOk=decoder.takeU8();
+if(isOk===1){
+const okValue=this.okType.decode(decoder);
+return{ok:okValue};
+}else{
+const errValue=this.errType.decode(decoder);
+return{err:errValue};
+}
+}
+}
+class ArrayType{
+elementType;
+constructor(elementType){
+this.elementType=elementType;
+}
+encode(encoder,value){
+encoder.pushU32(value.length);
+for(const element of value){
+this.elementType.encode(encoder,element);
+}
+}
+decode(decoder){
+const length=decoder.takeU32();
+const result=[];
+for(let i=0;i<length;i++){
+result.push(this.elementType.decode(decoder));
+}
+return result;
+}
+}
+class U8ClampedType{
+encode(encoder,value){
+encoder.pushU32(value.length);
+for(let i=0;i<value.length;i++){
+encoder.pushU8(value[i]);
+}
+}
+decode(decoder){
+const length=decoder.takeU32();
+const result=new Uint8ClampedArray(length);
+for(let i=0;i<length;i++){
+result[i]=decoder.takeU8();
+}
+return result;
+}
+}
+const u8ClampedTypeInstance=new U8ClampedType();
+const U8Type=new NumericType("u8");
+const U16Type=new NumericType("u16");
+const U32Type=new NumericType("u32");
+const U64Type=new NumericType("u64");
+const U128Type=new NumericType("u128");
+const I8Type=new NumericType("i8");
+const I16Type=new NumericType("i16");
+const I32Type=new NumericType("i32");
+const I64Type=new NumericType("i64");
+const I128Type=new NumericType("i128");
+const UsizeType=new NumericType("usize");
+const IsizeType=new NumericType("isize");
+const F32Type=new NumericType("f32");
+const F64Type=new NumericType("f64");
+const strType=new StringType();
+const boolTypeInstance=new BoolType();
+const nullTypeInstance=new NullType();
+const heapRefTypeInstance=new HeapRefType();
+const borrowedRefTypeInstance=new BorrowedRefType();
+const stringTypeInstance=new StringType();
+function parseTypeDef(bytes,offset){
+const tag=bytes[offset.value++];
+switch(tag){
+case TypeTag.Null:
+return nullTypeInstance;
+case TypeTag.Bool:
+return boolTypeInstance;
+case TypeTag.U8:
+return U8Type;
+case TypeTag.U16:
+return U16Type;
+case TypeTag.U32:
+return U32Type;
+case TypeTag.U64:
+return U64Type;
+case TypeTag.U128:
+return U128Type;
+case TypeTag.I8:
+return I8Type;
+case TypeTag.I16:
+return I16Type;
+case TypeTag.I32:
+return I32Type;
+case TypeTag.I64:
+return I64Type;
+case TypeTag.I128:
+return I128Type;
+case TypeTag.F32:
+return F32Type;
+case TypeTag.F64:
+return F64Type;
+case TypeTag.Usize:
+return UsizeType;
+case TypeTag.Isize:
+return IsizeType;
+case TypeTag.String:
+return stringTypeInstance;
+case TypeTag.HeapRef:
+return heapRefTypeInstance;
+case TypeTag.BorrowedRef:
+return borrowedRefTypeInstance;
+case TypeTag.Callback:{
+const paramCount=bytes[offset.value++];
+const paramTypes=[];
+for(let i=0;i<paramCount;i++){
+paramTypes.push(parseTypeDef(bytes,offset));
+}
+const returnType=parseTypeDef(bytes,offset);
+return new CallbackType(paramTypes,returnType);
+}
+case TypeTag.Option:{
+const innerType=parseTypeDef(bytes,offset);
+return new OptionType(innerType);
+}
+case TypeTag.Result:{
+const okType=parseTypeDef(bytes,offset);
+const errType=parseTypeDef(bytes,offset);
+return new ResultType(okType,errType);
+}
+case TypeTag.Array:{
+const elementType=parseTypeDef(bytes,offset);
+return new ArrayType(elementType);
+}
+case TypeTag.U8Clamped:
+return u8ClampedTypeInstance;
+case TypeTag.StringEnum:{
+const variantCount=bytes[offset.value++];
+const lookupArray=[];
+for(let i=0;i<variantCount;i++){
+const len=
+bytes[offset.value]|
+(bytes[offset.value+1]<<8)|
+(bytes[offset.value+2]<<16)|
+(bytes[offset.value+3]<<24);
+offset.value+=4;
+const strBytes=bytes.subarray(offset.value,offset.value+len);
+offset.value+=len;
+lookupArray.push(new TextDecoder().decode(strBytes));
+}
+return new StringEnumType(lookupArray);
+}
+case TypeTag.RustObject:{
+const borrowed=bytes[offset.value++]!==0;
+const len=
+bytes[offset.value]|
+(bytes[offset.value+1]<<8)|
+(bytes[offset.value+2]<<16)|
+(bytes[offset.value+3]<<24);
+offset.value+=4;
+const className=new TextDecoder().decode(bytes.subarray(offset.value,offset.value+len));
+offset.value+=len;
+return new RustObjectType(className,borrowed);
+}
+default:
+throw new Error(`Unknown TypeTag: ${tag}`);
+}
+}
+
+const nativeRefRegistry=new FinalizationRegistry((fnId)=>{
+const encoder=new DataEncoder();
+encoder.pushU8(MessageType.Evaluate);
+encoder.pushU32(DROP_NATIVE_REF_FN_ID);
+encoder.pushU32(fnId);
+const response=sync_request_binary(`/__wbg__/handler`,encoder.finalize());
+handleBinaryResponse(response);
+});
+class RustFunction{
+fnId;
+paramTypes;
+returnType;
+constructor(fnId,paramTypes,returnType){
+this.fnId=fnId;
+this.paramTypes=paramTypes;
+this.returnType=returnType;
+nativeRefRegistry.register(this,fnId);
+}
+call(...args){
+window.jsHeap.pushBorrowFrame();
+const encoder=new DataEncoder();
+encoder.pushU8(MessageType.Evaluate);
+encoder.pushU32(0);
+encoder.pushU32(this.fnId);
+for(let i=0;i<this.paramTypes.length;i++){
+this.paramTypes[i].encode(encoder,args[i]);
+}
+let result;
+try{
+const response=sync_request_binary(`/__wbg__/handler`,encoder.finalize());
+result=handleBinaryResponse(response);
+}finally{
+window.jsHeap.popBorrowFrame();
+}
+const decoded=this.returnType.decode(result);
+if(result&&!result.isEmpty()){
+throw new Error("Unprocessed data remaining after RustFunction call");
+}
+if(this.returnType instanceof ResultType){
+if("err"in decoded){
+throw decoded.err;
+}
+return decoded.ok;
+}
+return decoded;
+}
+}
+
+class DataEncoder{
+u8Buf;
+u16Buf;
+u32Buf;
+strBuf;
+constructor(){
+this.u8Buf=[];
+this.u16Buf=[];
+this.u32Buf=[];
+this.strBuf=[];
+}
+pushU8(value){
+this.u8Buf.push(value&0xff);
+}
+pushU16(value){
+this.u16Buf.push(value&0xffff);
+}
+pushU32(value){
+this.u32Buf.push(value>>>0);
+}
+pushU64(value){
+const low=value>>>0;
+const high=Math.floor(value/0x100000000)>>>0;
+this.pushU32(low);
+this.pushU32(high);
+}
+pushU128(value){
+const low=value>>>0;
+const high=Math.floor(value/0x10000000000000000)>>>0;
+this.pushU64(low);
+this.pushU64(high);
+}
+pushF32(value){
+const floatBuf=new Float32Array(1);
+floatBuf[0]=value;
+const intBuf=new Uint32Array(floatBuf.buffer);
+this.pushU32(intBuf[0]);
+}
+pushF64(value){
+const floatBuf=new Float64Array(1);
+floatBuf[0]=value;
+const intBuf=new Uint32Array(floatBuf.buffer);
+this.pushU32(intBuf[0]);
+this.pushU32(intBuf[1]);
+}
+pushStr(value){
+const encoded=new TextEncoder().encode(value);
+this.pushU32(encoded.length);
+for(let i=0;i<encoded.length;i++){
+this.strBuf.push(encoded[i]);
+}
+}
+finalize(){
+const u16Offset=12+this.u32Buf.length*4;
+const u8Offset=u16Offset+this.u16Buf.length*2;
+const strOffset=u8Offset+this.u8Buf.length;
+const totalSize=strOffset+this.strBuf.length;
+const buffer=new ArrayBuffer(totalSize);
+const dataView=new DataView(buffer);
+dataView.setUint32(0,u16Offset,true);
+dataView.setUint32(4,u8Offset,true);
+dataView.setUint32(8,strOffset,true);
+let offset=12;
+for(const val of this.u32Buf){
+dataView.setUint32(offset,val,true);
+offset+=4;
+}
+for(const val of this.u16Buf){
+dataView.setUint16(offset,val,true);
+offset+=2;
+}
+const u8View=new Uint8Array(buffer,u8Offset,this.u8Buf.length);
+u8View.set(this.u8Buf);
+const strView=new Uint8Array(buffer,strOffset,this.strBuf.length);
+strView.set(this.strBuf);
+return buffer;
+}
+}
+class DataDecoder{
+u8Buf;
+u8Offset;
+u16Buf;
+u16Offset;
+u32Buf;
+u32Offset;
+strBuf;
+strOffset;
+constructor(data){
+const headerView=new DataView(data,0,12);
+const u16ByteOffset=headerView.getUint32(0,true);
+const u8ByteOffset=headerView.getUint32(4,true);
+const strByteOffset=headerView.getUint32(8,true);
+const u32ByteLength=u16ByteOffset-12;
+this.u32Buf=new Uint32Array(data,12,u32ByteLength/4);
+this.u32Offset=0;
+const u16ByteLength=u8ByteOffset-u16ByteOffset;
+this.u16Buf=new Uint16Array(data,u16ByteOffset,u16ByteLength/2);
+this.u16Offset=0;
+const u8ByteLength=strByteOffset-u8ByteOffset;
+this.u8Buf=new Uint8Array(data,u8ByteOffset,u8ByteLength);
+this.u8Offset=0;
+const strBuf=new Uint8Array(data,strByteOffset);
+this.strBuf=new TextDecoder("utf-8").decode(strBuf);
+this.strOffset=0;
+}
+takeU8(){
+return this.u8Buf[this.u8Offset++];
+}
+takeU16(){
+return this.u16Buf[this.u16Offset++];
+}
+takeU32(){
+return this.u32Buf[this.u32Offset++];
+}
+peekU32(){
+return this.u32Buf[this.u32Offset];
+}
+hasMoreU32(){
+return this.u32Offset<this.u32Buf.length;
+}
+takeU64(){
+const low=this.takeU32();
+const high=this.takeU32();
+return low+high*0x100000000;
+}
+takeU128(){
+const low=this.takeU64();
+const high=this.takeU64();
+return low+high*0x10000000000000000;
+}
+takeF32(){
+const intVal=this.takeU32();
+const intBuf=new Uint32Array(1);
+intBuf[0]=intVal;
+const floatBuf=new Float32Array(intBuf.buffer);
+return floatBuf[0];
+}
+takeF64(){
+const low=this.takeU32();
+const high=this.takeU32();
+const intBuf=new Uint32Array(2);
+intBuf[0]=low;
+intBuf[1]=high;
+const floatBuf=new Float64Array(intBuf.buffer);
+return floatBuf[0];
+}
+takeStr(){
+const len=this.takeU32();
+const str=this.strBuf.substring(this.strOffset,this.strOffset+len);
+this.strOffset+=len;
+return str;
+}
+takeI8(){
+const unsigned=this.takeU8();
+return unsigned>0x7f?unsigned-0x100:unsigned;
+}
+takeI16(){
+const unsigned=this.takeU16();
+return unsigned>0x7fff?unsigned-0x10000:unsigned;
+}
+takeI32(){
+const unsigned=this.takeU32();
+return unsigned|0;
+}
+takeI64(){
+const low=this.takeU32();
+const high=this.takeU32();
+const signedHigh=high|0;
+return low+signedHigh*0x100000000;
+}
+takeI128(){
+const low=this.takeU64();
+const high=this.takeU64();
+const signedHigh=high|0;
+return low+signedHigh*0x10000000000000000;
+}
+getRemainingBytes(){
+return this.u8Buf.subarray(this.u8Offset);
+}
+skipBytes(count){
+this.u8Offset+=count;
+}
+isEmpty(){
+return(
+this.u8Offset>=this.u8Buf.length&&
+this.u16Offset>=this.u16Buf.length&&
+this.u32Offset>=this.u32Buf.length&&
+this.strOffset>=this.strBuf.length
+);
+}
+}
+
+var MessageType;
+(function (MessageType) {
+  MessageType[MessageType["Evaluate"] = 0] = "Evaluate";
+  MessageType[MessageType["Respond"] = 1] = "Respond";
+  MessageType[MessageType["Throw"] = 2] = "Throw";
+})(MessageType || (MessageType = {}));
+const TYPE_CACHED=0xff;
+const TYPE_FULL=0xfe;
+const DROP_NATIVE_REF_FN_ID=0xffffffff;
+const CALL_EXPORT_FN_ID=0xfffffffe;
+const PROTOCOL_VERSION=2;
+var IpcEncoding;
+(function (IpcEncoding) {
+  IpcEncoding["Binary"] = "binary";
+  IpcEncoding["Header"] = "header";
+})(IpcEncoding || (IpcEncoding = {}));
+let ipcEncoding=IpcEncoding.Binary;
+function setIpcEncoding(encoding){
+ipcEncoding=encoding;
+}
+let bundleHash="";
+function setBundleHash(hash){
+bundleHash=hash;
+}
+async function notifyInitialized(){
+const response=await fetch(
+`/__wbg__/initialized?version=${PROTOCOL_VERSION}&hash=${bundleHash}`,
+{method:"POST",body:[]}
+);
+if(!response.ok){
+console.error(`wry-bindgen refused to start: ${await response.text()}`);
+return;
+}
+const preloaded=await response.text();
+if(preloaded){
+loadFunctions(preloaded);
+}
+}
+function sync_request_binary(
+endpoint,
+data
+){
+if(ipcEncoding===IpcEncoding.Header){
+return sync_request_header(endpoint,data);
+}
+const xhr=new XMLHttpRequest();
+xhr.open("POST",endpoint,false);
+xhr.overrideMimeType("text/plain; charset=x-user-defined");
+xhr.send(new Uint8Array(data));
+if(xhr.status===200&&xhr.responseText){
+const responseText=xhr.responseText;
+const responseBytes=new Uint8Array(responseText.length);
+for(let i=0;i<responseText.length;i++){
+responseBytes[i]=responseText.charCodeAt(i)&0xff;
+}
+return responseBytes.buffer;
+}
+return null;
+}
+function sync_request_header(
+endpoint,
+data
+){
+const xhr=new XMLHttpRequest();
+xhr.open("POST",endpoint,false);
+const bytes=new Uint8Array(data);
+let binary="";
+for(let i=0;i<bytes.length;i++){
+binary+=String.fromCharCode(bytes[i]);
+}
+const base64=btoa(binary);
+xhr.setRequestHeader("dioxus-data",base64);
+xhr.send();
+if(xhr.status===200&&xhr.responseText){
+const responseBinary=atob(xhr.responseText);
+const responseBytes=new Uint8Array(responseBinary.length);
+for(let i=0;i<responseBinary.length;i++){
+responseBytes[i]=responseBinary.charCodeAt(i);
+}
+return responseBytes.buffer;
+}
+return null;
+}
+function evaluate_from_rust_binary(dataBase64){
+const binary=atob(dataBase64);
+const bytes=new Uint8Array(binary.length);
+for(let i=0;i<binary.length;i++){
+bytes[i]=binary.charCodeAt(i);
+}
+const remaining=handleBinaryResponse(bytes.buffer);
+if(remaining){
+throw new Error("Unprocessed data remaining after Evaluate handling");
+}
+}
+function evaluate_from_rust_socket(data){
+const decoder=new DataDecoder(data);
+const msgType=decoder.takeU8();
+if(msgType!==MessageType.Evaluate){
+throw new Error("Expected an Evaluate message from the socket");
+}
+return runEvaluate(decoder);
+}
+function parseTypeInfo(decoder){
+const typeCache=getTypeCache();
+const typeMarker=decoder.takeU8();
+if(typeMarker===TYPE_CACHED){
+const typeId=decoder.takeU32();
+const cached=typeCache.get(typeId);
+if(!cached){
+throw new Error(`Unknown cached type ID: ${typeId}`);
+}
+return cached;
+}else if(typeMarker===TYPE_FULL){
+const typeId=decoder.takeU32();
+const paramCount=decoder.takeU8();
+const typeBytes=decoder.getRemainingBytes();
+const offset={value:0};
+const paramTypes=[];
+for(let i=0;i<paramCount;i++){
+paramTypes.push(parseTypeDef(typeBytes,offset));
+}
+const returnType=parseTypeDef(typeBytes,offset);
+decoder.skipBytes(offset.value);
+const cached={paramTypes,returnType};
+typeCache.set(typeId,cached);
+return cached;
+}else{
+throw new Error(`Unknown type marker: ${typeMarker}`);
+}
+}
+function encodeThrow(fnId,opIndex,error){
+const encoder=new DataEncoder();
+encoder.pushU8(MessageType.Throw);
+encoder.pushU32(fnId);
+encoder.pushU32(opIndex);
+encoder.pushStr(error instanceof Error?error.message:String(error));
+encoder.pushStr(error instanceof Error&&error.stack?error.stack:"");
+return encoder.finalize();
+}
+function runEvaluate(decoder){
+const reservedCount=decoder.takeU32();
+window.jsHeap.pushReservationScope(reservedCount);
+const recycled=[];
+const recycledCount=decoder.takeU32();
+for(let i=0;i<recycledCount;i++){
+recycled.push(decoder.takeU64());
+}
+window.jsHeap.recycle(recycled);
+if(decoder.takeU32()!==0){
+const error=window.jsHeap.checkFreeList(
+decoder.takeU32(),
+decoder.takeU64(),
+decoder.takeU32()
+);
+if(error!==null){
+window.jsHeap.popReservationScope();
+return encodeThrow(0xffffffff,0xffffffff,new Error(error));
+}
+}
+const encoder=new DataEncoder();
+encoder.pushU8(MessageType.Respond);
+window.jsHeap.pushBorrowFrame();
+for(let opIndex=0;decoder.hasMoreU32();opIndex++){
+const fnId=decoder.takeU32();
+try{
+const typeInfo=getFunctionTypes()[fnId]??parseTypeInfo(decoder);
+const jsFunction=getFunction(fnId);
+if(!jsFunction){
+throw new Error("Unknown function ID in response: "+fnId);
+}
+const params=typeInfo.paramTypes.map((paramType)=>paramType.decode(decoder));
+const result=jsFunction(...params);
+if(typeInfo.returnType instanceof HeapRefType&&reservedCount>0){
+window.jsHeap.fillNextReserved(result);
+}else{
+typeInfo.returnType.encode(encoder,result);
+}
+}catch(error){
+window.jsHeap.popBorrowFrame();
+window.jsHeap.popReservationScope();
+return encodeThrow(fnId,opIndex,error);
+}
+}
+window.jsHeap.popBorrowFrame();
+window.jsHeap.popReservationScope();
+return encoder.finalize();
+}
+function handleBinaryResponse(
+response
+){
+if(!response||response.byteLength===0){
+return null;
+}
+const decoder=new DataDecoder(response);
+const rawMsgType=decoder.takeU8();
+const msgType=rawMsgType;
+if(msgType===MessageType.Respond){
+return decoder;
+}else if(msgType===MessageType.Throw){
+throw window.jsHeap.get(decoder.takeU64());
+}else if(msgType===MessageType.Evaluate){
+const nextResponse=sync_request_binary(
+`/__wbg__/handler`,
+runEvaluate(decoder)
+);
+return handleBinaryResponse(nextResponse);
+}
+if(!decoder.isEmpty()){
+throw new Error("Unprocessed data remaining after Evaluate handling");
+}
+return null;
+}
+
+function connectWebSocket(path){
+const protocol=location.protocol==="https:"?"wss:":"ws:";
+const socket=new WebSocket(`${protocol}//${location.host}${path}`);
+socket.binaryType="arraybuffer";
+socket.onmessage=(event)=>{
+socket.send(evaluate_from_rust_socket(event.data));
+};
+return socket;
+}
+
+window.setFunctionRegistry=setFunctionRegistry;
+window.evaluate_from_rust_binary=evaluate_from_rust_binary;
+window.setIpcEncoding=setIpcEncoding;
+window.setBundleHash=setBundleHash;
+window.notifyInitialized=notifyInitialized;
+window.connectWebSocket=connectWebSocket;
+window.jsHeap=new JSHeap();
+window.rustExports=rustExports;
+window.__wbg_exports={};
+
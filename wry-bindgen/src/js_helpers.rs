@@ -135,6 +135,10 @@ extern "C" {
     #[wasm_bindgen(js_name = "drop_heap_ref")]
     pub(crate) fn js_drop_heap_ref(heap_id: u64);
 
+    // Heap management - count the values alive in the JS heap
+    #[wasm_bindgen(js_name = "heap_objects_alive")]
+    pub(crate) fn js_heap_objects_alive() -> u32;
+
     // Create a wrapper object for an exported Rust struct
     #[wasm_bindgen(js_name = "create_rust_object_wrapper")]
     pub(crate) fn create_rust_object_wrapper(handle: u32, class_name: &str) -> JsValue;
@@ -12,6 +12,9 @@ use core::{mem::ManuallyDrop, panic::Location};
 /// ensuring the value is initialized on first access. You can access
 /// the value directly via `Deref`.
 ///
+/// If the webview reloads, the value is initialized again on the new page the
+/// next time it is used.
+///
 /// # Example
 ///
 /// ```ignore
@@ -66,14 +69,17 @@ impl<T> JsThreadLocal<T> {
             });
         }
         // Now that we know it exists, access it
-        let value: ManuallyDrop<T> = with_runtime(|runtime| runtime.take_thread_local(self.key));
+        let (value, generation): (ManuallyDrop<T>, _) =
+            with_runtime(|runtime| (runtime.take_thread_local(self.key), runtime.generation()));
         // We can't hold the runtime borrow while calling f, so we have to
         // move the value out temporarily and put it back afterwards. The f
         // closure could re-enter the runtime to access other thread locals.
         let result = f(&value);
-        // Put it back
+        // Put it back, unless the webview reloaded and it belongs to the old page
         with_runtime(|runtime| {
-            runtime.insert_thread_local(self.key, value);
+            if runtime.generation() == generation {
+                runtime.insert_thread_local(self.key, value);
+            }
         });
         result
     }
@@ -11,6 +11,8 @@
 //! - [`function`] - JSFunction type for calling JavaScript functions
 //! - [`mod@batch`] - Batching system for grouping multiple JS operations
 //! - [`runtime`] - Event loop and runtime management
+//! - `fake` - In-process fake of the JS side for headless tests, behind the `fake` feature
+//! - [`record`] - Recording, replaying and printing the IPC stream
 
 #![no_std]
 
@@ -21,16 +23,23 @@ extern crate std;
 pub mod batch;
 mod cast;
 pub mod convert;
+mod deferred;
 pub mod encode;
+#[cfg(feature = "fake")]
+pub mod fake;
 pub mod function;
 mod function_registry;
+pub mod heap;
 mod intern;
 pub(crate) mod ipc;
 mod js_helpers;
 mod lazy;
 #[doc(hidden)]
 pub mod object_store;
+pub mod record;
 pub mod runtime;
+mod send;
+mod type_def;
 mod value;
 pub mod wry;
 
@@ -57,6 +66,7 @@ pub mod __rt {
     /// It encodes `value` using From's BinaryEncode, sends to JS as identity,
     /// and decodes the result using To's BinaryDecode.
     #[inline]
+    #[track_caller]
     pub fn wbg_cast<From, To>(value: From) -> To
     where
         From: BinaryEncode + EncodeTypeDef,
@@ -66,9 +76,84 @@ pub mod __rt {
         func.call(value)
     }
 
+    /// The return types allowed for a `#[wasm_bindgen(start)]` function.
+    pub trait Start {
+        fn start(self);
+    }
+
+    impl Start for () {
+        #[inline]
+        fn start(self) {}
+    }
+
+    impl<E: core::fmt::Debug> Start for Result<(), E> {
+        #[inline]
+        #[track_caller]
+        fn start(self) {
+            if let Err(err) = self {
+                panic!("start function failed: {err:?}");
+            }
+        }
+    }
+
+    /// Start the future of an async exported method on this thread's runtime and
+    /// return the `Promise` JS gets for it.
+    ///
+    /// The promise resolves with the `Ok` value and rejects with the `Err` value,
+    /// or with a `PanicError` if the future panics.
+    pub fn future_to_promise<T, E>(
+        future: impl core::future::Future<Output = Result<T, E>> + 'static,
+    ) -> JsValue
+    where
+        T: BinaryEncode + EncodeTypeDef + 'static,
+        E: BinaryEncode + EncodeTypeDef + 'static,
+    {
+        use core::cell::Cell;
+        use futures_util::FutureExt;
+
+        type Executor = crate::Closure<dyn FnMut(JsValue, JsValue)>;
+        static NEW_PROMISE: LazyJsFunction<fn(&Executor) -> JsValue> =
+            __wry_submit_js_function!("(executor) => new Promise(executor)");
+
+        // The executor runs while the promise is created, so resolve and reject
+        // are set when the call returns
+        let settle = alloc::rc::Rc::new(Cell::new(None));
+        let executor = crate::Closure::new({
+            let settle = settle.clone();
+            move |resolve: JsValue, reject: JsValue| settle.set(Some((resolve, reject)))
+        });
+        let promise = NEW_PROMISE.call(&executor);
+        let (resolve, reject) = settle
+            .take()
+            .expect("the Promise executor runs synchronously");
+
+        let generation = crate::batch::with_runtime(|runtime| runtime.generation());
+        crate::runtime::spawn_local(async move {
+            let result = core::panic::AssertUnwindSafe(future).catch_unwind().await;
+            // The promise belongs to a page that is gone if the webview reloaded
+            if crate::batch::with_runtime(|runtime| runtime.generation()) != generation {
+                return;
+            }
+            match result {
+                Ok(Ok(value)) => settle_promise(&resolve, value),
+                Ok(Err(error)) => settle_promise(&reject, error),
+                Err(panic) => settle_promise(&reject, panic_to_panic_error(panic)),
+            }
+        });
+        promise
+    }
+
+    /// Call the `resolve` or `reject` function of a promise with `value`.
+    fn settle_promise<T: BinaryEncode + EncodeTypeDef>(settle: &JsValue, value: T) {
+        let func: LazyJsFunction<fn(&JsValue, T)> =
+            __wry_submit_js_function!("(settle, value) => settle(value)");
+        func.call(settle, value)
+    }
+
     /// Convert a panic value into a JsValue error.
     ///
-    /// This is used by wasm-bindgen-futures to convert Rust panics into JS errors.
+    /// This is used by wasm-bindgen-futures and by calls from JS into Rust
+    /// to convert Rust panics into JS errors.
     #[cfg(feature = "std")]
     pub fn panic_to_panic_error(val: std::boxed::Box<dyn std::any::Any + Send>) -> JsValue {
         let maybe_panic_msg: Option<&str> = if let Some(s) = val.downcast_ref::<&str>() {
@@ -79,7 +164,7 @@ pub mod __rt {
             None
         };
         // Create an Error object with the panic message
-        JsValue::from_str(maybe_panic_msg.unwrap_or("Rust panic"))
+        crate::JsError::new(maybe_panic_msg.unwrap_or("Rust panic")).into()
     }
 }
 
@@ -90,6 +175,7 @@ macro_rules! cast {
 macro_rules! to_js_value {
     ($ty:ty) => {
         impl From<$ty> for $crate::JsValue {
+            #[track_caller]
             fn from(val: $ty) -> Self {
                 cast! {($ty => $crate::JsValue) val}
             }
@@ -236,11 +322,13 @@ from_js_value!(usize);
 to_js_value!(isize);
 from_js_value!(isize);
 impl From<&str> for JsValue {
+    #[track_caller]
     fn from(val: &str) -> Self {
         cast! {(String => JsValue) val.to_string()}
     }
 }
 impl From<&String> for JsValue {
+    #[track_caller]
     fn from(val: &String) -> Self {
         cast! {(String => JsValue) val.clone()}
     }
@@ -369,7 +457,9 @@ use alloc::string::{String, ToString};
 use core::ops::{Deref, DerefMut};
 // Re-export core types
 pub use cast::JsCast;
+pub use deferred::Deferred;
 pub use lazy::JsThreadLocal;
+pub use send::SendJsValue;
 pub use value::JsValue;
 
 /// A wrapper type around slices and vectors for binding the `Uint8ClampedArray` in JS.
@@ -465,7 +555,7 @@ impl JsCast for JsError {
 
 // Re-export commonly used items
 pub use batch::batch;
-pub use encode::{BatchableResult, BinaryDecode, BinaryEncode, EncodeTypeDef};
+pub use encode::{BatchableResult, BinaryDecode, BinaryEncode, BorrowedForCall, EncodeTypeDef};
 pub use function::JSFunction;
 pub use ipc::{DecodeError, DecodedData, EncodedData};
 
@@ -483,7 +573,7 @@ use crate::object_store::insert_object;
 // Re-export function registry types
 pub use function_registry::{
     InlineJsModule, JsClassMemberKind, JsClassMemberSpec, JsExportSpec, JsFunctionSpec,
-    LazyJsFunction,
+    JsStartSpec, LazyJsFunction, run_start_functions,
 };
 
 /// Macro to register and call a JavaScript function.
@@ -502,7 +592,8 @@ pub use function_registry::{
 #[doc(hidden)]
 macro_rules! __wry_call_js_function {
     ($js_code:expr, $fn_type:ty, ($($args:expr),*)) => {{
-        static __FUNC: $crate::LazyJsFunction<$fn_type> = $crate::__wry_submit_js_function!($js_code);
+        static __FUNC: $crate::LazyJsFunction<$fn_type> =
+            $crate::__wry_submit_js_function!($js_code, $fn_type);
 
         __FUNC.call($($args),*)
     }};
@@ -515,16 +606,33 @@ macro_rules! __wry_call_js_function {
 /// 2. Submitting it to inventory
 /// 3. Creating a LazyJsFunction with the given signature
 ///
+/// If the function type is passed, its signature is registered with the spec
+/// and calls don't send type information. Generic code that can't name the
+/// type in a static leaves it out.
+///
 /// # Usage
 /// ```ignore
-/// __wry_submit_js_function!("(a, b) => a + b")
+/// __wry_submit_js_function!("(a, b) => a + b", fn(i32, i32) -> i32)
 /// ```
 #[macro_export]
 #[doc(hidden)]
 macro_rules! __wry_submit_js_function {
     ($js_code:expr) => {{
         static __SPEC: $crate::JsFunctionSpec =
-            $crate::JsFunctionSpec::new(|| $crate::alloc::format!($js_code));
+            $crate::JsFunctionSpec::new(|| $crate::alloc::format!($js_code))
+                .with_module_path(module_path!());
+
+        $crate::inventory::submit! {
+            __SPEC
+        }
+
+        __SPEC.resolve_as()
+    }};
+    ($js_code:expr, $fn_type:ty) => {{
+        static __SPEC: $crate::JsFunctionSpec =
+            $crate::JsFunctionSpec::new(|| $crate::alloc::format!($js_code))
+                .with_module_path(module_path!())
+                .with_signature(|buf| $crate::JSFunction::<$fn_type>::new(0).encode_signature(buf));
 
         $crate::inventory::submit! {
             __SPEC
@@ -567,28 +675,46 @@ where
     }
 }
 
+/// Throw a JS value as an exception.
+///
+/// Inside a callback or export called from JS, this unwinds back to the JS
+/// caller and throws `s` there.
+///
+/// # Panics
+/// This function panics when Rust is not running a call from JS.
 #[cold]
 #[inline(never)]
 pub fn throw_val(s: JsValue) -> ! {
+    if batch::in_js_call() {
+        // resume_unwind skips the panic hook, this is a JS exception rather than a bug
+        std::panic::resume_unwind(Box::new(runtime::JsThrow(s)));
+    }
     panic!("{s:?}");
 }
 
-/// Throw a JS exception with the given message.
+/// Throw a JS `Error` with the given message.
 ///
 /// # Panics
-/// This function always panics when running outside of WASM.
+/// This function panics when Rust is not running a call from JS.
 #[cold]
 #[inline(never)]
 pub fn throw_str(s: &str) -> ! {
-    panic!("cannot throw JS exception when running outside of wasm: {s}");
+    if batch::in_js_call() {
+        throw_val(JsError::new(s).into());
+    }
+    panic!("cannot throw JS exception outside of a call from JS: {s}");
 }
 
-/// Returns the number of live externref objects.
+/// Returns the number of live objects in the JS heap of the current webview.
+///
+/// Values dropped in Rust are counted until JS frees them with the next batch.
+/// See [`heap::live_js_values`] for the count in Rust.
 ///
 /// # Panics
-/// This function always panics when running outside of WASM.
+/// This function panics when called outside of a runtime.
+#[track_caller]
 pub fn externref_heap_live_count() -> u32 {
-    panic!("cannot introspect wasm memory when running outside of wasm")
+    js_helpers::js_heap_objects_alive()
 }
 
 /// Returns a handle to this Wasm instance's `WebAssembly.Module`.
@@ -5,7 +5,12 @@
 //! can be retrieved, borrowed, and dropped. It also stores callback functions
 //! that can be called from JavaScript.
 
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::{Ref, RefCell, RefMut};
+
 use crate::batch::with_runtime;
+use crate::encode::TypeTag;
 use crate::{BatchableResult, BinaryDecode, BinaryEncode, EncodeTypeDef};
 
 /// Handle to an exported object in the store.
@@ -26,7 +31,7 @@ impl BinaryEncode for ObjectHandle {
 }
 
 impl EncodeTypeDef for ObjectHandle {
-    fn encode_type_def(buf: &mut std::vec::Vec<u8>) {
+    fn encode_type_def(buf: &mut Vec<u8>) {
         u32::encode_type_def(buf);
     }
 }
@@ -55,6 +60,39 @@ pub fn remove_object<T: 'static>(handle: ObjectHandle) -> T {
     with_runtime(|state| state.remove_object(handle.0))
 }
 
+/// Share an exported object with an async method, which keeps it alive until
+/// the method's future finishes.
+pub fn share_object<T: 'static>(handle: ObjectHandle) -> Rc<RefCell<T>> {
+    with_runtime(|state| state.share_object(handle.0))
+}
+
+/// Borrow an exported object for a `&self` method.
+///
+/// Methods borrow the object until they return, and async methods until their
+/// future finishes. This panics with a message JS gets as the error if a
+/// `&mut self` method is running.
+pub fn borrow_object<T>(obj: &RefCell<T>) -> Ref<'_, T> {
+    obj.try_borrow().unwrap_or_else(|_| {
+        panic!(
+            "cannot use `{}` while a `&mut self` method on it is running",
+            core::any::type_name::<T>()
+        )
+    })
+}
+
+/// Borrow an exported object for a `&mut self` method.
+///
+/// This panics with a message JS gets as the error if another method on the
+/// object is running, see [`borrow_object`].
+pub fn borrow_object_mut<T>(obj: &RefCell<T>) -> RefMut<'_, T> {
+    obj.try_borrow_mut().unwrap_or_else(|_| {
+        panic!(
+            "cannot mutate `{}` while another method on it is running",
+            core::any::type_name::<T>()
+        )
+    })
+}
+
 pub fn drop_object(handle: ObjectHandle) -> bool {
     with_runtime(|state| state.remove_object_untyped(handle.0)).is_some()
 }
@@ -66,3 +104,92 @@ pub fn create_js_wrapper<T: 'static>(handle: ObjectHandle, class_name: &str) ->
     // The JS side will create an object with the appropriate methods
     crate::js_helpers::create_rust_object_wrapper(handle.0, class_name)
 }
+
+/// Encode the type definition of an exported struct, wrapped in JS as `class_name`.
+///
+/// `borrowed` tells JS that Rust only borrows an object JS passes in, so the
+/// JS wrapper keeps owning it.
+pub fn encode_object_type_def(buf: &mut Vec<u8>, class_name: &str, borrowed: bool) {
+    buf.push(TypeTag::RustObject as u8);
+    buf.push(borrowed as u8);
+    buf.extend_from_slice(&(class_name.len() as u32).to_le_bytes());
+    buf.extend_from_slice(class_name.as_bytes());
+}
+
+/// Move an exported struct into the store and pass it to JS, which wraps it in
+/// an object that owns it.
+pub fn encode_owned_object<T: 'static>(obj: T, encoder: &mut crate::EncodedData) {
+    insert_object(obj).encode(encoder);
+    encoder.push_u8(1);
+}
+
+/// Lend an exported struct to the JS call it is encoded into. The JS wrapper
+/// can only be used until the call returns.
+///
+/// The arguments of a call are borrowed until the call returns or unwinds, and
+/// the call releases the handle before then.
+pub fn encode_lent_object<T: 'static>(obj: &T, encoder: &mut crate::EncodedData) {
+    // The object is only borrowed until the call returns, so the call can't be batched
+    encoder.mark_needs_flush();
+    let handle = with_runtime(|state| state.lend_object(obj as *const T));
+    ObjectHandle(handle).encode(encoder);
+    encoder.push_u8(0);
+}
+
+/// Decode an exported struct JS passed by value, taking it out of the store.
+pub fn decode_owned_object<T: 'static>(
+    decoder: &mut crate::DecodedData,
+) -> Result<T, crate::DecodeError> {
+    let handle = ObjectHandle::decode(decoder)?;
+    Ok(remove_object(handle))
+}
+
+/// Decode an exported struct JS passed by reference. The object is taken out
+/// of the store while Rust borrows it and put back when the anchor drops.
+pub fn decode_object_ref<T: 'static>(
+    decoder: &mut crate::DecodedData,
+) -> Result<ObjectAnchor<T>, crate::DecodeError> {
+    let handle = ObjectHandle::decode(decoder)?;
+    let object = match with_runtime(|state| state.take_object_for_borrow::<T>(handle.0)) {
+        Ok(obj) => AnchoredObject::Taken(Some(obj)),
+        Err(lent) => AnchoredObject::Lent(lent),
+    };
+    Ok(ObjectAnchor { handle, object })
+}
+
+/// Keeps an exported struct JS passed by reference borrowed, see [`decode_object_ref`].
+pub struct ObjectAnchor<T: 'static> {
+    handle: ObjectHandle,
+    object: AnchoredObject<T>,
+}
+
+enum AnchoredObject<T> {
+    /// The object was taken out of the store until the anchor drops
+    Taken(Option<T>),
+    /// The object is lent to JS by a Rust call that is still running
+    Lent(*const T),
+}
+
+impl<T: 'static> core::ops::Deref for ObjectAnchor<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        match &self.object {
+            AnchoredObject::Taken(obj) => obj.as_ref().expect("object already restored"),
+            // SAFETY: Only sync exports borrow objects JS passes in, since async exports
+            // can't take references. They run while JS handles the synchronous call the
+            // object was lent to, so that call's borrow of the object outlives the anchor
+            AnchoredObject::Lent(obj) => unsafe { &**obj },
+        }
+    }
+}
+
+impl<T: 'static> Drop for ObjectAnchor<T> {
+    fn drop(&mut self) {
+        if let AnchoredObject::Taken(obj) = &mut self.object
+            && let Some(obj) = obj.take()
+        {
+            with_runtime(|state| state.restore_object(self.handle.0, obj));
+        }
+    }
+}
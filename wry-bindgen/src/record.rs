@@ -0,0 +1,618 @@
+//! Record and replay the IPC stream between Rust and JS.
+//!
+//! A [`Recorder`] passed to [`WryBindgen::with_recorder`](crate::wry::WryBindgen::with_recorder)
+//! writes every message that crosses the IPC boundary to a file. The file can be
+//! read back as a [`Recording`], printed with the JS source of every function it
+//! calls, or fed back into the app with a [`Replay`] so the Rust side re-executes
+//! the session without a webview.
+//!
+//! ```ignore
+//! // While reproducing the bug
+//! let wry_bindgen = WryBindgen::new(proxy).with_recorder(Recorder::create("session.wbgrec")?);
+//!
+//! // Later, in a test or a debugger
+//! let recording = Recording::open("session.wbgrec")?;
+//! println!("{recording}");
+//! pollster::block_on(Replay::new(recording).run(app));
+//! ```
+//!
+//! # File format
+//!
+//! All integers are little endian. The file starts with the 8 byte magic
+//! `WBGIPCRC` and a u32 format version, followed by one entry per message:
+//!
+//! - u8: direction (0 = Rust to JS, 1 = JS to Rust)
+//! - u64: webview id
+//! - u64: microseconds since the recording started
+//! - u32: message length
+//! - the raw bytes of the `IPCMessage`
+
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
+use core::fmt::{self, Write as _};
+use core::future::poll_fn;
+use core::pin::pin;
+use core::task::Poll;
+use core::time::Duration;
+use futures_util::FutureExt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Instant;
+
+use crate::batch::{Runtime, in_runtime};
+use crate::function::{CALL_EXPORT_FN_ID, DROP_NATIVE_REF_FN_ID};
+use crate::function_registry::FUNCTION_REGISTRY;
+use crate::ipc::{DecodeError, DecodedData, DecodedVariant, EvaluateHeader, IPCMessage};
+use crate::runtime::{AppEventVariant, IPCSenders, WryBindgenEvent, WryIPC, handle_callbacks};
+use crate::type_def::{FunctionType, TypeCache, TypeDef, decode_number};
+
+/// The magic bytes every recording starts with.
+const MAGIC: &[u8; 8] = b"WBGIPCRC";
+
+/// The current version of the recording format.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Which side of the IPC boundary sent a message.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// A message Rust sent to JS
+    RustToJs = 0,
+    /// A message JS sent to Rust
+    JsToRust = 1,
+}
+
+impl Direction {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Direction::RustToJs),
+            1 => Some(Direction::JsToRust),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Direction::RustToJs => f.write_str("Rust -> JS"),
+            Direction::JsToRust => f.write_str("JS -> Rust"),
+        }
+    }
+}
+
+/// One message in a [`Recording`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedMessage {
+    /// Which side sent the message
+    pub direction: Direction,
+    /// The webview the message was sent to or from
+    pub webview_id: u64,
+    /// Time since the recording started
+    pub timestamp: Duration,
+    /// The raw bytes of the message
+    pub data: Vec<u8>,
+}
+
+/// Writes every IPC message to a recording.
+///
+/// Recording is opt-in: pass a recorder to
+/// [`WryBindgen::with_recorder`](crate::wry::WryBindgen::with_recorder). Each
+/// message is flushed as it is written, so the recording survives a crash.
+pub struct Recorder {
+    writer: RefCell<Box<dyn Write>>,
+    start: Instant,
+    failed: Cell<bool>,
+}
+
+impl Recorder {
+    /// Start a recording in the file at `path`, replacing it if it exists.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+
+    /// Start a recording in any writer.
+    pub fn new(mut writer: impl Write + 'static) -> io::Result<Self> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        writer.flush()?;
+        Ok(Self {
+            writer: RefCell::new(Box::new(writer)),
+            start: Instant::now(),
+            failed: Cell::new(false),
+        })
+    }
+
+    /// Write one message to the recording.
+    ///
+    /// A failed write stops the recording instead of interrupting the app.
+    pub(crate) fn record(&self, direction: Direction, webview_id: u64, data: &[u8]) {
+        if self.failed.get() {
+            return;
+        }
+        let timestamp = self.start.elapsed().as_micros() as u64;
+        let mut writer = self.writer.borrow_mut();
+        let result = (|| {
+            writer.write_all(&[direction as u8])?;
+            writer.write_all(&webview_id.to_le_bytes())?;
+            writer.write_all(&timestamp.to_le_bytes())?;
+            writer.write_all(&(data.len() as u32).to_le_bytes())?;
+            writer.write_all(data)?;
+            writer.flush()
+        })();
+        if let Err(err) = result {
+            eprintln!("Failed to write the IPC recording, stopping the recording: {err}");
+            self.failed.set(true);
+        }
+    }
+}
+
+/// A recording read back from a file written by a [`Recorder`].
+#[derive(Debug, Clone, Default)]
+pub struct Recording {
+    messages: Vec<RecordedMessage>,
+}
+
+impl Recording {
+    /// Read the recording in the file at `path`.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+
+    /// Read a recording from any reader.
+    ///
+    /// A message cut off at the end of the recording (for example because the
+    /// app crashed while writing it) is ignored.
+    pub fn read(mut reader: impl Read) -> io::Result<Self> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Not a wry-bindgen IPC recording",
+            ));
+        }
+        let version = u32::from_le_bytes(read_array(&mut reader)?);
+        if version != FORMAT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Unsupported recording version {version}, expected version {FORMAT_VERSION}"
+                ),
+            ));
+        }
+
+        let mut messages = Vec::new();
+        loop {
+            match read_message(&mut reader) {
+                Ok(Some(message)) => messages.push(message),
+                Ok(None) => break,
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(Self { messages })
+    }
+
+    /// Every message in the order it was recorded.
+    pub fn messages(&self) -> &[RecordedMessage] {
+        &self.messages
+    }
+
+    /// The ids of every webview in the recording, in the order they first appear.
+    pub fn webview_ids(&self) -> Vec<u64> {
+        let mut ids = Vec::new();
+        for message in &self.messages {
+            if !ids.contains(&message.webview_id) {
+                ids.push(message.webview_id);
+            }
+        }
+        ids
+    }
+}
+
+/// Pretty-prints the recording, one message per entry.
+///
+/// Function ids are resolved with the function registry of the running binary,
+/// so the recording must come from the same build for the names to be right.
+impl fmt::Display for Recording {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut describers: BTreeMap<u64, Describer> = BTreeMap::new();
+        for (index, message) in self.messages.iter().enumerate() {
+            writeln!(
+                f,
+                "#{index} {:.6}s webview {} {}",
+                message.timestamp.as_secs_f64(),
+                message.webview_id,
+                message.direction
+            )?;
+            let describer = describers.entry(message.webview_id).or_default();
+            let mut out = String::new();
+            if let Err(err) = describer.describe(message.direction, &message.data, &mut out) {
+                let _ = writeln!(out, "    <failed to decode: {err}>");
+            }
+            f.write_str(&out)?;
+        }
+        Ok(())
+    }
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Read the next message, or `None` at the end of the recording.
+fn read_message(reader: &mut impl Read) -> io::Result<Option<RecordedMessage>> {
+    let mut direction = [0];
+    if reader.read(&mut direction)? == 0 {
+        return Ok(None);
+    }
+    let direction = Direction::from_u8(direction[0]).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid message direction {}", direction[0]),
+        )
+    })?;
+    let webview_id = u64::from_le_bytes(read_array(reader)?);
+    let timestamp = Duration::from_micros(u64::from_le_bytes(read_array(reader)?));
+    let len = u32::from_le_bytes(read_array(reader)?);
+    let mut data = vec![0; len as usize];
+    reader.read_exact(&mut data)?;
+    Ok(Some(RecordedMessage {
+        direction,
+        webview_id,
+        timestamp,
+        data,
+    }))
+}
+
+/// Replays the JS side of a [`Recording`] against the Rust app.
+///
+/// Every time the app sends a message, the replay checks it matches the next
+/// message Rust sent in the recording, then delivers the messages JS sent in
+/// response. Messages JS sent on its own, like event callbacks, are delivered
+/// at the same point in the conversation they were recorded at, so the app
+/// re-executes the session deterministically.
+pub struct Replay {
+    recording: Recording,
+    webview_id: Option<u64>,
+    auto_batch: bool,
+}
+
+impl Replay {
+    /// Replay the first webview in the recording.
+    pub fn new(recording: Recording) -> Self {
+        Self {
+            recording,
+            webview_id: None,
+            auto_batch: false,
+        }
+    }
+
+    /// Replay the webview with this id instead of the first one.
+    pub fn webview(mut self, webview_id: u64) -> Self {
+        self.webview_id = Some(webview_id);
+        self
+    }
+
+    /// Batch each poll of the app, for recordings made with
+    /// [`WryBindgen::with_auto_batch`](crate::wry::WryBindgen::with_auto_batch).
+    pub fn auto_batch(mut self, auto_batch: bool) -> Self {
+        self.auto_batch = auto_batch;
+        self
+    }
+
+    /// Run the app against the recording.
+    ///
+    /// The future resolves when the app finishes, or when every recorded message
+    /// has been delivered and the app is waiting for more. It panics if the app
+    /// sends a message that is different from the recording.
+    pub fn run<F>(self, app: impl FnOnce() -> F) -> impl Future<Output = ()>
+    where
+        F: Future<Output = ()>,
+    {
+        let webview_id = self
+            .webview_id
+            .or_else(|| self.recording.webview_ids().first().copied())
+            .unwrap_or_default();
+        let messages = self
+            .recording
+            .messages
+            .into_iter()
+            .enumerate()
+            .filter(|(_, message)| message.webview_id == webview_id)
+            .collect();
+
+        let state = Arc::new(Mutex::new(ReplayState {
+            messages,
+            senders: None,
+        }));
+        let (ipc, senders) = WryIPC::new({
+            let state = state.clone();
+            Arc::new(move |event| replay_event(&state, event))
+        });
+        {
+            let mut state = lock(&state);
+            state.senders = Some(senders);
+            // Deliver anything JS sent before Rust's first message
+            state.deliver_js_messages();
+        }
+
+        let auto_batch = self.auto_batch;
+        async move {
+            let run_app_in_runtime = async move {
+                futures_util::select! {
+                    _ = crate::batch::auto_batch(app(), auto_batch).fuse() => {},
+                    _ = handle_callbacks().fuse() => {},
+                }
+            };
+            let mut run_app_in_runtime = pin!(run_app_in_runtime);
+            let mut maybe_runtime = Some(Runtime::new(ipc, webview_id));
+            poll_fn(move |ctx| {
+                let (runtime, poll_result) = in_runtime(maybe_runtime.take().unwrap(), || {
+                    run_app_in_runtime.as_mut().poll(ctx)
+                });
+                let finished = poll_result.is_pending()
+                    && lock(&state).messages.is_empty()
+                    && !runtime.ipc().has_queued_evaluates();
+                maybe_runtime = Some(runtime);
+                if finished {
+                    Poll::Ready(())
+                } else {
+                    poll_result
+                }
+            })
+            .await
+        }
+    }
+}
+
+/// The messages a [`Replay`] has not delivered yet.
+struct ReplayState {
+    /// The remaining messages for the replayed webview, with their index in the recording
+    messages: VecDeque<(usize, RecordedMessage)>,
+    senders: Option<IPCSenders>,
+}
+
+impl ReplayState {
+    /// Deliver every JS message up to the next message Rust sent.
+    fn deliver_js_messages(&mut self) {
+        let senders = self.senders.as_ref().expect("The replay is not running");
+        while let Some((_, message)) = self.messages.front() {
+            if message.direction != Direction::JsToRust {
+                break;
+            }
+            let (_, message) = self.messages.pop_front().unwrap();
+            senders.start_send(IPCMessage::new(message.data));
+        }
+    }
+}
+
+/// Handle a message the app sent during a replay.
+fn replay_event(state: &Mutex<ReplayState>, event: WryBindgenEvent) {
+    let AppEventVariant::Ipc(message) = event.into_variant() else {
+        return;
+    };
+    let mut state = lock(state);
+    match state.messages.pop_front() {
+        Some((_, expected)) if expected.data == message.data() => {}
+        Some((index, _)) => panic!(
+            "The replay diverged from the recording at message #{index}: Rust sent a different message than it did while recording"
+        ),
+        None => panic!("Rust sent a message after the end of the recording"),
+    }
+    state.deliver_js_messages();
+}
+
+/// Lock a mutex, ignoring poisoning from a panicking app.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// What the next Respond message in a conversation answers.
+enum Pending {
+    /// The operations in an Evaluate Rust sent
+    Operations(Vec<Arc<FunctionType>>),
+    /// A callback JS called
+    Callback(Arc<FunctionType>),
+    /// A message whose response can't be decoded
+    Unknown,
+}
+
+/// Decodes the messages of one webview, tracking the state JS and Rust share.
+#[derive(Default)]
+struct Describer {
+    types: TypeCache,
+    /// The type of every callback Rust passed to JS by key
+    callbacks: BTreeMap<u32, Arc<FunctionType>>,
+    pending: Vec<Pending>,
+}
+
+impl Describer {
+    fn describe(
+        &mut self,
+        direction: Direction,
+        bytes: &[u8],
+        out: &mut String,
+    ) -> Result<(), DecodeError> {
+        let message = IPCMessage::new(bytes.to_vec());
+        match (message.decoded()?, direction) {
+            (DecodedVariant::Evaluate { mut data }, Direction::RustToJs) => {
+                let header = EvaluateHeader::decode(&mut data)?;
+                let _ = writeln!(
+                    out,
+                    "    Evaluate, {} reserved heap ids, recycled {:?}",
+                    header.reserved, header.recycled
+                );
+                let mut operations = Vec::new();
+                while data.has_more_u32() {
+                    let fn_id = data.take_u32()?;
+                    let ty = self.types.parse_type_info(fn_id, &mut data)?;
+                    let args = self.describe_args(&ty, &mut data, direction)?;
+                    let _ = writeln!(out, "    [{fn_id}] `{}`({args})", js_code(fn_id));
+                    operations.push(ty);
+                }
+                self.pending.push(Pending::Operations(operations));
+            }
+            (DecodedVariant::Evaluate { mut data }, Direction::JsToRust) => {
+                match data.take_u32()? {
+                    0 => {
+                        let key = data.take_u32()?;
+                        match self.callbacks.get(&key).cloned() {
+                            Some(ty) => {
+                                let args = self.describe_args(&ty, &mut data, direction)?;
+                                let _ = writeln!(out, "    Call callback {key}({args})");
+                                self.pending.push(Pending::Callback(ty));
+                            }
+                            None => {
+                                let _ = writeln!(out, "    Call callback {key}(..)");
+                                self.pending.push(Pending::Unknown);
+                            }
+                        }
+                    }
+                    DROP_NATIVE_REF_FN_ID => {
+                        let _ = writeln!(out, "    Drop native object {}", data.take_u32()?);
+                        self.pending.push(Pending::Unknown);
+                    }
+                    CALL_EXPORT_FN_ID => {
+                        let _ = writeln!(out, "    Call export `{}`(..)", data.take_str()?);
+                        self.pending.push(Pending::Unknown);
+                    }
+                    fn_id => {
+                        let _ = writeln!(out, "    Unknown function {fn_id}");
+                        self.pending.push(Pending::Unknown);
+                    }
+                }
+            }
+            (DecodedVariant::Respond { mut data }, _) => {
+                let _ = writeln!(out, "    Respond");
+                match self.pending.pop() {
+                    Some(Pending::Operations(operations)) => {
+                        for ty in operations {
+                            let value = self.describe_value(&ty.ret, &mut data, direction)?;
+                            let _ = writeln!(out, "    -> {value}");
+                        }
+                    }
+                    Some(Pending::Callback(ty)) => {
+                        let value = self.describe_value(&ty.ret, &mut data, direction)?;
+                        let _ = writeln!(out, "    -> {value}");
+                    }
+                    Some(Pending::Unknown) | None => {}
+                }
+            }
+            (DecodedVariant::Throw { mut data }, Direction::RustToJs) => {
+                let _ = writeln!(out, "    Throw heap value {}", data.take_u64()?);
+                self.pending.pop();
+            }
+            (DecodedVariant::Throw { mut data }, Direction::JsToRust) => {
+                let fn_id = data.take_u32()?;
+                let op_index = data.take_u32()?;
+                let message = data.take_str()?;
+                let _ = writeln!(
+                    out,
+                    "    Throw from operation {op_index} [{fn_id}]: {message}"
+                );
+                self.pending.pop();
+            }
+        }
+        Ok(())
+    }
+
+    fn describe_args(
+        &mut self,
+        ty: &FunctionType,
+        data: &mut DecodedData,
+        direction: Direction,
+    ) -> Result<String, DecodeError> {
+        let args = ty
+            .params
+            .iter()
+            .map(|param| self.describe_value(param, data, direction))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(args.join(", "))
+    }
+
+    /// Describe one value encoded by the side `direction` starts from.
+    fn describe_value(
+        &mut self,
+        ty: &TypeDef,
+        data: &mut DecodedData,
+        direction: Direction,
+    ) -> Result<String, DecodeError> {
+        Ok(match ty {
+            TypeDef::Null => "undefined".to_string(),
+            TypeDef::Bool => (data.take_u8()? != 0).to_string(),
+            TypeDef::Number(tag) => decode_number(*tag, data)?.to_string(),
+            TypeDef::String => format!("{:?}", data.take_str()?),
+            // JS never sends heap ids, Rust allocates the same id on its side
+            TypeDef::HeapRef | TypeDef::BorrowedRef if direction == Direction::JsToRust => {
+                "<heap value>".to_string()
+            }
+            TypeDef::HeapRef | TypeDef::BorrowedRef => format!("heap[{}]", data.take_u64()?),
+            TypeDef::Callback(callback) => {
+                let key = data.take_u32()?;
+                if direction == Direction::RustToJs {
+                    self.callbacks.insert(key, callback.clone());
+                }
+                format!("callback {key}")
+            }
+            TypeDef::Option(inner) => match data.take_u8()? {
+                0 => "None".to_string(),
+                _ => format!("Some({})", self.describe_value(inner, data, direction)?),
+            },
+            TypeDef::Result(ok, err) => match data.take_u8()? {
+                1 => format!("Ok({})", self.describe_value(ok, data, direction)?),
+                _ => format!("Err({})", self.describe_value(err, data, direction)?),
+            },
+            TypeDef::Array(element) => {
+                let len = data.take_u32()?;
+                let items = (0..len)
+                    .map(|_| self.describe_value(element, data, direction))
+                    .collect::<Result<Vec<_>, _>>()?;
+                format!("[{}]", items.join(", "))
+            }
+            TypeDef::U8Clamped => {
+                let len = data.take_u32()?;
+                let bytes = (0..len)
+                    .map(|_| data.take_u8())
+                    .collect::<Result<Vec<_>, _>>()?;
+                format!("{bytes:?}")
+            }
+            TypeDef::StringEnum(variants) => {
+                let index = data.take_u32()?;
+                variants.get(index as usize).map_or_else(
+                    || format!("<invalid variant {index}>"),
+                    |v| format!("{v:?}"),
+                )
+            }
+            TypeDef::RustObject { borrowed, class } => {
+                let handle = data.take_u32()?;
+                // Rust marks the objects it moves to JS and the ones it only lends
+                let borrowed = match direction {
+                    Direction::RustToJs => data.take_u8()? == 0,
+                    Direction::JsToRust => *borrowed,
+                };
+                let prefix = if borrowed { "&" } else { "" };
+                format!("{prefix}{class}({handle})")
+            }
+        })
+    }
+}
+
+fn js_code(fn_id: u32) -> String {
+    FUNCTION_REGISTRY
+        .function_specs()
+        .get(fn_id as usize)
+        .map_or_else(
+            || "<unknown function>".to_string(),
+            |spec| (spec.js_code())(),
+        )
+}
@@ -3,21 +3,31 @@
 //! This module handles the connection between the Rust runtime and the
 //! JavaScript environment via winit's event loop.
 
-use core::pin::Pin;
+use core::panic::AssertUnwindSafe;
+use core::pin::{Pin, pin};
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::task::{Context, Poll, Waker, ready};
+use core::time::Duration;
 use std::sync::Arc;
+use std::task::Wake;
+use std::time::Instant;
 
 use alloc::boxed::Box;
+use alloc::string::{String, ToString};
 use async_channel::{Receiver, Sender};
 use futures_util::{FutureExt, StreamExt};
+use once_cell::sync::OnceCell;
 use spin::RwLock;
 
-use crate::BinaryDecode;
-use crate::batch::with_runtime;
+use crate::batch::{FlushTimeout, PendingOperation, force_flush, with_runtime};
 use crate::function::{CALL_EXPORT_FN_ID, DROP_NATIVE_REF_FN_ID, RustCallback};
+use crate::function_registry::FUNCTION_REGISTRY;
 use crate::ipc::MessageType;
 use crate::ipc::{DecodedData, DecodedVariant, IPCMessage};
 use crate::object_store::ObjectHandle;
 use crate::object_store::remove_object;
+use crate::wry::ProtocolError;
+use crate::{BinaryDecode, JsValue};
 
 /// Application-level events that can be sent through the event loop.
 ///
@@ -51,6 +61,62 @@ impl WryBindgenEvent {
         }
     }
 
+    /// Create a new event for a webview whose runtime was reset after a reload.
+    pub(crate) fn webview_reloaded(id: u64) -> Self {
+        Self {
+            id,
+            event: AppEventVariant::WebviewReloaded,
+        }
+    }
+
+    /// Create a new event for a webview that failed the protocol handshake.
+    pub(crate) fn handshake_failed(id: u64, error: ProtocolError) -> Self {
+        Self {
+            id,
+            event: AppEventVariant::ProtocolError(error),
+        }
+    }
+
+    /// Create a new event for a flush that JS did not respond to in time.
+    pub(crate) fn flush_timed_out(id: u64, error: FlushTimeout) -> Self {
+        Self {
+            id,
+            event: AppEventVariant::FlushTimeout(error),
+        }
+    }
+
+    /// Get the timeout if this event reports that JS stopped responding to a flush.
+    ///
+    /// The app thread panics or returns the error from
+    /// [`batch_with_timeout`](crate::batch::batch_with_timeout) after sending
+    /// this, so event loops can use it to show a diagnostic.
+    pub fn flush_timeout(&self) -> Option<&FlushTimeout> {
+        match &self.event {
+            AppEventVariant::FlushTimeout(error) => Some(error),
+            _ => None,
+        }
+    }
+
+    /// Check if this event reports that the webview reloaded.
+    ///
+    /// The app thread sends this after it reset its runtime for the new page and
+    /// before it runs the [`on_reload`] callbacks.
+    pub fn is_reload(&self) -> bool {
+        matches!(self.event, AppEventVariant::WebviewReloaded)
+    }
+
+    /// Get the handshake error if this event reports that a webview loaded an
+    /// incompatible init script.
+    ///
+    /// The webview's runtime is shut down when the event is handled, so event
+    /// loops can use this to report the error before exiting.
+    pub fn protocol_error(&self) -> Option<&ProtocolError> {
+        match &self.event {
+            AppEventVariant::ProtocolError(error) => Some(error),
+            _ => None,
+        }
+    }
+
     /// Consume the event and return the inner variant.
     pub(crate) fn into_variant(self) -> AppEventVariant {
         self.event
@@ -63,39 +129,93 @@ pub(crate) enum AppEventVariant {
     Ipc(IPCMessage),
     /// The webview has finished loading
     WebviewLoaded,
+    /// The app thread reset its runtime after the webview reloaded
+    WebviewReloaded,
+    /// The webview loaded an init script that does not match this runtime
+    ProtocolError(ProtocolError),
+    /// JS did not respond to a flush within the timeout
+    FlushTimeout(FlushTimeout),
+}
+
+/// A message for the app thread on the channel for calls from JS.
+enum Incoming {
+    /// A call from JS
+    Message(IPCMessage),
+    /// The webview reloaded. Wakes the app thread so it can reset its runtime.
+    Reloaded,
 }
 
 #[derive(Clone)]
 pub(crate) struct IPCSenders {
-    eval_sender: Sender<IPCMessage>,
+    eval_sender: Sender<Incoming>,
+    /// Used to throw away calls from the old page when the webview reloads
+    eval_receiver: Receiver<Incoming>,
     respond_sender: futures_channel::mpsc::UnboundedSender<IPCMessage>,
+    closed: Arc<OnceCell<String>>,
+    page_loads: Arc<AtomicU32>,
 }
 
 impl IPCSenders {
     pub(crate) fn start_send(&self, msg: IPCMessage) {
+        // Messages JS sends after the connection was closed are dropped
+        if self.closed.get().is_some() {
+            return;
+        }
         match msg.ty().unwrap() {
             MessageType::Evaluate => {
                 self.eval_sender
-                    .try_send(msg)
+                    .try_send(Incoming::Message(msg))
                     .expect("Failed to send evaluate message");
             }
-            MessageType::Respond => {
+            MessageType::Respond | MessageType::Throw => {
                 self.respond_sender
                     .unbounded_send(msg)
                     .expect("Failed to send respond message");
             }
         }
     }
+
+    /// Tell the app thread the webview reloaded.
+    ///
+    /// Calls from the old page that the app thread has not started yet are
+    /// dropped. The app thread resets its runtime before it handles anything
+    /// the new page sends.
+    pub(crate) fn reload(&self) {
+        while self.eval_receiver.try_recv().is_ok() {}
+        self.page_loads.fetch_add(1, Ordering::SeqCst);
+        _ = self.eval_sender.try_send(Incoming::Reloaded);
+    }
+
+    /// Close the connection to JS.
+    ///
+    /// Rust code waiting on JS panics with `reason` instead of hanging, and the
+    /// app stops handling callbacks.
+    pub(crate) fn close(&self, reason: String) {
+        _ = self.closed.set(reason);
+        self.eval_sender.close();
+        self.respond_sender.close_channel();
+    }
 }
 
 struct IPCReceivers {
-    eval_receiver: Pin<Box<Receiver<IPCMessage>>>,
+    eval_receiver: Pin<Box<Receiver<Incoming>>>,
     respond_receiver: futures_channel::mpsc::UnboundedReceiver<IPCMessage>,
 }
 
+/// The result of waiting for a message from JS.
+enum Received {
+    Message(IPCMessage),
+    /// The webview reloaded
+    Reloaded,
+    /// The connection to JS was closed
+    Closed,
+    /// JS did not send anything before the deadline
+    TimedOut,
+}
+
 impl IPCReceivers {
-    pub fn recv_blocking(&mut self) -> Option<IPCMessage> {
-        pollster::block_on(async {
+    fn recv_blocking(&mut self, deadline: Option<Instant>) -> Received {
+        let received = block_on_until(deadline, async {
             let Self {
                 eval_receiver,
                 respond_receiver,
@@ -104,13 +224,64 @@ impl IPCReceivers {
                 // We need to always poll the respond receiver first. If the response is ready, quit immediately
                 // before running any more callbacks
                 respond_msg = respond_receiver.next().fuse() => {
-                    respond_msg
+                    respond_msg.map(Incoming::Message)
                 },
                 eval_msg = eval_receiver.next().fuse() => {
                     eval_msg
                 },
             }
-        })
+        });
+        match received {
+            Some(Some(Incoming::Message(message))) => Received::Message(message),
+            Some(Some(Incoming::Reloaded)) => Received::Reloaded,
+            Some(None) => Received::Closed,
+            None => Received::TimedOut,
+        }
+    }
+
+    /// Like [`IPCReceivers::recv_blocking`], but wake the task when a message arrives instead of blocking.
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Received> {
+        if let Poll::Ready(response) = self.respond_receiver.poll_next_unpin(cx) {
+            return Poll::Ready(response.map_or(Received::Closed, Received::Message));
+        }
+        self.eval_receiver
+            .poll_next_unpin(cx)
+            .map(|incoming| match incoming {
+                Some(Incoming::Message(message)) => Received::Message(message),
+                Some(Incoming::Reloaded) => Received::Reloaded,
+                None => Received::Closed,
+            })
+    }
+}
+
+/// Like `pollster::block_on`, but gives up and returns `None` at the deadline.
+fn block_on_until<F: core::future::Future>(
+    deadline: Option<Instant>,
+    future: F,
+) -> Option<F::Output> {
+    struct ThreadWaker(std::thread::Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let Some(deadline) = deadline else {
+        return Some(pollster::block_on(future));
+    };
+    let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return Some(output);
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return None;
+        }
+        std::thread::park_timeout(deadline - now);
     }
 }
 
@@ -121,6 +292,15 @@ impl IPCReceivers {
 pub(crate) struct WryIPC {
     pub(crate) proxy: Arc<dyn Fn(WryBindgenEvent) + Send + Sync>,
     receivers: RwLock<IPCReceivers>,
+    closed: Arc<OnceCell<String>>,
+    /// How many times the webview reloaded
+    page_loads: Arc<AtomicU32>,
+    /// Wakes [`handle_callbacks`] to run the reload callbacks after the runtime is reset
+    reload_sender: Sender<()>,
+    reload_receiver: Receiver<()>,
+    /// Work sent to the runtime from other threads with a [`RuntimeHandle`]
+    task_sender: Sender<Task>,
+    task_receiver: Receiver<Task>,
 }
 
 impl WryIPC {
@@ -128,46 +308,297 @@ impl WryIPC {
     pub(crate) fn new(proxy: Arc<dyn Fn(WryBindgenEvent) + Send + Sync>) -> (Self, IPCSenders) {
         let (eval_sender, eval_receiver) = async_channel::unbounded();
         let (respond_sender, respond_receiver) = futures_channel::mpsc::unbounded();
+        let closed = Arc::new(OnceCell::new());
+        let page_loads = Arc::new(AtomicU32::new(0));
         let senders = IPCSenders {
             eval_sender,
+            eval_receiver: eval_receiver.clone(),
             respond_sender,
+            closed: closed.clone(),
+            page_loads: page_loads.clone(),
         };
         let receivers = RwLock::new(IPCReceivers {
             eval_receiver: Box::pin(eval_receiver),
             respond_receiver,
         });
-        let ipc = Self { proxy, receivers };
+        let (reload_sender, reload_receiver) = async_channel::unbounded();
+        let (task_sender, task_receiver) = async_channel::unbounded();
+        let ipc = Self {
+            proxy,
+            receivers,
+            closed,
+            page_loads,
+            reload_sender,
+            reload_receiver,
+            task_sender,
+            task_receiver,
+        };
         (ipc, senders)
     }
 
+    /// Check if JS sent Evaluate messages that have not been handled yet.
+    pub(crate) fn has_queued_evaluates(&self) -> bool {
+        !self.receivers.read().eval_receiver.is_empty()
+    }
+
     /// Send a response back to JavaScript.
     pub(crate) fn js_response(&self, id: u64, responder: IPCMessage) {
         (self.proxy)(WryBindgenEvent::ipc(id, responder));
     }
 }
 
+/// Wait for the next message from JS. Returns the result of `with_respond` if it
+/// is the response to the current Evaluate, or `None` if it was a callback.
+///
+/// `operations` are the operations in the Evaluate, used to report where an
+/// operation that threw was called from. If JS sends nothing within `timeout`,
+/// this returns the timeout as an error.
 pub(crate) fn progress_js_with<O>(
+    operations: &[PendingOperation],
+    timeout: Option<Duration>,
     with_respond: impl for<'a> Fn(DecodedData<'a>) -> O,
-) -> Option<O> {
-    let response = with_runtime(|runtime| runtime.ipc().receivers.write().recv_blocking())?;
+) -> Result<Option<O>, Duration> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let response =
+        match with_runtime(|runtime| runtime.ipc().receivers.write().recv_blocking(deadline)) {
+            Received::Message(response) => response,
+            Received::Closed => panic_closed(),
+            Received::Reloaded => {
+                // The flush was sent to the old page if the runtime was not reset yet
+                if check_reload() {
+                    panic_reloaded(operations.len());
+                }
+                return Ok(None);
+            }
+            Received::TimedOut => return Err(timeout.unwrap_or_default()),
+        };
 
     let decoder = response.decoded().expect("Failed to decode response");
     match decoder {
-        DecodedVariant::Respond { data } => Some(with_respond(data)),
         DecodedVariant::Evaluate { mut data } => {
+            let generation = with_runtime(|runtime| runtime.generation());
             handle_rust_callback(&mut data);
-            None
+            // The webview reloaded while the callback ran, the old page will never respond
+            if with_runtime(|runtime| runtime.generation()) != generation {
+                panic_reloaded(operations.len());
+            }
+            Ok(None)
         }
+        // JS finished a flush that already timed out
+        DecodedVariant::Respond { .. } | DecodedVariant::Throw { .. }
+            if with_runtime(|runtime| runtime.take_abandoned_response()) =>
+        {
+            Ok(None)
+        }
+        DecodedVariant::Respond { data } => Ok(Some(with_respond(data))),
+        DecodedVariant::Throw { mut data } => panic!("{}", js_exception(&mut data, operations)),
     }
 }
 
-pub async fn handle_callbacks() {
-    let receiver = with_runtime(|runtime| runtime.ipc().receivers.read().eval_receiver.clone());
+/// Poll until no async call is in flight, running the callbacks JS makes meanwhile.
+pub(crate) fn poll_in_flight(cx: &mut Context<'_>) -> Poll<()> {
+    while with_runtime(|runtime| runtime.wait_for_in_flight(cx.waker())) {
+        let received = ready!(with_runtime(|runtime| runtime
+            .ipc()
+            .receivers
+            .write()
+            .poll_recv(cx)));
+        handle_in_flight_message(received);
+    }
+    Poll::Ready(())
+}
+
+/// Block until no async call is in flight, running the callbacks JS makes meanwhile.
+pub(crate) fn finish_in_flight() {
+    while with_runtime(|runtime| runtime.has_in_flight()) {
+        let received = with_runtime(|runtime| runtime.ipc().receivers.write().recv_blocking(None));
+        handle_in_flight_message(received);
+    }
+}
 
-    while let Ok(response) = receiver.recv().await {
+/// Resolve the async call in flight if JS already responded to it.
+///
+/// JS runs one thing at a time, so a response it sent before it started a call
+/// into Rust is the response to the async call, not to a flush made by the
+/// callback.
+fn take_in_flight_response() {
+    while with_runtime(|runtime| runtime.has_in_flight()) {
+        let response = with_runtime(|runtime| {
+            let mut receivers = runtime.ipc().receivers.write();
+            receivers.respond_receiver.next().now_or_never()
+        });
+        match response {
+            Some(Some(response)) => handle_in_flight_message(Received::Message(response)),
+            Some(None) => panic_closed(),
+            None => break,
+        }
+    }
+}
+
+/// Handle a message from JS while an async call is in flight.
+fn handle_in_flight_message(received: Received) {
+    let response = match received {
+        Received::Message(response) => response,
+        Received::Closed => panic_closed(),
+        // Resetting the runtime fails the call, the old page will never respond
+        Received::Reloaded => {
+            check_reload();
+            return;
+        }
+        Received::TimedOut => unreachable!("Async calls wait without a timeout"),
+    };
+    let decoder = response.decoded().expect("Failed to decode response");
+    match decoder {
+        DecodedVariant::Evaluate { mut data } => handle_rust_callback(&mut data),
+        // JS finished a flush that already timed out
+        DecodedVariant::Respond { .. } | DecodedVariant::Throw { .. }
+            if with_runtime(|runtime| runtime.take_abandoned_response()) => {}
+        DecodedVariant::Respond { mut data } => {
+            let in_flight = with_runtime(|runtime| runtime.take_in_flight())
+                .expect("JS responded to an async call that was not sent");
+            in_flight.resolve(Ok(&mut data));
+        }
+        DecodedVariant::Throw { mut data } => {
+            let in_flight = with_runtime(|runtime| runtime.take_in_flight())
+                .expect("JS responded to an async call that was not sent");
+            let exception = js_exception(&mut data, &in_flight.operations);
+            in_flight.resolve(Err(exception));
+        }
+    }
+}
+
+/// Panic because the connection to JS was closed while Rust waited on it.
+fn panic_closed() -> ! {
+    let reason = with_runtime(|runtime| runtime.ipc().closed.get().cloned())
+        .unwrap_or_else(|| "the webview is gone".to_string());
+    panic!("The connection to JS was closed: {reason}");
+}
+
+/// Describe an exception JS threw while running an operation.
+fn js_exception(data: &mut DecodedData, operations: &[PendingOperation]) -> String {
+    let fn_id = data.take_u32().expect("Failed to read fn_id");
+    let op_index = data.take_u32().expect("Failed to read operation index");
+    let message = data.take_str().expect("Failed to read exception message");
+    let stack = data.take_str().expect("Failed to read exception stack");
+
+    let call_site = operations.get(op_index as usize).map_or_else(
+        || "an unknown location".to_string(),
+        |operation| operation.location.to_string(),
+    );
+    let js_code = FUNCTION_REGISTRY
+        .js_code(fn_id)
+        .unwrap_or_else(|| "<unknown function>".to_string());
+    let stack = if stack.is_empty() {
+        String::new()
+    } else {
+        format!("\nJS stack:\n{stack}")
+    };
+    format!(
+        "JS threw an exception in a call from {call_site}: {message}\nJS function {fn_id}: {js_code}{stack}"
+    )
+}
+
+/// Panic because the webview reloaded before JS finished a batch.
+pub(crate) fn panic_reloaded(operations: usize) -> ! {
+    panic!("The webview reloaded before JS finished a batch of {operations} operations")
+}
+
+/// Reset the runtime if the webview reloaded since the last check.
+///
+/// Returns `true` if the runtime was reset. Values and closures from the old
+/// page are invalid after this, and the event loop is told it can deliver
+/// messages to the new page.
+pub(crate) fn check_reload() -> bool {
+    let stale = with_runtime(|runtime| {
+        let page_loads = runtime.ipc().page_loads.load(Ordering::SeqCst);
+        (page_loads != runtime.generation()).then(|| runtime.reset_for_reload(page_loads))
+    });
+    let Some(stale) = stale else {
+        return false;
+    };
+    drop(stale);
+    crate::intern::clear_cache();
+
+    let (proxy, webview_id) =
+        with_runtime(|runtime| (runtime.ipc().proxy.clone(), runtime.webview_id()));
+    proxy(WryBindgenEvent::webview_reloaded(webview_id));
+    with_runtime(|runtime| runtime.ipc().reload_sender.try_send(()))
+        .expect("The reload channel is never closed");
+    true
+}
+
+/// Reset the runtime if the webview reloaded and run the [`on_reload`] callbacks.
+///
+/// This must only run when Rust is not in the middle of a call to or from JS.
+#[cfg(feature = "fake")]
+pub(crate) fn handle_reload() {
+    check_reload();
+    let mut reloaded = false;
+    while with_runtime(|runtime| runtime.ipc().reload_receiver.try_recv()).is_ok() {
+        reloaded = true;
+    }
+    if reloaded {
+        run_reload_listeners();
+    }
+}
+
+fn run_reload_listeners() {
+    let mut listeners = with_runtime(|runtime| runtime.take_reload_listeners());
+    for listener in &mut listeners {
+        listener();
+    }
+    with_runtime(|runtime| runtime.restore_reload_listeners(listeners));
+}
+
+/// Run `callback` on the app thread every time the webview reloads.
+///
+/// When the page reloads, every [`JsValue`] and [`Closure`](crate::Closure) from
+/// the old page becomes invalid and using one panics. Thread locals are
+/// initialized again the next time they are used. Use this to rebuild the UI
+/// on the new page.
+pub fn on_reload(callback: impl FnMut() + 'static) {
+    with_runtime(|runtime| runtime.add_reload_listener(Box::new(callback)));
+}
+
+pub async fn handle_callbacks() {
+    let (receiver, reloads, tasks) = with_runtime(|runtime| {
+        (
+            runtime.ipc().receivers.read().eval_receiver.clone(),
+            runtime.ipc().reload_receiver.clone(),
+            runtime.ipc().task_receiver.clone(),
+        )
+    });
+    // Futures spawned with a RuntimeHandle
+    let mut spawned = futures_util::stream::FuturesUnordered::new();
+
+    loop {
+        let message = futures_util::select_biased! {
+            // The runtime was reset for a new page, let the app rebuild it
+            _ = reloads.recv().fuse() => Woken::Reloaded,
+            message = receiver.recv().fuse() => Woken::Message(message),
+            task = tasks.recv().fuse() => Woken::Task(task.ok()),
+            _ = spawned.select_next_some() => Woken::TaskFinished,
+        };
+        let response = match message {
+            Woken::Reloaded => {
+                run_reload_listeners();
+                continue;
+            }
+            Woken::Message(Ok(Incoming::Message(response))) => response,
+            Woken::Message(Ok(Incoming::Reloaded)) => {
+                check_reload();
+                continue;
+            }
+            Woken::Message(Err(_)) => break,
+            Woken::Task(task) => {
+                spawned.extend(task.map(|task| task()));
+                continue;
+            }
+            Woken::TaskFinished => continue,
+        };
         let decoder = response.decoded().expect("Failed to decode response");
         match decoder {
-            DecodedVariant::Respond { .. } => unreachable!(),
+            DecodedVariant::Respond { .. } | DecodedVariant::Throw { .. } => unreachable!(),
             DecodedVariant::Evaluate { mut data } => {
                 handle_rust_callback(&mut data);
             }
@@ -175,10 +606,184 @@ pub async fn handle_callbacks() {
     }
 }
 
+/// What woke up [`handle_callbacks`].
+enum Woken {
+    Reloaded,
+    Message(Result<Incoming, async_channel::RecvError>),
+    Task(Option<Task>),
+    TaskFinished,
+}
+
+/// A closure sent to the runtime thread, which starts a future there.
+type Task = Box<dyn FnOnce() -> Pin<Box<dyn Future<Output = ()>>> + Send>;
+
+/// A handle to run code on an app's runtime from any thread.
+///
+/// Get one with [`RuntimeHandle::current`] inside the app, then clone it into
+/// worker threads that need to use JS values. Work sent through the handle runs
+/// in [`handle_callbacks`], so it waits while the app is blocked on a
+/// synchronous call and runs once the app awaits something.
+///
+/// # Example
+///
+/// ```ignore
+/// let runtime = RuntimeHandle::current();
+/// rayon::spawn(move || {
+///     let text = index_files();
+///     runtime.spawn(move || async move { set_status(&text) });
+/// });
+/// ```
+#[derive(Clone)]
+pub struct RuntimeHandle {
+    webview_id: u64,
+    tasks: Sender<Task>,
+}
+
+impl RuntimeHandle {
+    /// Get a handle to the runtime running on this thread.
+    ///
+    /// Panics if called outside a runtime.
+    pub fn current() -> Self {
+        with_runtime(|runtime| Self {
+            webview_id: runtime.webview_id(),
+            tasks: runtime.ipc().task_sender.clone(),
+        })
+    }
+
+    /// The id of the webview the runtime belongs to.
+    pub fn webview_id(&self) -> u64 {
+        self.webview_id
+    }
+
+    /// Start a future on the runtime thread.
+    ///
+    /// `f` is called on the runtime thread, so the future it returns doesn't need
+    /// to be `Send`. Does nothing if the app already exited.
+    pub fn spawn<F, Fut>(&self, f: F)
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + 'static,
+    {
+        _ = self.tasks.try_send(Box::new(move || {
+            Box::pin(f()) as Pin<Box<dyn Future<Output = ()>>>
+        }));
+    }
+
+    /// Run `f` on the runtime thread and block until it returns.
+    ///
+    /// On the runtime thread itself, `f` runs immediately. If `f` panics, the
+    /// panic continues on the calling thread. Panics if the app exits before
+    /// running `f`.
+    pub fn run_blocking<T, F>(&self, f: F) -> T
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        if crate::batch::current_webview_id() == Some(self.webview_id) {
+            return f();
+        }
+        let (sender, receiver) = std::sync::mpsc::sync_channel(1);
+        self.spawn(move || {
+            _ = sender.send(std::panic::catch_unwind(AssertUnwindSafe(f)));
+            core::future::ready(())
+        });
+        match receiver.recv() {
+            Ok(Ok(value)) => value,
+            Ok(Err(panic)) => std::panic::resume_unwind(panic),
+            Err(_) => panic!("The app exited before running the closure"),
+        }
+    }
+}
+
+/// Start a future on this thread's runtime. Unlike [`RuntimeHandle::spawn`],
+/// the future doesn't need to be `Send`.
+///
+/// The future starts once the app awaits [`handle_callbacks`].
+pub(crate) fn spawn_local(future: impl Future<Output = ()> + 'static) {
+    let tasks = with_runtime(|runtime| {
+        runtime.push_local_task(Box::pin(future));
+        runtime.ipc().task_sender.clone()
+    });
+    // The task runs on this thread and takes the future back out of the runtime.
+    // It finds nothing if the webview reloaded in between.
+    _ = tasks.try_send(Box::new(|| {
+        with_runtime(|runtime| runtime.pop_local_task())
+            .unwrap_or_else(|| Box::pin(core::future::ready(())))
+    }));
+}
+
+impl core::fmt::Debug for RuntimeHandle {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("RuntimeHandle")
+            .field("webview_id", &self.webview_id)
+            .finish()
+    }
+}
+
+/// A JS value thrown with [`throw_val`](crate::throw_val) while Rust was running a call from JS.
+pub(crate) struct JsThrow(pub(crate) JsValue);
+
 /// Handle a Rust callback invocation from JavaScript.
-fn handle_rust_callback(data: &mut DecodedData) {
+///
+/// If the call panics or throws a value, the JS caller gets a Throw response
+/// and rethrows it instead of waiting for a response that never comes. If the
+/// webview reloaded during the call, the page that made it is gone and gets
+/// no response.
+pub(crate) fn handle_rust_callback(data: &mut DecodedData) {
+    // Calls from JS started after JS responded to the async call can't take its response
+    if !crate::batch::in_js_call() {
+        take_in_flight_response();
+    }
+    // Values encoded into the response are dropped after JS reads it
+    let (frame, generation) = with_runtime(|state| {
+        state.push_ids_to_free();
+        (state.enter_js_call(), state.generation())
+    });
+    let result = std::panic::catch_unwind(AssertUnwindSafe(|| call_rust(data)));
+    let reloaded = with_runtime(|state| state.generation()) != generation;
+
+    let (response, thrown) = match result {
+        Ok(response) => {
+            with_runtime(|state| state.exit_js_call(frame));
+            (Some(response), None)
+        }
+        Err(_) if reloaded => {
+            let ids = with_runtime(|state| state.unwind_js_call(frame));
+            for id in ids {
+                crate::js_helpers::js_drop_heap_ref(id);
+            }
+            (None, None)
+        }
+        Err(payload) => {
+            let ids = with_runtime(|state| state.unwind_js_call(frame));
+            // JS already ran up to the failed call, so run anything the call queued before it
+            // unwound. If that throws too, the original panic is the more useful error.
+            _ = std::panic::catch_unwind(force_flush);
+            for id in ids {
+                crate::js_helpers::js_drop_heap_ref(id);
+            }
+            let value = match payload.downcast::<JsThrow>() {
+                Ok(thrown) => thrown.0,
+                Err(payload) => crate::__rt::panic_to_panic_error(payload),
+            };
+            (Some(IPCMessage::new_throw(&value)), Some(value))
+        }
+    };
+    if let Some(response) = response.filter(|_| !reloaded) {
+        with_runtime(|runtime| runtime.ipc().js_response(runtime.webview_id(), response));
+    }
+
+    drop(thrown);
+    let ids = with_runtime(|state| state.pop_and_release_ids());
+    for id in ids {
+        crate::js_helpers::js_drop_heap_ref(id);
+    }
+}
+
+/// Run the Rust side of a call from JavaScript and encode its response.
+fn call_rust(data: &mut DecodedData) -> IPCMessage {
     let fn_id = data.take_u32().expect("Failed to read fn_id");
-    let response = match fn_id {
+    match fn_id {
         // Call a registered Rust callback
         0 => {
             let key = data.take_u32().unwrap();
@@ -225,8 +830,10 @@ fn handle_rust_callback(data: &mut DecodedData) {
                 .find(|e| e.name == export_name)
                 .unwrap_or_else(|| panic!("Unknown export: {export_name}"));
 
-            // Call the handler
+            // Arguments JS passed by reference are on the borrow stack for this call
+            with_runtime(|state| state.push_borrow_frame());
             let result = (export.handler)(data);
+            with_runtime(|state| state.pop_borrow_frame());
 
             assert!(data.is_empty(), "Extra data remaining after export call");
 
@@ -241,6 +848,5 @@ fn handle_rust_callback(data: &mut DecodedData) {
             }
         }
         _ => todo!(),
-    };
-    with_runtime(|runtime| runtime.ipc().js_response(runtime.webview_id(), response));
+    }
 }
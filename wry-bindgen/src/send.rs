@@ -0,0 +1,169 @@
+//! Handles to JS values that can move between native threads.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+use core::mem::ManuallyDrop;
+use core::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::batch::{current_webview_id, queue_js_drop, with_runtime};
+use crate::value::{JSIDX_RESERVED, JSIDX_UNDEFINED, JsValue};
+
+/// Heap values whose [`SendJsValue`] was dropped off the runtime's thread.
+///
+/// They are freed the next time the runtime calls into JS.
+#[derive(Default)]
+pub(crate) struct ForeignDrops {
+    /// Set when `values` may be non-empty, so the runtime can skip the lock
+    pending: AtomicBool,
+    /// The heap id and page load of each dropped value
+    values: Mutex<Vec<(u64, u32)>>,
+}
+
+impl ForeignDrops {
+    fn push(&self, id: u64, generation: u32) {
+        self.values.lock().unwrap().push((id, generation));
+        self.pending.store(true, Ordering::Release);
+    }
+
+    /// Take every value dropped since the last call.
+    pub(crate) fn take(&self) -> Vec<(u64, u32)> {
+        if !self.pending.swap(false, Ordering::Acquire) {
+            return Vec::new();
+        }
+        core::mem::take(&mut *self.values.lock().unwrap())
+    }
+}
+
+/// Free the values dropped on other threads since the last call.
+pub(crate) fn apply_foreign_drops() {
+    let dropped = with_runtime(|runtime| runtime.foreign_drops().take());
+    for (id, generation) in dropped {
+        queue_js_drop(id, generation);
+    }
+}
+
+/// A handle to a JS value that is `Send + Sync`.
+///
+/// A [`JsValue`] belongs to the runtime of the webview that created it. A
+/// `SendJsValue` keeps the heap value alive while it is passed to other threads,
+/// and turns back into a [`JsValue`] on the runtime that owns it. Dropping the
+/// handle on another thread frees the value the next time that runtime calls
+/// into JS.
+///
+/// # Example
+///
+/// ```ignore
+/// let node = SendJsValue::new(document.create_element("div")?.into());
+/// std::thread::spawn(move || {
+///     let text = index_files();
+///     // Send the handle back to the app, which sets `text` on the node
+///     results.send((node, text)).unwrap();
+/// });
+/// ```
+pub struct SendJsValue {
+    id: u64,
+    generation: u32,
+    webview_id: u64,
+    drops: Arc<ForeignDrops>,
+}
+
+impl SendJsValue {
+    /// Wrap a value so it can leave the runtime's thread.
+    ///
+    /// Panics if called outside a runtime.
+    #[track_caller]
+    pub fn new(value: JsValue) -> Self {
+        let value = ManuallyDrop::new(value);
+        let id = value.js_id();
+        let (webview_id, drops) =
+            with_runtime(|runtime| (runtime.webview_id(), runtime.foreign_drops().clone()));
+        Self {
+            id,
+            generation: value.generation(),
+            webview_id,
+            drops,
+        }
+    }
+
+    /// The id of the webview the value belongs to.
+    pub fn webview_id(&self) -> u64 {
+        self.webview_id
+    }
+
+    /// Check if this thread is running the runtime that owns the value.
+    pub fn is_owned_here(&self) -> bool {
+        current_webview_id() == Some(self.webview_id)
+    }
+
+    /// Turn the handle back into the value.
+    ///
+    /// Panics if this thread isn't running the runtime that owns the value.
+    #[track_caller]
+    pub fn into_js_value(self) -> JsValue {
+        match self.try_into_js_value() {
+            Ok(value) => value,
+            Err(handle) => panic!(
+                "A SendJsValue from webview {} can only be used on that webview's runtime",
+                handle.webview_id
+            ),
+        }
+    }
+
+    /// Turn the handle back into the value, or return the handle if this thread
+    /// isn't running the runtime that owns the value.
+    pub fn try_into_js_value(mut self) -> Result<JsValue, Self> {
+        if !self.is_owned_here() {
+            return Err(self);
+        }
+        let value = JsValue::from_heap_id(self.id, self.generation);
+        // The value owns the heap reference now, don't free it when the handle drops
+        self.id = JSIDX_UNDEFINED;
+        Ok(value)
+    }
+
+    /// Borrow the value without consuming the handle.
+    ///
+    /// Panics if this thread isn't running the runtime that owns the value.
+    #[track_caller]
+    pub fn with<R>(&self, f: impl FnOnce(&JsValue) -> R) -> R {
+        if !self.is_owned_here() {
+            panic!(
+                "A SendJsValue from webview {} can only be used on that webview's runtime",
+                self.webview_id
+            );
+        }
+        let value = ManuallyDrop::new(JsValue::from_heap_id(self.id, self.generation));
+        f(&value)
+    }
+}
+
+impl From<JsValue> for SendJsValue {
+    #[track_caller]
+    fn from(value: JsValue) -> Self {
+        Self::new(value)
+    }
+}
+
+impl Drop for SendJsValue {
+    fn drop(&mut self) {
+        if self.id < JSIDX_RESERVED {
+            return;
+        }
+        if self.is_owned_here() {
+            queue_js_drop(self.id, self.generation);
+        } else {
+            self.drops.push(self.id, self.generation);
+        }
+    }
+}
+
+impl fmt::Debug for SendJsValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SendJsValue")
+            .field("id", &self.id)
+            .field("webview_id", &self.webview_id)
+            .finish()
+    }
+}
@@ -139,6 +139,11 @@ export function drop_heap_ref(heapId: number): void {
   window.jsHeap.remove(heapId);
 }
 
+// Heap management - count the values alive in the JS heap
+export function heap_objects_alive(): number {
+  return window.jsHeap.heapObjectsAlive();
+}
+
 // Create a wrapper object for an exported Rust struct
 export function create_rust_object_wrapper(handle: number, className: string): unknown {
   return window.rustExports.createWrapper(handle, className);
@@ -159,6 +159,13 @@ class DataDecoder {
     return this.u32Buf[this.u32Offset++];
   }
 
+  /**
+   * Get the next u32 without taking it.
+   */
+  peekU32(): number {
+    return this.u32Buf[this.u32Offset];
+  }
+
   /**
    * Check if there are more u32 values available to read.
    * Used for iterating over batched operations.
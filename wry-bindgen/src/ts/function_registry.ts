@@ -1,12 +1,24 @@
-import { TypeClass } from "./types";
+import { TypeClass, parseTypeDef } from "./types";
 
 /**
  * Function registry - maps function IDs to raw JS functions.
- * Type information is now passed at call time, not registration time.
+ * The init script only registers how many functions there are. Each function's
+ * code is fetched from Rust and compiled the first time it is called.
+ * Functions with a signature known at compile time get their types at
+ * registration time, other functions pass type information at call time.
  */
 export type RawJsFunction = (...args: any[]) => any;
 
-let functionRegistry: RawJsFunction[] | null = null;
+let functionRegistry: (RawJsFunction | undefined)[] = [];
+
+let functionCount = 0;
+
+/**
+ * The inline_js modules function code can refer to, by the name the code uses
+ * for them (`module_{hash}`).
+ */
+let moduleNames: string[] = [];
+let moduleValues: unknown[] = [];
 
 /**
  * Type cache - maps type IDs to parsed type information.
@@ -19,12 +31,78 @@ export interface CachedTypeInfo {
 
 const typeCache: Map<number, CachedTypeInfo> = new Map();
 
-export function getFunctionRegistry(): RawJsFunction[] {
-  return functionRegistry!;
+/**
+ * Registered signatures by function ID. Calls to these functions don't carry
+ * any type information.
+ */
+let functionTypes: (CachedTypeInfo | undefined)[] = [];
+
+/**
+ * Get the function with this ID, fetching and compiling its code if it has not
+ * been used yet.
+ */
+export function getFunction(fnId: number): RawJsFunction | undefined {
+  const loaded = functionRegistry[fnId];
+  if (loaded || fnId >= functionCount) {
+    return loaded;
+  }
+  const xhr = new XMLHttpRequest();
+  xhr.open("GET", `/__wbg__/fn/${fnId}`, false);
+  xhr.send();
+  if (xhr.status !== 200) {
+    throw new Error(`Failed to load the code of JS function ${fnId}`);
+  }
+  loadFunctions(xhr.responseText);
+  return functionRegistry[fnId];
+}
+
+/**
+ * Compile a chunk of function code sent by Rust. The chunk is an object
+ * expression mapping function IDs to functions.
+ */
+export function loadFunctions(chunk: string) {
+  const functions: Record<number, RawJsFunction> = new Function(
+    ...moduleNames,
+    `return ${chunk};`
+  )(...moduleValues);
+  for (const [fnId, jsFunction] of Object.entries(functions)) {
+    functionRegistry[Number(fnId)] = jsFunction;
+  }
+}
+
+export function setFunctionRegistry(
+  count: number,
+  modules: Record<string, unknown> = {},
+  types: number[][] = [],
+  functionTypeIds: (number | null)[] = []
+) {
+  functionRegistry = [];
+  functionCount = count;
+  moduleNames = Object.keys(modules);
+  moduleValues = Object.values(modules);
+  const parsed = types.map(parseSignature);
+  functionTypes = functionTypeIds.map((typeId) =>
+    typeId === null ? undefined : parsed[typeId]
+  );
+}
+
+/**
+ * Parse a signature from the registry: a param count followed by the param
+ * and return type definitions.
+ */
+export function parseSignature(signature: number[]): CachedTypeInfo {
+  const bytes = new Uint8Array(signature);
+  const offset = { value: 1 };
+  const paramTypes: TypeClass[] = [];
+  for (let i = 0; i < bytes[0]; i++) {
+    paramTypes.push(parseTypeDef(bytes, offset));
+  }
+  const returnType = parseTypeDef(bytes, offset);
+  return { paramTypes, returnType };
 }
 
-export function setFunctionRegistry(registry: RawJsFunction[]) {
-  functionRegistry = registry;
+export function getFunctionTypes(): (CachedTypeInfo | undefined)[] {
+  return functionTypes;
 }
 
 export function getTypeCache(): Map<number, CachedTypeInfo> {
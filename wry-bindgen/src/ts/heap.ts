@@ -6,17 +6,30 @@ const JSIDX_TRUE = JSIDX_OFFSET + 2;
 const JSIDX_FALSE = JSIDX_OFFSET + 3;
 const JSIDX_RESERVED = JSIDX_OFFSET + 4;
 
+// FNV-1a hash of the free list in order - must match free_list_hash in Rust's ipc.rs
+function freeListHash(freeIds: number[]): number {
+  let hash = 2166136261;
+  for (const id of freeIds) {
+    hash = Math.imul(hash ^ (id >>> 0), 16777619) >>> 0;
+    hash = Math.imul(hash ^ Math.floor(id / 0x100000000), 16777619) >>> 0;
+  }
+  return hash;
+}
+
 // Object store implementation for JS heap types
 class JSHeap {
   private slots: (unknown | undefined)[];
+  // Freed IDs available for reuse. Rust keeps the same stack and tells us when
+  // to add to it, so both sides hand out the same IDs
   private freeIds: number[];
   private maxId: number;
+  private alive: number;
   // Borrow stack uses indices 1-127, growing downward from 127 to 1
   private borrowStackPointer: number;
   // Frame stack for nested operations - saves borrow stack pointers
   private borrowFrameStack: number[];
   // Stack of reservation scopes: each scope tracks reserved IDs for batch mode
-  private reservationStack: { start: number; count: number; nextIndex: number }[];
+  private reservationStack: { ids: number[]; nextIndex: number }[];
 
   constructor() {
     // Pre-allocate slots array - slots 0-127 are for borrow stack (1-127 usable),
@@ -32,6 +45,7 @@ class JSHeap {
     this.freeIds = [];
     // Start allocating from JSIDX_RESERVED (132)
     this.maxId = JSIDX_RESERVED;
+    this.alive = 0;
     // Borrow stack pointer starts at 128 (just below reserved values)
     this.borrowStackPointer = JSIDX_OFFSET;
     // Frame stack starts empty
@@ -40,19 +54,31 @@ class JSHeap {
     this.reservationStack = [];
   }
 
-  insert(value: unknown): number {
-    const id = this.maxId;
-    this.maxId++;
+  // Reuse the most recently recycled ID, or grow the heap
+  private allocId(): number {
+    return this.freeIds.pop() ?? this.maxId++;
+  }
+
+  private set(id: number, value: unknown): void {
+    if (!(id in this.slots)) {
+      this.alive++;
+    }
     this.slots[id] = value;
+  }
+
+  insert(value: unknown): number {
+    const id = this.allocId();
+    this.set(id, value);
     return id;
   }
 
-  // Push a reservation scope for `count` IDs starting at current maxId
+  // Push a reservation scope for the `count` IDs Rust handed out as placeholders
   pushReservationScope(count: number): void {
-    const start = this.maxId;
-    this.reservationStack.push({ start, count, nextIndex: 0 });
-    // Advance maxId past all reserved IDs
-    this.maxId += count;
+    const ids: number[] = [];
+    for (let i = 0; i < count; i++) {
+      ids.push(this.allocId());
+    }
+    this.reservationStack.push({ ids, nextIndex: 0 });
   }
 
   popReservationScope(): void {
@@ -62,12 +88,32 @@ class JSHeap {
   // Fill the next reserved slot in the current scope
   fillNextReserved(value: unknown): void {
     const scope = this.reservationStack[this.reservationStack.length - 1];
-    if (!scope || scope.nextIndex >= scope.count) {
+    if (!scope || scope.nextIndex >= scope.ids.length) {
       throw new Error("No reserved slots available");
     }
-    const id = scope.start + scope.nextIndex;
+    const id = scope.ids[scope.nextIndex];
     scope.nextIndex++;
-    this.slots[id] = value;
+    this.set(id, value);
+  }
+
+  // Make freed IDs available for reuse, in the same order Rust does
+  recycle(ids: number[]): void {
+    for (const id of ids) {
+      this.freeIds.push(id);
+    }
+  }
+
+  // Compare the free list with the one Rust sent. Returns an error message if they differ
+  checkFreeList(freeIds: number, maxId: number, hash: number): string | null {
+    const ourHash = freeListHash(this.freeIds);
+    if (freeIds === this.freeIds.length && maxId === this.maxId && hash === ourHash) {
+      return null;
+    }
+    return (
+      `The JS heap is out of sync with Rust: JS has ${this.freeIds.length} free ids, ` +
+      `max id ${this.maxId} and hash ${ourHash}, Rust has ${freeIds} free ids, ` +
+      `max id ${maxId} and hash ${hash}`
+    );
   }
 
   get(id: number): unknown | undefined {
@@ -82,17 +128,20 @@ class JSHeap {
 
     const value = this.slots[id];
 
+    // The ID is reused once Rust recycles it
+    if (id in this.slots) {
+      this.alive--;
+    }
     delete this.slots[id];
-    this.freeIds.push(id);
     return value;
   }
 
   has(id: number): boolean {
-    return this.freeIds.indexOf(id) === -1 && id < this.slots.length;
+    return id in this.slots;
   }
 
   heapObjectsAlive(): number {
-    return this.slots.length - this.freeIds.length - JSIDX_RESERVED;
+    return this.alive;
   }
 
   // Add a borrowed reference to the borrow stack (indices 1-127)
@@ -9,17 +9,24 @@
  * - string buffer: from str_offset to end
  *
  * Message format in the u8 buffer:
- * - First u8: message type (0 = Evaluate, 1 = Respond)
+ * - First u8: message type (0 = Evaluate, 1 = Respond, 2 = Throw)
  * - Remaining data depends on message type
  */
 
 import { DataDecoder, DataEncoder } from "./encoding";
-import { getFunctionRegistry, getTypeCache, CachedTypeInfo } from "./function_registry";
+import {
+  getFunction,
+  getFunctionTypes,
+  loadFunctions,
+  getTypeCache,
+  CachedTypeInfo,
+} from "./function_registry";
 import { parseTypeDef, TypeClass, HeapRefType } from "./types";
 
 enum MessageType {
   Evaluate = 0,
   Respond = 1,
+  Throw = 2,
 }
 
 // Type caching markers - must match Rust's TYPE_CACHED and TYPE_FULL
@@ -32,18 +39,99 @@ const DROP_NATIVE_REF_FN_ID = 0xffffffff;
 // Reserved function ID for calling exported Rust struct methods - must match Rust's CALL_EXPORT_FN_ID
 const CALL_EXPORT_FN_ID = 0xfffffffe;
 
+// Version of the binary protocol - must match Rust's PROTOCOL_VERSION
+const PROTOCOL_VERSION = 2;
+
+/**
+ * How IPC payloads are carried over the synchronous XHR.
+ * - Binary: raw bytes in the POST body, raw bytes in the response body
+ * - Header: base64 in the `dioxus-data` header, base64 text in the response body.
+ *   Fallback for platforms whose custom protocol handlers drop request bodies (Android).
+ */
+enum IpcEncoding {
+  Binary = "binary",
+  Header = "header",
+}
+
+let ipcEncoding: IpcEncoding = IpcEncoding.Binary;
+
+/**
+ * Select the IPC encoding. Called by the init script before any requests are made.
+ */
+function setIpcEncoding(encoding: IpcEncoding) {
+  ipcEncoding = encoding;
+}
+
+// Hash of the init script this runtime was loaded with, set by the init script
+let bundleHash = "";
+
+/**
+ * Set the bundle hash sent in the handshake. Called by the init script before any requests are made.
+ */
+function setBundleHash(hash: string) {
+  bundleHash = hash;
+}
+
+/**
+ * Tell Rust the function registry is ready. Rust checks the protocol version and
+ * bundle hash before it sends anything, and refuses a stale or foreign init script.
+ * The response holds the code of any functions the app asked to preload.
+ */
+async function notifyInitialized() {
+  const response = await fetch(
+    `/__wbg__/initialized?version=${PROTOCOL_VERSION}&hash=${bundleHash}`,
+    { method: "POST", body: [] }
+  );
+  if (!response.ok) {
+    console.error(`wry-bindgen refused to start: ${await response.text()}`);
+    return;
+  }
+  const preloaded = await response.text();
+  if (preloaded) {
+    loadFunctions(preloaded);
+  }
+}
+
 /**
  * Sends binary data to Rust and receives binary response.
  */
 function sync_request_binary(
   endpoint: string,
   data: ArrayBuffer
+): ArrayBuffer | null {
+  if (ipcEncoding === IpcEncoding.Header) {
+    return sync_request_header(endpoint, data);
+  }
+
+  const xhr = new XMLHttpRequest();
+  xhr.open("POST", endpoint, false);
+  // Sync requests cannot set responseType="arraybuffer". Overriding the charset to
+  // x-user-defined maps every response byte to a single char code we can read back.
+  xhr.overrideMimeType("text/plain; charset=x-user-defined");
+  xhr.send(new Uint8Array(data));
+
+  if (xhr.status === 200 && xhr.responseText) {
+    const responseText = xhr.responseText;
+    const responseBytes = new Uint8Array(responseText.length);
+    for (let i = 0; i < responseText.length; i++) {
+      responseBytes[i] = responseText.charCodeAt(i) & 0xff;
+    }
+    return responseBytes.buffer;
+  }
+  return null;
+}
+
+/**
+ * Fallback for sync_request_binary that sends the payload as a base64 header
+ * and reads a base64 encoded response.
+ */
+function sync_request_header(
+  endpoint: string,
+  data: ArrayBuffer
 ): ArrayBuffer | null {
   const xhr = new XMLHttpRequest();
   xhr.open("POST", endpoint, false);
-  // Note: Cannot set responseType on sync requests - response comes as base64 text
 
-  // Encode as base64 for header (Android workaround)
   const bytes = new Uint8Array(data);
   let binary = "";
   for (let i = 0; i < bytes.length; i++) {
@@ -84,6 +172,23 @@ function evaluate_from_rust_binary(dataBase64: string) {
   }
 }
 
+/**
+ * Entry point for Evaluate messages Rust pushes over a WebSocket.
+ * Returns the Respond message to send back on the socket. Any nested conversation
+ * (callbacks into Rust while handling the operations) still uses sync requests
+ * because JS has to block for the result.
+ *
+ * @param data - Binary message containing the operations
+ */
+function evaluate_from_rust_socket(data: ArrayBuffer): ArrayBuffer {
+  const decoder = new DataDecoder(data);
+  const msgType: MessageType = decoder.takeU8();
+  if (msgType !== MessageType.Evaluate) {
+    throw new Error("Expected an Evaluate message from the socket");
+  }
+  return runEvaluate(decoder);
+}
+
 /**
  * Parse type information from the decoder.
  * Handles both cached and full type definitions.
@@ -127,47 +232,71 @@ function parseTypeInfo(decoder: DataDecoder): CachedTypeInfo {
 }
 
 /**
- * Handle binary response from Rust.
- * May contain nested Evaluate calls (for callbacks).
+ * Encode a Throw message for an operation that threw. Rust panics with the
+ * message and stack instead of waiting for a Respond that never comes.
  */
-function handleBinaryResponse(
-  response: ArrayBuffer | null
-): DataDecoder | null {
-  if (!response || response.byteLength === 0) {
-    return null;
-  }
-
-  const decoder = new DataDecoder(response);
-  const rawMsgType = decoder.takeU8();
-  const msgType: MessageType = rawMsgType;
+function encodeThrow(fnId: number, opIndex: number, error: unknown): ArrayBuffer {
+  const encoder = new DataEncoder();
+  encoder.pushU8(MessageType.Throw);
+  encoder.pushU32(fnId);
+  encoder.pushU32(opIndex);
+  encoder.pushStr(error instanceof Error ? error.message : String(error));
+  encoder.pushStr(error instanceof Error && error.stack ? error.stack : "");
+  return encoder.finalize();
+}
 
-  if (msgType === MessageType.Respond) {
-    // Respond - just return the decoder for further processing
-    return decoder;
-  } else if (msgType === MessageType.Evaluate) {
-    // Evaluate - Rust is calling JS functions (possibly multiple)
+/**
+ * Run every operation in an Evaluate message and encode the Respond message.
+ * If an operation throws, the remaining operations are skipped and a Throw
+ * message is returned instead.
+ * The decoder must be positioned just after the message type.
+ */
+function runEvaluate(decoder: DataDecoder): ArrayBuffer {
+  // Read the reserved placeholder count and push a reservation scope
+  // This ensures nested callback allocations skip these reserved IDs
+  const reservedCount = decoder.takeU32();
+  window.jsHeap.pushReservationScope(reservedCount);
+
+  // Reuse the freed IDs Rust recycled, after reserving this message's placeholders
+  const recycled: number[] = [];
+  const recycledCount = decoder.takeU32();
+  for (let i = 0; i < recycledCount; i++) {
+    recycled.push(decoder.takeU64());
+  }
+  window.jsHeap.recycle(recycled);
+
+  // Debug builds of Rust send a summary of their free list to check ours matches
+  if (decoder.takeU32() !== 0) {
+    const error = window.jsHeap.checkFreeList(
+      decoder.takeU32(),
+      decoder.takeU64(),
+      decoder.takeU32()
+    );
+    if (error !== null) {
+      window.jsHeap.popReservationScope();
+      return encodeThrow(0xffffffff, 0xffffffff, new Error(error));
+    }
+  }
 
-    // Read the reserved placeholder count and push a reservation scope
-    // This ensures nested callback allocations skip these reserved IDs
-    const reservedCount = decoder.takeU32();
-    window.jsHeap.pushReservationScope(reservedCount);
+  const encoder = new DataEncoder();
+  encoder.pushU8(MessageType.Respond);
 
-    const encoder = new DataEncoder();
-    encoder.pushU8(MessageType.Respond);
+  // Push a single borrow frame for this entire Evaluate message
+  // This frame persists across all operations and nested calls
+  window.jsHeap.pushBorrowFrame();
 
-    // Push a single borrow frame for this entire Evaluate message
-    // This frame persists across all operations and nested calls
-    window.jsHeap.pushBorrowFrame();
+  // Process all operations
+  for (let opIndex = 0; decoder.hasMoreU32(); opIndex++) {
+    const fnId = decoder.takeU32();
 
-    // Process all operations
-    while (decoder.hasMoreU32()) {
-      const fnId = decoder.takeU32();
-      // Parse type information (cached or full)
-      const typeInfo = parseTypeInfo(decoder);
+    // Anything that fails from here on is reported to Rust as a Throw, so the
+    // Rust call site panics instead of waiting for a response forever
+    try {
+      // Use the registered signature, or parse type information (cached or full)
+      const typeInfo = getFunctionTypes()[fnId] ?? parseTypeInfo(decoder);
 
-      // Get the raw JS function
-      const functionRegistry = getFunctionRegistry();
-      const jsFunction = functionRegistry[fnId];
+      // Get the raw JS function, loading its code on first use
+      const jsFunction = getFunction(fnId);
       if (!jsFunction) {
         throw new Error("Unknown function ID in response: " + fnId);
       }
@@ -187,17 +316,48 @@ function handleBinaryResponse(
         // Encode the result using the return type
         typeInfo.returnType.encode(encoder, result);
       }
+    } catch (error) {
+      window.jsHeap.popBorrowFrame();
+      window.jsHeap.popReservationScope();
+      return encodeThrow(fnId, opIndex, error);
     }
+  }
 
-    // Pop the borrow frame after all operations complete
-    window.jsHeap.popBorrowFrame();
+  // Pop the borrow frame after all operations complete
+  window.jsHeap.popBorrowFrame();
 
-    // Pop the reservation scope
-    window.jsHeap.popReservationScope();
+  // Pop the reservation scope
+  window.jsHeap.popReservationScope();
 
+  return encoder.finalize();
+}
+
+/**
+ * Handle binary response from Rust.
+ * May contain nested Evaluate calls (for callbacks).
+ */
+function handleBinaryResponse(
+  response: ArrayBuffer | null
+): DataDecoder | null {
+  if (!response || response.byteLength === 0) {
+    return null;
+  }
+
+  const decoder = new DataDecoder(response);
+  const rawMsgType = decoder.takeU8();
+  const msgType: MessageType = rawMsgType;
+
+  if (msgType === MessageType.Respond) {
+    // Respond - just return the decoder for further processing
+    return decoder;
+  } else if (msgType === MessageType.Throw) {
+    // Throw - the Rust call panicked or threw, rethrow the value it sent
+    throw window.jsHeap.get(decoder.takeU64());
+  } else if (msgType === MessageType.Evaluate) {
+    // Evaluate - Rust is calling JS functions (possibly multiple)
     const nextResponse = sync_request_binary(
       `/__wbg__/handler`,
-      encoder.finalize()
+      runEvaluate(decoder)
     );
     return handleBinaryResponse(nextResponse);
   }
@@ -211,8 +371,13 @@ function handleBinaryResponse(
 
 export {
   evaluate_from_rust_binary,
+  evaluate_from_rust_socket,
   handleBinaryResponse,
   sync_request_binary,
+  setIpcEncoding,
+  setBundleHash,
+  notifyInitialized,
+  IpcEncoding,
   MessageType,
   DROP_NATIVE_REF_FN_ID,
   CALL_EXPORT_FN_ID,
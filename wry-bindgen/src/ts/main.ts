@@ -1,19 +1,41 @@
 import { JSHeap } from "./heap.ts";
 import "./ipc.ts";
-import { evaluate_from_rust_binary } from "./ipc.ts";
-import { RawJsFunction, setFunctionRegistry } from "./function_registry.ts";
+import {
+  evaluate_from_rust_binary,
+  IpcEncoding,
+  notifyInitialized,
+  setBundleHash,
+  setIpcEncoding,
+} from "./ipc.ts";
+import { setFunctionRegistry } from "./function_registry.ts";
 import { rustExports } from "./rust_exports.ts";
+import { connectWebSocket } from "./socket.ts";
 
 window.setFunctionRegistry = setFunctionRegistry;
 window.evaluate_from_rust_binary = evaluate_from_rust_binary;
+window.setIpcEncoding = setIpcEncoding;
+window.setBundleHash = setBundleHash;
+window.notifyInitialized = notifyInitialized;
+window.connectWebSocket = connectWebSocket;
 window.jsHeap = new JSHeap();
 window.rustExports = rustExports;
+window.__wbg_exports = {};
 
 declare global {
   interface Window {
-    setFunctionRegistry: (registry: RawJsFunction[]) => void;
+    setFunctionRegistry: (
+      count: number,
+      modules: Record<string, unknown>,
+      types: number[][],
+      functionTypeIds: (number | null)[]
+    ) => void;
     evaluate_from_rust_binary: (dataBase64: string) => unknown;
+    setIpcEncoding: (encoding: IpcEncoding) => void;
+    setBundleHash: (hash: string) => void;
+    notifyInitialized: () => Promise<void>;
+    connectWebSocket: (path: string) => WebSocket;
     jsHeap: JSHeap;
     rustExports: typeof rustExports;
+    __wbg_exports: Record<string, (...args: any[]) => any>;
   }
 }
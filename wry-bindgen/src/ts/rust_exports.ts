@@ -1,5 +1,7 @@
 import { DataEncoder } from "./encoding";
+import { CachedTypeInfo, parseSignature } from "./function_registry";
 import { handleBinaryResponse, MessageType, sync_request_binary, CALL_EXPORT_FN_ID } from "./ipc";
+import { ResultType } from "./types";
 
 /**
  * FinalizationRegistry to notify Rust when exported object wrappers are GC'd.
@@ -41,10 +43,13 @@ function callExport(exportName: string, ...args: any[]): any {
     }
   }
 
-  const response = sync_request_binary(`/__wbg__/handler`, encoder.finalize());
-  const decoder = handleBinaryResponse(response);
-
-  window.jsHeap.popBorrowFrame();
+  let decoder;
+  try {
+    const response = sync_request_binary(`/__wbg__/handler`, encoder.finalize());
+    decoder = handleBinaryResponse(response);
+  } finally {
+    window.jsHeap.popBorrowFrame();
+  }
 
   // If we have response data, try to decode it
   // For now, try to decode as i32 if there's u32 data available
@@ -55,15 +60,84 @@ function callExport(exportName: string, ...args: any[]): any {
   return undefined;
 }
 
+/**
+ * Call an exported Rust function by name, encoding the arguments and decoding
+ * the result with its registered types. Methods pass the handle of the object
+ * they are called on, which is sent before the arguments.
+ */
+function callTypedExport(exportName: string, typeInfo: CachedTypeInfo, args: any[], handle?: number): any {
+  // Push a borrow frame before encoding args - nested calls won't clear our borrowed refs
+  window.jsHeap.pushBorrowFrame();
+
+  const encoder = new DataEncoder();
+  encoder.pushU8(MessageType.Evaluate);
+  encoder.pushU32(CALL_EXPORT_FN_ID);
+  encoder.pushStr(exportName);
+  if (handle !== undefined) {
+    encoder.pushU32(handle);
+  }
+  for (let i = 0; i < typeInfo.paramTypes.length; i++) {
+    typeInfo.paramTypes[i].encode(encoder, args[i]);
+  }
+
+  let result;
+  try {
+    const response = sync_request_binary(`/__wbg__/handler`, encoder.finalize());
+    result = handleBinaryResponse(response)!;
+  } finally {
+    window.jsHeap.popBorrowFrame();
+  }
+
+  const decoded = typeInfo.returnType.decode(result);
+  if (result && !result.isEmpty()) {
+    throw new Error(`Unprocessed data remaining after calling ${exportName}`);
+  }
+  // Exports returning Result throw the Err value like in wasm-bindgen
+  if (typeInfo.returnType instanceof ResultType) {
+    if ("err" in decoded) {
+      throw decoded.err;
+    }
+    return decoded.ok;
+  }
+  return decoded;
+}
+
+/**
+ * Create the JS function for an exported Rust function from its name and the
+ * signature in the registry.
+ */
+function exportFunction(exportName: string, signature: number[]): (...args: any[]) => any {
+  const typeInfo = parseSignature(signature);
+  return (...args: any[]) => callTypedExport(exportName, typeInfo, args);
+}
+
+/**
+ * Create the JS function for a member of an exported Rust class from its export
+ * name and the signature in the registry. Members called on an object take the
+ * object's handle as their first argument.
+ */
+function exportMethod(exportName: string, signature: number[], hasHandle: boolean): (...args: any[]) => any {
+  const typeInfo = parseSignature(signature);
+  if (hasHandle) {
+    return (handle: number, ...args: any[]) => callTypedExport(exportName, typeInfo, args, handle);
+  }
+  return (...args: any[]) => callTypedExport(exportName, typeInfo, args);
+}
+
 /**
  * Create a JavaScript wrapper object for a Rust exported struct.
  * Uses the generated class from JsClassSpec if available, otherwise falls back to Proxy.
+ * Wrappers for objects Rust only lends to a call don't own them and are never dropped.
  */
-function createWrapper(handle: number, className: string): object {
+function createWrapper(handle: number, className: string, owned: boolean = true): object {
   // Try to use the generated class if available
   const ClassConstructor = (window as any)[className];
   if (ClassConstructor && typeof ClassConstructor.__wrap === 'function') {
-    return ClassConstructor.__wrap(handle);
+    const obj = ClassConstructor.__wrap(handle);
+    if (!owned) {
+      exportRegistry.unregister(obj);
+    }
+    return obj;
   }
 
   // Fallback: Create wrapper object with the handle stored (legacy Proxy approach)
@@ -93,11 +167,32 @@ function createWrapper(handle: number, className: string): object {
   });
 
   // Register for GC notification
-  exportRegistry.register(proxy, { handle, className });
+  if (owned) {
+    exportRegistry.register(proxy, { handle, className }, proxy);
+  }
 
   return proxy;
 }
 
+/**
+ * Get the handle of a Rust exported struct passed back to Rust. Passing it by
+ * value moves the object to Rust, so the wrapper no longer owns it.
+ */
+function takeHandle(obj: any, className: string, borrowed: boolean): number {
+  if (obj === null || typeof obj !== "object" || obj.__className !== className) {
+    throw new Error(`Expected an instance of ${className}`);
+  }
+  const handle = obj.__handle;
+  if (handle === 0) {
+    throw new Error(`${className} was already moved to Rust or freed`);
+  }
+  if (!borrowed) {
+    exportRegistry.unregister(obj);
+    obj.__handle = 0;
+  }
+  return handle;
+}
+
 // Expose callExport and exportRegistry as window globals for generated classes to use
 (window as any).__wryCallExport = callExport;
 (window as any).__wryExportRegistry = exportRegistry;
@@ -108,6 +203,8 @@ function createWrapper(handle: number, className: string): object {
 const rustExports = {
   createWrapper,
   callExport,
+  exportFunction,
+  exportMethod,
 };
 
-export { rustExports, createWrapper, callExport };
+export { rustExports, createWrapper, takeHandle, callExport, callTypedExport, exportFunction, exportMethod };
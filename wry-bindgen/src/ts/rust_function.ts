@@ -1,6 +1,6 @@
 import { DataEncoder } from "./encoding";
 import { handleBinaryResponse, MessageType, sync_request_binary, DROP_NATIVE_REF_FN_ID } from "./ipc";
-import { TypeClass } from "./types";
+import { ResultType, TypeClass } from "./types";
 
 /**
  * FinalizationRegistry to notify Rust when RustFunction wrappers are GC'd.
@@ -48,18 +48,28 @@ class RustFunction {
       this.paramTypes[i].encode(encoder, args[i]);
     }
 
-    // Send to Rust and get response (Rust may call back to JS during this)
-    const response = sync_request_binary(`/__wbg__/handler`, encoder.finalize());
-    const result = handleBinaryResponse(response)!;
-
-    // Pop the borrow frame - clears borrowed refs from this call
-    window.jsHeap.popBorrowFrame();
+    let result;
+    try {
+      // Send to Rust and get response (Rust may call back to JS during this)
+      const response = sync_request_binary(`/__wbg__/handler`, encoder.finalize());
+      result = handleBinaryResponse(response)!;
+    } finally {
+      // Pop the borrow frame - clears borrowed refs from this call
+      window.jsHeap.popBorrowFrame();
+    }
 
     // Decode return value
     const decoded = this.returnType.decode(result);
     if (result && !result.isEmpty()) {
       throw new Error("Unprocessed data remaining after RustFunction call");
     }
+    // Callbacks returning Result throw the Err value like in wasm-bindgen
+    if (this.returnType instanceof ResultType) {
+      if ("err" in decoded) {
+        throw decoded.err;
+      }
+      return decoded.ok;
+    }
     return decoded;
   }
 }
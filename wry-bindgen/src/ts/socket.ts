@@ -0,0 +1,21 @@
+import { evaluate_from_rust_socket } from "./ipc";
+
+/**
+ * Connect to a WebSocket that Rust pushes Evaluate messages over.
+ * Used instead of evaluate_script when the page is served over plain HTTP
+ * (e.g. opened in a desktop browser). The Respond for each pushed message is
+ * sent back over the same socket.
+ *
+ * @param path - Path of the WebSocket endpoint on the current host
+ */
+function connectWebSocket(path: string): WebSocket {
+  const protocol = location.protocol === "https:" ? "wss:" : "ws:";
+  const socket = new WebSocket(`${protocol}//${location.host}${path}`);
+  socket.binaryType = "arraybuffer";
+  socket.onmessage = (event: MessageEvent<ArrayBuffer>) => {
+    socket.send(evaluate_from_rust_socket(event.data));
+  };
+  return socket;
+}
+
+export { connectWebSocket };
@@ -1,5 +1,6 @@
 import { DataEncoder, DataDecoder } from "./encoding";
 import { RustFunction } from "./rust_function";
+import { createWrapper, takeHandle } from "./rust_exports";
 
 /**
  * Type tags for the binary type definition protocol.
@@ -31,6 +32,7 @@ enum TypeTag {
   BorrowedRef = 22,
   U8Clamped = 23,
   StringEnum = 24,
+  RustObject = 25,
 }
 
 /**
@@ -88,6 +90,12 @@ class BorrowedRefType implements TypeClass {
   }
 }
 
+/**
+ * Sent in place of a string's length when Rust interned the string, followed by
+ * the heap id of the JS string. Must match INTERNED_STR in encode.rs.
+ */
+const INTERNED_STR = 0xffffffff;
+
 /**
  * Type class for string values with encoding/decoding methods
  */
@@ -97,6 +105,10 @@ class StringType implements TypeClass {
   }
 
   decode(decoder: DataDecoder): string {
+    if (decoder.peekU32() === INTERNED_STR) {
+      decoder.takeU32();
+      return window.jsHeap.get(decoder.takeU64()) as string;
+    }
     return decoder.takeStr();
   }
 }
@@ -124,6 +136,31 @@ class StringEnumType implements TypeClass {
   }
 }
 
+/**
+ * Type class for exported Rust structs, passed as the handle of the object in
+ * Rust's object store. Rust marks the objects it only lends to a call, and
+ * `borrowed` marks objects Rust only borrows from JS.
+ */
+class RustObjectType implements TypeClass {
+  private className: string;
+  private borrowed: boolean;
+
+  constructor(className: string, borrowed: boolean) {
+    this.className = className;
+    this.borrowed = borrowed;
+  }
+
+  encode(encoder: DataEncoder, value: any): void {
+    encoder.pushU32(takeHandle(value, this.className, this.borrowed));
+  }
+
+  decode(decoder: DataDecoder): object {
+    const handle = decoder.takeU32();
+    const owned = decoder.takeU8() !== 0;
+    return createWrapper(handle, this.className, owned);
+  }
+}
+
 /**
  * Type class for Rust callbacks with encoding/decoding methods
  */
@@ -495,6 +532,19 @@ function parseTypeDef(bytes: Uint8Array, offset: { value: number }): TypeClass {
 
       return new StringEnumType(lookupArray);
     }
+    case TypeTag.RustObject: {
+      const borrowed = bytes[offset.value++] !== 0;
+      // Read class name length (u32 little-endian) and bytes
+      const len =
+        bytes[offset.value] |
+        (bytes[offset.value + 1] << 8) |
+        (bytes[offset.value + 2] << 16) |
+        (bytes[offset.value + 3] << 24);
+      offset.value += 4;
+      const className = new TextDecoder().decode(bytes.subarray(offset.value, offset.value + len));
+      offset.value += len;
+      return new RustObjectType(className, borrowed);
+    }
     default:
       throw new Error(`Unknown TypeTag: ${tag}`);
   }
@@ -514,6 +564,7 @@ export {
   StringType,
   StringEnumType,
   ResultType,
+  RustObjectType,
   U8ClampedType,
   parseTypeDef,
 };
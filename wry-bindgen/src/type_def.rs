@@ -0,0 +1,190 @@
+//! Type definitions parsed back out of the binary protocol.
+//!
+//! This is the Rust side of `parseTypeDef` in `ts/types.ts`, used by code that
+//! needs to read messages the way JS does.
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use crate::encode::{TYPE_CACHED, TYPE_FULL, TypeTag};
+use crate::function_registry::FUNCTION_REGISTRY;
+use crate::ipc::{DecodeError, DecodedData};
+
+/// A parsed function signature.
+#[derive(Debug)]
+pub(crate) struct FunctionType {
+    pub(crate) params: Vec<TypeDef>,
+    pub(crate) ret: TypeDef,
+}
+
+impl FunctionType {
+    pub(crate) fn parse(data: &mut DecodedData) -> Result<Self, DecodeError> {
+        let param_count = data.take_u8()?;
+        let params = (0..param_count)
+            .map(|_| TypeDef::parse(data))
+            .collect::<Result<_, _>>()?;
+        let ret = TypeDef::parse(data)?;
+        Ok(Self { params, ret })
+    }
+}
+
+/// A parsed type definition, the Rust equivalent of a JS `TypeClass`.
+#[derive(Debug)]
+pub(crate) enum TypeDef {
+    Null,
+    Bool,
+    Number(TypeTag),
+    String,
+    HeapRef,
+    BorrowedRef,
+    Callback(Arc<FunctionType>),
+    Option(Box<TypeDef>),
+    Result(Box<TypeDef>, Box<TypeDef>),
+    Array(Box<TypeDef>),
+    U8Clamped,
+    StringEnum(Vec<String>),
+    /// An exported Rust struct wrapped in JS as `class`
+    RustObject {
+        borrowed: bool,
+        class: String,
+    },
+}
+
+impl TypeDef {
+    /// Parse a type definition from the u8 buffer, like `parseTypeDef` in JS.
+    pub(crate) fn parse(data: &mut DecodedData) -> Result<Self, DecodeError> {
+        let tag = data.take_u8()?;
+        let tag = TypeTag::from_u8(tag)
+            .ok_or_else(|| DecodeError::Custom(format!("Unknown TypeTag: {tag}")))?;
+        Ok(match tag {
+            TypeTag::Null => TypeDef::Null,
+            TypeTag::Bool => TypeDef::Bool,
+            TypeTag::String => TypeDef::String,
+            TypeTag::HeapRef => TypeDef::HeapRef,
+            TypeTag::BorrowedRef => TypeDef::BorrowedRef,
+            TypeTag::Callback => TypeDef::Callback(Arc::new(FunctionType::parse(data)?)),
+            TypeTag::Option => TypeDef::Option(Box::new(Self::parse(data)?)),
+            TypeTag::Result => {
+                TypeDef::Result(Box::new(Self::parse(data)?), Box::new(Self::parse(data)?))
+            }
+            TypeTag::Array => TypeDef::Array(Box::new(Self::parse(data)?)),
+            TypeTag::U8Clamped => TypeDef::U8Clamped,
+            TypeTag::StringEnum => {
+                let variant_count = data.take_u8()?;
+                let mut variants = Vec::with_capacity(variant_count as usize);
+                for _ in 0..variant_count {
+                    let mut len = [0; 4];
+                    for byte in &mut len {
+                        *byte = data.take_u8()?;
+                    }
+                    let bytes = (0..u32::from_le_bytes(len))
+                        .map(|_| data.take_u8())
+                        .collect::<Result<Vec<_>, _>>()?;
+                    variants.push(String::from_utf8_lossy(&bytes).into_owned());
+                }
+                TypeDef::StringEnum(variants)
+            }
+            TypeTag::RustObject => {
+                let borrowed = data.take_u8()? != 0;
+                let mut len = [0; 4];
+                for byte in &mut len {
+                    *byte = data.take_u8()?;
+                }
+                let bytes = (0..u32::from_le_bytes(len))
+                    .map(|_| data.take_u8())
+                    .collect::<Result<Vec<_>, _>>()?;
+                TypeDef::RustObject {
+                    borrowed,
+                    class: String::from_utf8_lossy(&bytes).into_owned(),
+                }
+            }
+            number => TypeDef::Number(number),
+        })
+    }
+}
+
+pub(crate) fn decode_number(tag: TypeTag, data: &mut DecodedData) -> Result<f64, DecodeError> {
+    Ok(match tag {
+        TypeTag::U8 => data.take_u8()? as f64,
+        TypeTag::U16 => data.take_u16()? as f64,
+        TypeTag::U32 => data.take_u32()? as f64,
+        TypeTag::U64 | TypeTag::Usize => data.take_u64()? as f64,
+        TypeTag::U128 => data.take_u128()? as f64,
+        TypeTag::I8 => data.take_u8()? as i8 as f64,
+        TypeTag::I16 => data.take_u16()? as i16 as f64,
+        TypeTag::I32 => data.take_u32()? as i32 as f64,
+        TypeTag::I64 | TypeTag::Isize => data.take_u64()? as i64 as f64,
+        TypeTag::I128 => data.take_u128()? as i128 as f64,
+        TypeTag::F32 => f32::from_bits(data.take_u32()?) as f64,
+        TypeTag::F64 => f64::from_bits(data.take_u64()?),
+        tag => unreachable!("{tag:?} is not a number type"),
+    })
+}
+
+#[cfg(feature = "fake")]
+pub(crate) fn encode_number(tag: TypeTag, value: f64, encoder: &mut crate::ipc::EncodedData) {
+    match tag {
+        TypeTag::U8 => encoder.push_u8(value as u8),
+        TypeTag::U16 => encoder.push_u16(value as u16),
+        TypeTag::U32 => encoder.push_u32(value as u32),
+        TypeTag::U64 | TypeTag::Usize => encoder.push_u64(value as u64),
+        TypeTag::U128 => encoder.push_u128(value as u128),
+        TypeTag::I8 => encoder.push_u8(value as i8 as u8),
+        TypeTag::I16 => encoder.push_u16(value as i16 as u16),
+        TypeTag::I32 => encoder.push_u32(value as i32 as u32),
+        TypeTag::I64 | TypeTag::Isize => encoder.push_u64(value as i64 as u64),
+        TypeTag::I128 => encoder.push_u128(value as i128 as u128),
+        TypeTag::F32 => encoder.push_u32((value as f32).to_bits()),
+        TypeTag::F64 => encoder.push_u64(value.to_bits()),
+        tag => unreachable!("{tag:?} is not a number type"),
+    }
+}
+
+/// Function types by type id, like the JS type cache.
+#[derive(Default)]
+pub(crate) struct TypeCache {
+    types: BTreeMap<u32, Arc<FunctionType>>,
+    /// Signatures from the function registry by their type id
+    registered: BTreeMap<u32, Arc<FunctionType>>,
+}
+
+impl TypeCache {
+    /// Get the type of a call to `fn_id`, like `runEvaluate` in JS. Functions with a
+    /// signature in the registry don't send type information, other calls send
+    /// cached or full type information.
+    pub(crate) fn parse_type_info(
+        &mut self,
+        fn_id: u32,
+        data: &mut DecodedData,
+    ) -> Result<Arc<FunctionType>, DecodeError> {
+        if let Some((id, signature)) = FUNCTION_REGISTRY.function_type(fn_id) {
+            if let Some(ty) = self.registered.get(&id) {
+                return Ok(ty.clone());
+            }
+            let ty = Arc::new(FunctionType::parse(&mut DecodedData::from_u8s(signature))?);
+            self.registered.insert(id, ty.clone());
+            return Ok(ty);
+        }
+        match data.take_u8()? {
+            TYPE_CACHED => {
+                let id = data.take_u32()?;
+                self.types
+                    .get(&id)
+                    .cloned()
+                    .ok_or_else(|| DecodeError::Custom(format!("Unknown cached type ID: {id}")))
+            }
+            TYPE_FULL => {
+                let id = data.take_u32()?;
+                let ty = Arc::new(FunctionType::parse(data)?);
+                self.types.insert(id, ty.clone());
+                Ok(ty)
+            }
+            marker => Err(DecodeError::Custom(format!(
+                "Unknown type marker: {marker}"
+            ))),
+        }
+    }
+}
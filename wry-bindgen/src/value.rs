@@ -36,9 +36,14 @@ pub(crate) const JSIDX_RESERVED: u64 = JSIDX_OFFSET + 4;
 ///
 /// Unlike wasm-bindgen which runs in a single-threaded Wasm environment,
 /// this implementation uses the IPC protocol to communicate with JS.
+///
+/// Values on the JS heap belong to the page that created them. If the webview
+/// reloads, using a value from before the reload panics.
 pub struct JsValue {
     #[doc(hidden)]
     pub idx: u64,
+    /// The page load the heap value belongs to
+    generation: u32,
 }
 
 impl JsValue {
@@ -57,7 +62,7 @@ impl JsValue {
     /// Create a new JsValue from an index (const fn for static values).
     #[inline]
     const fn _new(idx: u64) -> JsValue {
-        JsValue { idx }
+        JsValue { idx, generation: 0 }
     }
 
     /// Create a new JsValue from a heap ID.
@@ -65,7 +70,43 @@ impl JsValue {
     /// This is called internally when decoding a value from JS.
     #[inline]
     pub(crate) fn from_id(id: u64) -> Self {
-        Self { idx: id }
+        Self {
+            idx: id,
+            generation: crate::batch::current_generation(),
+        }
+    }
+
+    /// Create a new JsValue from a heap ID allocated in the given page load.
+    ///
+    /// Use this instead of [`JsValue::from_id`] while the runtime is borrowed.
+    #[inline]
+    pub(crate) fn from_heap_id(id: u64, generation: u32) -> Self {
+        Self {
+            idx: id,
+            generation,
+        }
+    }
+
+    /// Get the heap ID to send to JS.
+    ///
+    /// Panics if the value was created before the webview reloaded, since the
+    /// ID now refers to nothing or to a different value on the new page.
+    #[inline]
+    #[track_caller]
+    pub(crate) fn js_id(&self) -> u64 {
+        if self.idx >= JSIDX_RESERVED && crate::batch::current_generation() != self.generation {
+            panic!(
+                "Used a JsValue or Closure from before the webview reloaded. Values from the old page \
+                 are invalid, create them again in a `wry_bindgen::runtime::on_reload` callback"
+            );
+        }
+        self.idx
+    }
+
+    /// The page load the heap value belongs to.
+    #[inline]
+    pub(crate) fn generation(&self) -> u32 {
+        self.generation
     }
 
     /// Get the heap ID for this value.
@@ -119,11 +160,13 @@ impl JsValue {
 
     /// Creates a JS string from a Rust string.
     #[allow(clippy::should_implement_trait)]
+    #[track_caller]
     pub fn from_str(s: &str) -> JsValue {
         s.into()
     }
 
     /// Creates a JS number from an f64.
+    #[track_caller]
     pub fn from_f64(n: f64) -> JsValue {
         n.into()
     }
@@ -131,14 +174,18 @@ impl JsValue {
 
 impl Clone for JsValue {
     #[inline]
+    #[track_caller]
     fn clone(&self) -> JsValue {
         // Reserved values don't need cloning - they're constants
         if self.idx < JSIDX_RESERVED {
-            return JsValue { idx: self.idx };
+            return JsValue {
+                idx: self.idx,
+                generation: self.generation,
+            };
         }
 
         // Clone the value on the JS heap
-        crate::js_helpers::js_clone_heap_ref(self.idx)
+        crate::js_helpers::js_clone_heap_ref(self.js_id())
     }
 }
 
@@ -151,7 +198,7 @@ impl Drop for JsValue {
         }
 
         // Drop the value on the JS heap
-        crate::batch::queue_js_drop(self.idx);
+        crate::batch::queue_js_drop(self.idx, self.generation);
     }
 }
 
@@ -309,7 +356,7 @@ impl fmt::Debug for JsValue {
 
 impl PartialEq for JsValue {
     fn eq(&self, other: &Self) -> bool {
-        self.idx == other.idx
+        self.idx == other.idx && self.generation == other.generation
     }
 }
 
@@ -318,6 +365,7 @@ impl Eq for JsValue {}
 impl core::hash::Hash for JsValue {
     fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
         self.idx.hash(state);
+        self.generation.hash(state);
     }
 }
 
@@ -10,19 +10,102 @@ use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 use base64::Engine;
 use core::cell::RefCell;
+use core::fmt;
 use core::future::poll_fn;
 use core::pin::{Pin, pin};
+use core::time::Duration;
 use futures_util::FutureExt;
 use std::collections::HashMap;
 use std::sync::Arc;
 
 use http::Response;
+use once_cell::sync::Lazy;
 
 use crate::batch::{Runtime, in_runtime};
 use crate::function_registry::FUNCTION_REGISTRY;
 use crate::ipc::{DecodedVariant, IPCMessage, MessageType, decode_data};
+use crate::record::{Direction, Recorder};
 use crate::runtime::{AppEventVariant, IPCSenders, WryBindgenEvent, WryIPC, handle_callbacks};
 
+/// The version of the binary protocol spoken between Rust and JS.
+///
+/// This must match `PROTOCOL_VERSION` in `ts/ipc.ts`. Bump both whenever the
+/// encoding of messages or type definitions changes.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Why a webview's init script was refused in the `/__wbg__/initialized` handshake.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProtocolError {
+    /// The init script did not send a protocol version or bundle hash
+    MissingHandshake,
+    /// The init script speaks a different version of the binary protocol
+    VersionMismatch { expected: u32, found: u32 },
+    /// The init script is not the one this app serves
+    BundleMismatch { expected: String, found: String },
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolError::MissingHandshake => write!(
+                f,
+                "The page loaded an init.js from an older version of wry-bindgen that does not send a protocol version. \
+                 It was probably cached; clear the webview cache and reload the page"
+            ),
+            ProtocolError::VersionMismatch { expected, found } => write!(
+                f,
+                "The page loaded an init.js for protocol version {found}, but this app speaks protocol version {expected}. \
+                 Make sure the page loads /__wbg__/init.js from the app instead of a cached or bundled copy"
+            ),
+            ProtocolError::BundleMismatch { expected, found } => write!(
+                f,
+                "The page loaded an init.js with bundle hash {found}, but this app serves bundle hash {expected}. \
+                 Make sure the page loads /__wbg__/init.js from the app instead of a cached or bundled copy"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+/// Check the protocol version and bundle hash JS sent with `/__wbg__/initialized`.
+fn check_handshake(query: Option<&str>) -> Result<(), ProtocolError> {
+    let mut version = None;
+    let mut hash = None;
+    for (key, value) in query
+        .unwrap_or_default()
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+    {
+        match key {
+            "version" => version = Some(value),
+            "hash" => hash = Some(value),
+            _ => {}
+        }
+    }
+    let (Some(version), Some(hash)) = (version, hash) else {
+        return Err(ProtocolError::MissingHandshake);
+    };
+
+    let found = version
+        .parse()
+        .map_err(|_| ProtocolError::MissingHandshake)?;
+    if found != PROTOCOL_VERSION {
+        return Err(ProtocolError::VersionMismatch {
+            expected: PROTOCOL_VERSION,
+            found,
+        });
+    }
+    let expected = &INIT_SCRIPT.hash;
+    if hash != expected {
+        return Err(ProtocolError::BundleMismatch {
+            expected: expected.clone(),
+            found: hash.to_string(),
+        });
+    }
+    Ok(())
+}
+
 pub trait ImplWryBindgenResponder {
     fn respond(self: Box<Self>, response: Response<Vec<u8>>);
 }
@@ -68,12 +151,98 @@ impl WryBindgenResponder {
     }
 }
 
-/// Decode request data from the dioxus-data header.
-fn decode_request_data(request: &http::Request<Vec<u8>>) -> Option<IPCMessage> {
+/// How Rust delivers messages to JS when JS is not blocked on a request.
+///
+/// Messages that answer a pending `/__wbg__/handler` request are always sent as the
+/// response to that request. Everything else is pushed through the transport.
+pub trait Transport {
+    /// Push an Evaluate message to JS.
+    fn push(&mut self, message: &[u8]);
+}
+
+/// The default transport which pushes messages with `WebView::evaluate_script`.
+pub struct EvaluateScriptTransport<F> {
+    evaluate_script: F,
+}
+
+impl<F: FnMut(&str)> EvaluateScriptTransport<F> {
+    /// Create a transport from a function that evaluates scripts in the webview.
+    pub fn new(evaluate_script: F) -> Self {
+        Self { evaluate_script }
+    }
+}
+
+impl<F: FnMut(&str)> Transport for EvaluateScriptTransport<F> {
+    fn push(&mut self, message: &[u8]) {
+        // Encode the binary data as base64 and pass to JS
+        // JS will iterate over operations in the buffer
+        let engine = base64::engine::general_purpose::STANDARD;
+        let data_base64 = engine.encode(message);
+        let code = format!("window.evaluate_from_rust_binary(\"{data_base64}\")");
+        (self.evaluate_script)(&code);
+    }
+}
+
+/// How IPC payloads are carried between JS and the protocol handler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IpcEncoding {
+    /// Raw bytes in the request body and the response body.
+    Binary,
+    /// Base64 in the dioxus-data header and a base64 text response body.
+    /// Fallback for platforms whose custom protocol handlers drop request bodies.
+    Header,
+}
+
+impl IpcEncoding {
+    /// The encoding JS is told to use on the current platform.
+    const fn for_platform() -> Self {
+        if cfg!(target_os = "android") {
+            IpcEncoding::Header
+        } else {
+            IpcEncoding::Binary
+        }
+    }
+
+    /// The name of the matching `IpcEncoding` variant in ipc.ts.
+    const fn js_name(self) -> &'static str {
+        match self {
+            IpcEncoding::Binary => "binary",
+            IpcEncoding::Header => "header",
+        }
+    }
+
+    /// Build the response for a message that arrived with this encoding.
+    fn response(self, body: Vec<u8>) -> Response<Vec<u8>> {
+        match self {
+            IpcEncoding::Binary => http::Response::builder()
+                .status(200)
+                .header("Content-Type", "application/octet-stream")
+                .body(body)
+                .expect("Failed to build response"),
+            IpcEncoding::Header => {
+                // Sync XMLHttpRequest cannot use responseType="arraybuffer", so the
+                // header fallback reads the response as base64 text
+                let engine = base64::engine::general_purpose::STANDARD;
+                http::Response::builder()
+                    .status(200)
+                    .header("Content-Type", "text/plain")
+                    .body(engine.encode(&body).into_bytes())
+                    .expect("Failed to build response")
+            }
+        }
+    }
+}
+
+/// Decode request data from either the dioxus-data header or the request body.
+fn decode_request_data(request: &http::Request<Vec<u8>>) -> Option<(IPCMessage, IpcEncoding)> {
     if let Some(header_value) = request.headers().get("dioxus-data") {
-        return decode_data(header_value.as_bytes());
+        return decode_data(header_value.as_bytes()).map(|msg| (msg, IpcEncoding::Header));
+    }
+    let body = request.body();
+    if body.is_empty() {
+        return None;
     }
-    None
+    Some((IPCMessage::new(body.clone()), IpcEncoding::Binary))
 }
 
 /// Tracks the loading state of the webview.
@@ -82,6 +251,9 @@ enum WebviewLoadingState {
     Pending { queued: Vec<IPCMessage> },
     /// Webview is loaded and ready.
     Loaded,
+    /// Webview reloaded and the app thread has not reset its runtime yet.
+    /// Messages from Rust are meant for the old page and are dropped.
+    Reloading,
 }
 
 impl Default for WebviewLoadingState {
@@ -92,7 +264,8 @@ impl Default for WebviewLoadingState {
 
 /// Shared state for managing async protocol responses.
 struct WebviewState {
-    ongoing_request: Option<WryBindgenResponder>,
+    /// The request JS is blocked on, and the encoding its response must use
+    ongoing_request: Option<(WryBindgenResponder, IpcEncoding)>,
     /// How many responses we are waiting for from JS
     pending_js_evaluates: usize,
     /// How many responses JS is waiting for from us
@@ -101,33 +274,33 @@ struct WebviewState {
     sender: IPCSenders,
     // The state of the webview. Either loading (with queued messages) or loaded.
     loading_state: WebviewLoadingState,
-    // A function that evaluates scripts in the webview
-    evaluate_script: Box<dyn FnMut(&str)>,
+    // The transport used to push messages to the webview
+    transport: Box<dyn Transport>,
 }
 
 impl WebviewState {
     /// Create a new webview state.
-    fn new(sender: IPCSenders, evaluate_script: impl FnMut(&str) + 'static) -> Self {
+    fn new(sender: IPCSenders, transport: impl Transport + 'static) -> Self {
         Self {
             ongoing_request: None,
             pending_js_evaluates: 0,
             pending_rust_evaluates: 0,
             sender,
             loading_state: WebviewLoadingState::default(),
-            evaluate_script: Box::new(evaluate_script),
+            transport: Box::new(transport),
         }
     }
 
-    fn set_ongoing_request(&mut self, responder: WryBindgenResponder) {
+    fn set_ongoing_request(&mut self, responder: WryBindgenResponder, encoding: IpcEncoding) {
         if self.ongoing_request.is_some() {
             panic!(
                 "WARNING: Overwriting existing ongoing_request! Previous request will never be responded to."
             );
         }
-        self.ongoing_request = Some(responder);
+        self.ongoing_request = Some((responder, encoding));
     }
 
-    fn take_ongoing_request(&mut self) -> Option<WryBindgenResponder> {
+    fn take_ongoing_request(&mut self) -> Option<(WryBindgenResponder, IpcEncoding)> {
         self.ongoing_request.take()
     }
 
@@ -136,25 +309,31 @@ impl WebviewState {
     }
 
     fn respond_to_request(&mut self, response: IPCMessage) {
-        if let Some(responder) = self.take_ongoing_request() {
-            let body = response.into_data();
-            // Encode as base64 - sync XMLHttpRequest cannot use responseType="arraybuffer"
-            let engine = base64::engine::general_purpose::STANDARD;
-            let body_base64 = engine.encode(&body);
-            responder.respond(
-                http::Response::builder()
-                    .status(200)
-                    .header("Content-Type", "text/plain")
-                    .body(body_base64.into_bytes())
-                    .expect("Failed to build response"),
-            );
+        if let Some((responder, encoding)) = self.take_ongoing_request() {
+            responder.respond(encoding.response(response.into_data()));
         } else {
             panic!("WARNING: respond_to_request called but no pending request! Response dropped.");
         }
     }
 
-    fn evaluate_script(&mut self, script: &str) {
-        (self.evaluate_script)(script);
+    fn push(&mut self, message: &[u8]) {
+        self.transport.push(message);
+    }
+
+    /// Handle the page initializing again after it already loaded.
+    ///
+    /// Returns `false` if this is the first load.
+    fn reload(&mut self) -> bool {
+        if let WebviewLoadingState::Pending { .. } = self.loading_state {
+            return false;
+        }
+        // The old page is gone along with any request it was blocked on
+        self.ongoing_request = None;
+        self.pending_js_evaluates = 0;
+        self.pending_rust_evaluates = 0;
+        self.loading_state = WebviewLoadingState::Reloading;
+        self.sender.reload();
+        true
     }
 }
 
@@ -193,14 +372,19 @@ impl PreparedApp {
 pub struct ProtocolHandler {
     id: u64,
     webview: Rc<RefCell<HashMap<u64, WebviewState>>>,
+    recorder: Option<Rc<Recorder>>,
+    preload: Rc<[u32]>,
 }
 
 impl ProtocolHandler {
     /// Create a protocol handler closure suitable for `WebViewBuilder::with_asynchronous_custom_protocol`.
     ///
     /// The returned closure handles this subset of "{protocol}://" requests:
-    /// - "/__wbg__/initialized" - signals webview loaded
+    /// - "/__wbg__/initialized" - checks the protocol handshake and signals webview loaded,
+    ///   or resets the app's runtime if the page reloaded. Responds with the code of the
+    ///   preloaded functions
     /// - "/__wbg__/snippets/{path}" - serves inline JS modules
+    /// - "/__wbg__/fn/{ids}" - serves the code of the comma separated function ids
     /// - "/__wbg__/init.js" - serves the initialization script
     /// - "/__wbg__/handler" - main IPC endpoint
     ///
@@ -230,6 +414,10 @@ impl ProtocolHandler {
             .or_else(|| uri.strip_prefix(&windows_prefix))
             .or_else(|| uri.strip_prefix(&android_prefix))
             .unwrap_or(&uri);
+        let (real_path, query) = match real_path.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (real_path, None),
+        };
         let real_path = real_path.trim_matches('/');
 
         let Some(path_without_wbg) = real_path.strip_prefix("__wbg__/") else {
@@ -248,16 +436,53 @@ impl ProtocolHandler {
             return None;
         }
 
+        // Serve the code of JS functions the first time they are called
+        if let Some(ids) = path_without_wbg.strip_prefix("fn/") {
+            let responder = responder.into();
+            let chunk = ids
+                .split(',')
+                .map(|id| id.parse().ok())
+                .collect::<Option<Vec<u32>>>()
+                .and_then(|ids| FUNCTION_REGISTRY.function_chunk(ids));
+            match chunk {
+                Some(chunk) => responder.respond(module_response(&chunk)),
+                None => responder.respond(not_found_response()),
+            }
+            return None;
+        }
+
         if path_without_wbg == "init.js" {
             let responder = responder.into();
-            responder.respond(module_response(&init_script()));
+            responder.respond(module_response(&INIT_SCRIPT.script));
             return None;
         }
 
         if path_without_wbg == "initialized" {
-            proxy(WryBindgenEvent::webview_loaded(webview_id));
             let responder = responder.into();
-            responder.respond(blank_response());
+            match check_handshake(query) {
+                Ok(()) => {
+                    // Tell the app thread about a reload before anything the new page sends
+                    let reloaded = webviews
+                        .borrow_mut()
+                        .get_mut(&webview_id)
+                        .is_some_and(WebviewState::reload);
+                    if !reloaded {
+                        proxy(WryBindgenEvent::webview_loaded(webview_id));
+                    }
+                    if self.preload.is_empty() {
+                        responder.respond(blank_response());
+                    } else {
+                        let chunk = FUNCTION_REGISTRY
+                            .function_chunk(self.preload.iter().copied())
+                            .expect("preloaded ids come from the registry");
+                        responder.respond(module_response(&chunk));
+                    }
+                }
+                Err(error) => {
+                    responder.respond(handshake_error_response(&error));
+                    proxy(WryBindgenEvent::handshake_failed(webview_id, error));
+                }
+            }
             return None;
         }
 
@@ -269,26 +494,31 @@ impl ProtocolHandler {
                 responder.respond(error_response());
                 return None;
             };
-            let Some(msg) = decode_request_data(request) else {
+            let Some((msg, encoding)) = decode_request_data(request) else {
+                responder.respond(error_response());
+                return None;
+            };
+            self.record(Direction::JsToRust, &msg);
+            // The request may come from a network transport, so a bad message is refused
+            let Ok(msg_type) = msg.ty() else {
                 responder.respond(error_response());
                 return None;
             };
-            let msg_type = msg.ty().unwrap();
             match msg_type {
                 // New call from JS - save responder and wait for the js application thread to respond
                 MessageType::Evaluate => {
                     webview_state.pending_rust_evaluates += 1;
-                    webview_state.set_ongoing_request(responder);
+                    webview_state.set_ongoing_request(responder, encoding);
                 }
                 // Response from JS to a previous Evaluate - decrement pending count and respond accordingly
-                MessageType::Respond => {
+                MessageType::Respond | MessageType::Throw => {
                     webview_state.pending_js_evaluates =
                         webview_state.pending_js_evaluates.saturating_sub(1);
                     if webview_state.pending_rust_evaluates > 0
                         || webview_state.pending_js_evaluates > 0
                     {
                         // Still more round-trips expected
-                        webview_state.set_ongoing_request(responder);
+                        webview_state.set_ongoing_request(responder, encoding);
                     } else {
                         // Conversation is over
                         responder.respond(blank_response());
@@ -301,16 +531,70 @@ impl ProtocolHandler {
 
         Some(responder)
     }
+
+    /// Handle a Respond message JS sent through the [`Transport`] instead of a request.
+    ///
+    /// Transports that push Evaluate messages over a channel JS can reply on (like a
+    /// WebSocket) use this to deliver the reply. Returns `false` if the message could
+    /// not be decoded or was not a Respond or Throw message.
+    pub fn handle_message(&self, data: Vec<u8>) -> bool {
+        let mut webviews = self.webview.borrow_mut();
+        let Some(webview_state) = webviews.get_mut(&self.id) else {
+            return false;
+        };
+        let msg = IPCMessage::new(data);
+        if !matches!(msg.ty(), Ok(MessageType::Respond | MessageType::Throw)) {
+            return false;
+        }
+        self.record(Direction::JsToRust, &msg);
+        webview_state.pending_js_evaluates = webview_state.pending_js_evaluates.saturating_sub(1);
+        webview_state.sender.start_send(msg);
+        true
+    }
+
+    fn record(&self, direction: Direction, msg: &IPCMessage) {
+        if let Some(recorder) = &self.recorder {
+            recorder.record(direction, self.id, msg.data());
+        }
+    }
 }
 
-/// Get the initialization script that must be evaluated in the webview.
-///
-/// This script sets up the JavaScript function registry and IPC infrastructure.
-fn init_script() -> String {
+/// The initialization script that must be evaluated in the webview.
+struct InitScript {
+    /// This script sets up the JavaScript function registry and IPC infrastructure.
+    script: String,
+    /// The hash of everything in the script except the hash itself. JS sends it
+    /// back in the handshake so a stale or foreign copy of the script is refused.
+    ///
+    /// The script lists the function ids and their types, so the hash changes when
+    /// the functions do without generating the code of every function at startup.
+    hash: String,
+}
+
+static INIT_SCRIPT: Lazy<InitScript> = Lazy::new(|| {
     /// The script you need to include in the initialization of your webview.
     const INITIALIZATION_SCRIPT: &str = include_str!("./js/main.js");
     let collect_functions = FUNCTION_REGISTRY.script();
-    format!("{INITIALIZATION_SCRIPT}\n{collect_functions}")
+    let encoding = IpcEncoding::for_platform().js_name();
+    let hash = bundle_hash(&[INITIALIZATION_SCRIPT, encoding, collect_functions]);
+    InitScript {
+        script: format!(
+            "{INITIALIZATION_SCRIPT}\nwindow.setIpcEncoding(\"{encoding}\");\nwindow.setBundleHash(\"{hash}\");\n{collect_functions}"
+        ),
+        hash,
+    }
+});
+
+/// A 64 bit FNV-1a hash of the parts of the init script, as hex.
+fn bundle_hash(parts: &[&str]) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for part in parts {
+        for byte in part.bytes().chain([0]) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    format!("{hash:016x}")
 }
 
 /// Reusable wry-bindgen state for integrating with existing wry applications.
@@ -318,6 +602,12 @@ fn init_script() -> String {
 /// This struct manages the IPC protocol between Rust and JavaScript,
 /// handling message queuing, async responses, and JS function registration.
 ///
+/// # Content Security Policy
+///
+/// The code of each JS function is served by the protocol handler and compiled
+/// with `new Function` the first time it is called. Pages with a Content Security
+/// Policy must allow `'unsafe-eval'` in `script-src`.
+///
 /// # Example
 ///
 /// ```ignore
@@ -343,6 +633,14 @@ pub struct WryBindgen {
     event_loop_proxy: Arc<dyn Fn(WryBindgenEvent) + Send + Sync>,
     // State that is unique to each webview
     webview: Rc<RefCell<HashMap<u64, WebviewState>>>,
+    // Where every IPC message is recorded, if recording is enabled
+    recorder: Option<Rc<Recorder>>,
+    // How long flushes wait for JS before panicking
+    flush_timeout: Option<Duration>,
+    // Whether each poll of the app is batched
+    auto_batch: bool,
+    // The ids of the JS functions sent to each webview when it initializes
+    preload: Rc<[u32]>,
 }
 
 impl WryBindgen {
@@ -351,9 +649,69 @@ impl WryBindgen {
         Self {
             event_loop_proxy: Arc::new(event_loop_proxy),
             webview: Rc::new(RefCell::new(HashMap::new())),
+            recorder: None,
+            flush_timeout: None,
+            auto_batch: false,
+            preload: Rc::from([]),
         }
     }
 
+    /// Record every IPC message sent to or from any webview.
+    ///
+    /// See the [`record`](crate::record) module for reading and replaying the recording.
+    pub fn with_recorder(mut self, recorder: Recorder) -> Self {
+        self.recorder = Some(Rc::new(recorder));
+        self
+    }
+
+    /// Panic with a [`FlushTimeout`](crate::batch::FlushTimeout) when JS goes
+    /// `timeout` without responding to a flush, instead of hanging forever.
+    ///
+    /// The event loop receives the timeout as an event before the app panics. See
+    /// [`WryBindgenEvent::flush_timeout`].
+    pub fn with_flush_timeout(mut self, timeout: Duration) -> Self {
+        self.flush_timeout = Some(timeout);
+        self
+    }
+
+    /// Batch every operation the app runs during one poll of its future, as if
+    /// each poll ran inside [`batch`](crate::batch::batch).
+    ///
+    /// Calls that return placeholder types like `JsValue` are sent to JS together
+    /// when the poll ends, or earlier when the app needs a result from JS. Calls
+    /// from JS into Rust are not batched.
+    pub fn with_auto_batch(mut self, auto_batch: bool) -> Self {
+        self.auto_batch = auto_batch;
+        self
+    }
+
+    /// Send the code of every JS function declared in one of the Rust `modules`,
+    /// or their submodules, to the webview when it initializes.
+    ///
+    /// Other functions are fetched the first time they are called. Preloading the
+    /// functions the app calls at startup saves a request for each of them.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let wry_bindgen = wry_bindgen.with_preload(["my_app::startup"]);
+    /// ```
+    pub fn with_preload(mut self, modules: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        let modules: Vec<_> = modules.into_iter().collect();
+        self.preload = FUNCTION_REGISTRY
+            .function_specs()
+            .iter()
+            .enumerate()
+            .filter(|(_, spec)| {
+                modules
+                    .iter()
+                    .any(|module| spec.is_in_module(module.as_ref()))
+            })
+            .map(|(id, _)| id as u32)
+            .collect();
+        self
+    }
+
     /// Start the application thread with the given event loop proxy.
     ///
     /// Returns a tuple of:
@@ -365,9 +723,12 @@ impl WryBindgen {
         let (ipc, senders) = WryIPC::new(event_loop_proxy);
         self.webview.borrow_mut().insert(
             webview_id,
-            WebviewState::new(senders, |_| {
-                unreachable!("evaluate_script will only be used after spawning the app")
-            }),
+            WebviewState::new(
+                senders,
+                EvaluateScriptTransport::new(|_: &str| {
+                    unreachable!("the transport will only be used after spawning the app")
+                }),
+            ),
         );
 
         AppBuilder {
@@ -377,6 +738,19 @@ impl WryBindgen {
         }
     }
 
+    /// Forget a webview whose window was closed and shut down its app.
+    ///
+    /// The app's [`handle_callbacks`] loop ends, and the transport and anything it
+    /// owns (like the webview) are dropped. Other webviews are not affected.
+    pub fn remove_webview(&self, id: u64) {
+        let removed = self.webview.borrow_mut().remove(&id);
+        if let Some(webview_state) = removed {
+            webview_state
+                .sender
+                .close("the webview was closed".to_string());
+        }
+    }
+
     /// Handle a user event from the event loop.
     ///
     /// This should be called from your ApplicationHandler::user_event implementation.
@@ -389,7 +763,12 @@ impl WryBindgen {
         let id = event.id();
         match event.into_variant() {
             // The rust thread sent us an IPCMessage to send to JS
-            AppEventVariant::Ipc(ipc_msg) => self.handle_ipc_message(id, ipc_msg),
+            AppEventVariant::Ipc(ipc_msg) => {
+                if let Some(recorder) = &self.recorder {
+                    recorder.record(Direction::RustToJs, id, ipc_msg.data());
+                }
+                self.handle_ipc_message(id, ipc_msg)
+            }
             AppEventVariant::WebviewLoaded => {
                 let mut state = self.webview.borrow_mut();
                 let Some(webview_state) = state.get_mut(&id) else {
@@ -404,6 +783,23 @@ impl WryBindgen {
                     }
                 }
             }
+            // The app thread reset its runtime, so messages it sends now are for the new page
+            AppEventVariant::WebviewReloaded => {
+                if let Some(webview_state) = self.webview.borrow_mut().get_mut(&id) {
+                    webview_state.loading_state = WebviewLoadingState::Loaded;
+                }
+            }
+            // Only reported to the event loop, the app thread handles the timeout itself
+            AppEventVariant::FlushTimeout(_) => {}
+            // The webview can't talk to this runtime, stop the app instead of letting it hang
+            AppEventVariant::ProtocolError(error) => {
+                let mut state = self.webview.borrow_mut();
+                let Some(webview_state) = state.get_mut(&id) else {
+                    return;
+                };
+                webview_state.loading_state = WebviewLoadingState::default();
+                webview_state.sender.close(error.to_string());
+            }
         }
     }
 
@@ -412,9 +808,13 @@ impl WryBindgen {
         let Some(webview_state) = state.get_mut(&id) else {
             return;
         };
-        if let WebviewLoadingState::Pending { queued } = &mut webview_state.loading_state {
-            queued.push(ipc_msg);
-            return;
+        match &mut webview_state.loading_state {
+            WebviewLoadingState::Pending { queued } => {
+                queued.push(ipc_msg);
+                return;
+            }
+            WebviewLoadingState::Reloading => return,
+            WebviewLoadingState::Loaded => {}
         }
 
         self.immediately_handle_ipc_message(webview_state, ipc_msg)
@@ -425,14 +825,18 @@ impl WryBindgen {
         webview_state: &mut WebviewState,
         ipc_msg: IPCMessage,
     ) {
-        let ty = ipc_msg.ty().unwrap();
+        // Messages from the app thread always have a type, drop one that doesn't
+        // instead of taking down the event loop
+        let Ok(ty) = ipc_msg.ty() else {
+            return;
+        };
         match ty {
             // Rust wants to evaluate something in js
             MessageType::Evaluate => {
                 webview_state.pending_js_evaluates += 1;
             }
             // Rust is responding to a previous js evaluate
-            MessageType::Respond => {
+            MessageType::Respond | MessageType::Throw => {
                 webview_state.pending_rust_evaluates =
                     webview_state.pending_rust_evaluates.saturating_sub(1);
             }
@@ -444,16 +848,11 @@ impl WryBindgen {
             return;
         }
 
-        // Otherwise call into js through evaluate_script
+        // Otherwise push the message to js through the transport
         let decoded = ipc_msg.decoded().unwrap();
 
         if let DecodedVariant::Evaluate { .. } = decoded {
-            // Encode the binary data as base64 and pass to JS
-            // JS will iterate over operations in the buffer
-            let engine = base64::engine::general_purpose::STANDARD;
-            let data_base64 = engine.encode(ipc_msg.data());
-            let code = format!("window.evaluate_from_rust_binary(\"{data_base64}\")");
-            webview_state.evaluate_script(&code);
+            webview_state.push(ipc_msg.data());
         }
     }
 }
@@ -471,10 +870,14 @@ impl<'a> AppBuilder<'a> {
         ProtocolHandler {
             id: self.webview_id,
             webview: self.bindgen.webview.clone(),
+            recorder: self.bindgen.recorder.clone(),
+            preload: self.bindgen.preload.clone(),
         }
     }
 
     /// Consume the builder and get the prepared app future.
+    ///
+    /// Messages are pushed to the webview with the `evaluate_script` function.
     pub fn build<F>(
         self,
         app: impl FnOnce() -> F + Send + 'static,
@@ -483,18 +886,33 @@ impl<'a> AppBuilder<'a> {
     where
         F: core::future::Future<Output = ()> + 'static,
     {
-        // First set up the evaluate_script function in the webview state
+        self.build_with_transport(app, EvaluateScriptTransport::new(evaluate_script))
+    }
+
+    /// Consume the builder and get the prepared app future, pushing messages
+    /// to JS through a custom [`Transport`].
+    pub fn build_with_transport<F>(
+        self,
+        app: impl FnOnce() -> F + Send + 'static,
+        transport: impl Transport + 'static,
+    ) -> PreparedApp
+    where
+        F: core::future::Future<Output = ()> + 'static,
+    {
+        // First set up the transport in the webview state
         {
             let mut webviews = self.bindgen.webview.borrow_mut();
             let webview_state = webviews
                 .get_mut(&self.webview_id)
                 .expect("The webview state was created in WryBindgen::spawner");
-            webview_state.evaluate_script = Box::new(evaluate_script);
+            webview_state.transport = Box::new(transport);
         }
 
+        let flush_timeout = self.bindgen.flush_timeout;
+        let auto_batch = self.bindgen.auto_batch;
         let start_future = move || {
             let run_app_in_runtime = async move {
-                let run_app = app();
+                let run_app = crate::batch::auto_batch(app(), auto_batch);
                 let wait_for_events = handle_callbacks();
 
                 futures_util::select! {
@@ -503,7 +921,8 @@ impl<'a> AppBuilder<'a> {
                 }
             };
 
-            let runtime = Runtime::new(self.ipc, self.webview_id);
+            let mut runtime = Runtime::new(self.ipc, self.webview_id);
+            runtime.set_flush_timeout(flush_timeout);
             let mut maybe_runtime = Some(runtime);
             let poll_in_runtime = async move {
                 let mut run_app_in_runtime = pin!(run_app_in_runtime);
@@ -544,6 +963,15 @@ pub fn error_response() -> http::Response<Vec<u8>> {
         .expect("Failed to build error response")
 }
 
+/// Create the response for a refused `/__wbg__/initialized` handshake.
+fn handshake_error_response(error: &ProtocolError) -> http::Response<Vec<u8>> {
+    http::Response::builder()
+        .status(409)
+        .header("Content-Type", "text/plain")
+        .body(error.to_string().into_bytes())
+        .expect("Failed to build handshake error response")
+}
+
 /// Create a JavaScript module HTTP response.
 pub fn module_response(content: &str) -> http::Response<Vec<u8>> {
     http::Response::builder()
@@ -0,0 +1,1058 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_util::FutureExt;
+
+use wry_bindgen::batch::{batch_async, batch_with_timeout, is_batching, set_flush_timeout};
+use wry_bindgen::fake::{FakeJs, FakeObject, FakeValue};
+use wry_bindgen::runtime::{RuntimeHandle, handle_callbacks, on_reload};
+use wry_bindgen::{
+    Closure, JSFunction, JsFunctionSpec, JsValue, LazyJsFunction, SendJsValue, batch,
+    externref_heap_live_count, heap, throw_val, wasm_bindgen,
+};
+
+#[wasm_bindgen(crate = wry_bindgen)]
+extern "C" {
+    fn add(a: u32, b: u32) -> u32;
+
+    #[wasm_bindgen(js_name = makePoint)]
+    fn make_point(x: f64, y: f64) -> JsValue;
+
+    #[wasm_bindgen(js_name = pointX)]
+    fn point_x(point: &JsValue) -> f64;
+
+    #[wasm_bindgen(js_name = setCallback)]
+    fn set_callback(callback: &Closure<dyn FnMut(u32) -> u32>);
+
+    #[wasm_bindgen(catch, js_name = mayThrow)]
+    fn may_throw(fail: bool) -> Result<u32, JsValue>;
+
+    #[wasm_bindgen(js_name = alwaysThrows)]
+    fn always_throws() -> u32;
+
+    #[wasm_bindgen(js_name = setCallback)]
+    fn set_fallible_callback(callback: &Closure<dyn FnMut(u32) -> Result<u32, JsValue>>);
+
+    #[wasm_bindgen(js_name = setCallback)]
+    fn set_point_callback(callback: &Closure<dyn FnMut(JsValue) -> f64>);
+}
+
+fn fake_add() -> FakeJs {
+    let fake = FakeJs::new();
+    fake.on_import("add", |args| {
+        Ok(FakeValue::from(
+            args[0].as_f64().unwrap() + args[1].as_f64().unwrap(),
+        ))
+    });
+    fake
+}
+
+fn fake_points() -> FakeJs {
+    let fake = FakeJs::new();
+    fake.on_import("makePoint", |args| {
+        let point = FakeObject::new();
+        point.set("x", args[0].clone());
+        point.set("y", args[1].clone());
+        Ok(point.into())
+    });
+    fake.on_import("pointX", |args| Ok(args[0].as_object().unwrap().get("x")));
+    fake
+}
+
+#[test]
+fn numbers() {
+    fake_add().run(|| assert_eq!(add(1, 2), 3));
+}
+
+#[test]
+fn heap_values_are_freed() {
+    let fake = fake_points();
+    fake.run(|| {
+        let alive = fake.heap_objects_alive();
+        let point = make_point(1.0, 2.0);
+        assert_eq!(point_x(&point), 1.0);
+        assert_eq!(
+            fake.value_of(&point).as_object().unwrap().get("y"),
+            FakeValue::from(2.0)
+        );
+        assert_eq!(fake.heap_objects_alive(), alive + 1);
+        drop(point);
+        assert_eq!(fake.heap_objects_alive(), alive);
+    });
+}
+
+#[test]
+fn batched_placeholders() {
+    let fake = fake_points();
+    fake.run(|| {
+        let (a, b) = batch(|| (make_point(1.0, 0.0), make_point(2.0, 0.0)));
+        assert_eq!(point_x(&a), 1.0);
+        assert_eq!(point_x(&b), 2.0);
+
+        let sum = batch(|| {
+            let c = make_point(3.0, 0.0);
+            point_x(&a) + point_x(&c)
+        });
+        assert_eq!(sum, 4.0);
+    });
+}
+
+#[test]
+fn batched_polls() {
+    let fake = fake_points();
+    fake.run(|| {
+        let alive = fake.heap_objects_alive();
+        let (resume, resumed) = futures_channel::oneshot::channel();
+        let mut app = Box::pin(batch_async(async {
+            assert!(is_batching());
+            let point = make_point(1.0, 2.0);
+            // The call is sent when the poll ends
+            assert_eq!(fake.heap_objects_alive(), alive);
+            resumed.await.unwrap();
+            // Results JS has to return flush right away
+            let other = make_point(3.0, 4.0);
+            point_x(&point) + point_x(&other)
+        }));
+
+        let mut cx = Context::from_waker(futures_util::task::noop_waker_ref());
+        assert!(app.as_mut().poll(&mut cx).is_pending());
+        assert!(!is_batching());
+        assert_eq!(fake.heap_objects_alive(), alive + 1);
+
+        resume.send(()).unwrap();
+        assert_eq!(app.as_mut().poll(&mut cx), Poll::Ready(4.0));
+        // The drops at the end of the future are sent when the poll ends too
+        assert_eq!(fake.heap_objects_alive(), alive);
+    });
+}
+
+#[test]
+fn deferred_results() {
+    static ADD: LazyJsFunction<fn(u32, u32) -> u32> =
+        wry_bindgen::__wry_submit_js_function!("(a0, a1) => add(a0, a1)");
+
+    let fake = fake_add();
+    fake.run(|| {
+        // Deferred calls don't flush the batch
+        let (a, b) = batch(|| {
+            let a = ADD.call_deferred(1, 2);
+            let b = ADD.call_deferred(3, 4);
+            assert!(!a.is_resolved() && !b.is_resolved());
+            (a, b)
+        });
+        assert!(a.is_resolved() && b.is_resolved());
+        assert_eq!(a.get() + b.get(), 10);
+
+        // A flush for another result resolves them, and get flushes if needed
+        batch(|| {
+            let a = ADD.call_deferred(1, 1);
+            assert_eq!(add(2, 2), 4);
+            assert!(a.is_resolved());
+            let b = ADD.call_deferred(5, 5);
+            assert_eq!(b.get(), 10);
+            assert_eq!(a.get(), 2);
+        });
+
+        // Outside a batch the call is sent right away
+        assert!(ADD.call_deferred(2, 3).is_resolved());
+    });
+}
+
+#[test]
+fn async_calls() {
+    static ADD: LazyJsFunction<fn(u32, u32) -> u32> =
+        wry_bindgen::__wry_submit_js_function!("(a0, a1) => add(a0, a1)");
+    static MAKE_POINT: LazyJsFunction<fn(f64, f64) -> JsValue> =
+        wry_bindgen::__wry_submit_js_function!("(a0, a1) => makePoint(a0, a1)");
+
+    let fake = fake_points();
+    fake.on_import("add", |args| {
+        Ok(FakeValue::from(
+            args[0].as_f64().unwrap() + args[1].as_f64().unwrap(),
+        ))
+    });
+    fake.run(|| {
+        let mut cx = Context::from_waker(futures_util::task::noop_waker_ref());
+        fake.pause();
+        let mut first = Box::pin(ADD.call_async(1, 2));
+        let mut second = Box::pin(ADD.call_async(3, 4));
+        // The first call is sent and the second waits for it without blocking
+        assert!(first.as_mut().poll(&mut cx).is_pending());
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+
+        fake.resume();
+        assert_eq!(second.as_mut().poll(&mut cx), Poll::Ready(7));
+        assert_eq!(first.as_mut().poll(&mut cx), Poll::Ready(3));
+
+        // JS objects take a placeholder id like batched calls
+        let point = pollster::block_on(MAKE_POINT.call_async(1.0, 2.0));
+        assert_eq!(point_x(&point), 1.0);
+
+        // Sync calls wait for the async call in flight
+        fake.pause();
+        let mut pending = Box::pin(MAKE_POINT.call_async(3.0, 4.0));
+        assert!(pending.as_mut().poll(&mut cx).is_pending());
+        drop(point);
+        fake.resume();
+        assert_eq!(add(1, 1), 2);
+        let Poll::Ready(point) = pending.as_mut().poll(&mut cx) else {
+            panic!("the sync call did not finish the async call");
+        };
+        assert_eq!(point_x(&point), 3.0);
+        drop(point);
+        assert_eq!(fake.heap_objects_alive(), 0);
+    });
+}
+
+#[test]
+fn heap_ids_are_reused() {
+    let fake = fake_points();
+    fake.run(|| {
+        let first = make_point(0.0, 0.0);
+        let first_id = first.id();
+        drop(first);
+
+        // Debug builds check that the fake's free list matches Rust's on every message
+        for i in 0..100 {
+            let x = i as f64;
+            let (a, b) = batch(|| (make_point(x, 0.0), make_point(-x, 0.0)));
+            let c = make_point(x + 0.5, 0.0);
+            assert_eq!(point_x(&a), x);
+            assert_eq!(point_x(&b), -x);
+            assert_eq!(point_x(&c), x + 0.5);
+            assert!(c.id() < first_id + 8, "heap id {} was not reused", c.id());
+        }
+        assert_eq!(fake.heap_objects_alive(), 0);
+    });
+}
+
+#[test]
+fn leak_checks() {
+    let fake = fake_points();
+    fake.run(|| {
+        let before = externref_heap_live_count();
+        heap::start_leak_check();
+        let kept = make_point(1.0, 2.0);
+        let leaked_line = line!() - 1;
+        drop(make_point(3.0, 4.0));
+        let (a, b) = batch(|| (make_point(5.0, 6.0), make_point(7.0, 8.0)));
+        drop(a);
+        assert_eq!(heap::live_js_values(), 2);
+
+        let leaks = heap::finish_leak_check();
+        assert_eq!(leaks.len(), 2);
+        assert_eq!(leaks.leaks()[0].id, kept.id());
+        assert_eq!(leaks.leaks()[0].location.unwrap().line(), leaked_line);
+        assert!(
+            leaks
+                .to_string()
+                .starts_with("1 JsValue leaked, created at wry-bindgen/tests/fake_js.rs:"),
+            "{leaks}"
+        );
+
+        drop((kept, b));
+        assert_eq!(heap::live_js_values(), 0);
+        assert_eq!(externref_heap_live_count(), before);
+    });
+}
+
+#[test]
+fn callbacks() {
+    let fake = FakeJs::new();
+    let stored = Arc::new(Mutex::new(FakeValue::Undefined));
+    fake.on_import("setCallback", {
+        let stored = stored.clone();
+        move |args| {
+            *stored.lock().unwrap() = args[0].clone();
+            Ok(FakeValue::Undefined)
+        }
+    });
+    fake.run(|| {
+        let closure = Closure::new(|x: u32| x * 2);
+        set_callback(&closure);
+        let callback = stored.lock().unwrap().clone();
+        let result = callback.as_function().unwrap().call(&[FakeValue::from(21)]);
+        assert_eq!(result, FakeValue::from(42));
+    });
+}
+
+#[test]
+fn catch() {
+    let fake = FakeJs::new();
+    fake.on_import("mayThrow", |args| {
+        if args[0].is_truthy() {
+            Err(FakeObject::error("failed").into())
+        } else {
+            Ok(FakeValue::from(1))
+        }
+    });
+    fake.run(|| {
+        assert_eq!(may_throw(false).unwrap(), 1);
+        let err = may_throw(true).unwrap_err();
+        assert_eq!(
+            fake.value_of(&err).as_object().unwrap().get("message"),
+            FakeValue::from("failed")
+        );
+    });
+}
+
+#[test]
+#[should_panic(expected = "JS threw an exception in a call from")]
+fn uncaught_exceptions_panic() {
+    let fake = FakeJs::new();
+    fake.on_import("alwaysThrows", |_| Err(FakeObject::error("boom").into()));
+    fake.run(|| always_throws());
+}
+
+fn stored_callback(fake: &FakeJs) -> Arc<Mutex<FakeValue>> {
+    let stored = Arc::new(Mutex::new(FakeValue::Undefined));
+    fake.on_import("setCallback", {
+        let stored = stored.clone();
+        move |args| {
+            *stored.lock().unwrap() = args[0].clone();
+            Ok(FakeValue::Undefined)
+        }
+    });
+    stored
+}
+
+#[test]
+fn callback_panics_throw_in_js() {
+    let fake = FakeJs::new();
+    let stored = stored_callback(&fake);
+    fake.run(|| {
+        let closure = Closure::new(|x: u32| -> u32 { panic!("bad input {x}") });
+        set_callback(&closure);
+        let callback = stored.lock().unwrap().clone();
+        let error = callback
+            .as_function()
+            .unwrap()
+            .try_call(&[FakeValue::from(3)])
+            .unwrap_err();
+        assert_eq!(
+            error.as_object().unwrap().get("message"),
+            FakeValue::from("bad input 3")
+        );
+
+        // The runtime is still usable after the panic
+        let closure = Closure::new(|x: u32| x + 1);
+        set_callback(&closure);
+        let callback = stored.lock().unwrap().clone();
+        let result = callback.as_function().unwrap().call(&[FakeValue::from(1)]);
+        assert_eq!(result, FakeValue::from(2));
+    });
+}
+
+#[test]
+fn callbacks_throw_values() {
+    let fake = FakeJs::new();
+    let stored = stored_callback(&fake);
+    fake.run(|| {
+        let closure =
+            Closure::new(|x: u32| -> u32 { throw_val(JsValue::from_str(&x.to_string())) });
+        set_callback(&closure);
+        let callback = stored.lock().unwrap().clone();
+        let error = callback
+            .as_function()
+            .unwrap()
+            .try_call(&[FakeValue::from(5)])
+            .unwrap_err();
+        assert_eq!(error, FakeValue::from("5"));
+
+        let closure = Closure::new(|x: u32| {
+            if x > 10 {
+                Err(JsValue::from_str("too big"))
+            } else {
+                Ok(x * 2)
+            }
+        });
+        set_fallible_callback(&closure);
+        let callback = stored.lock().unwrap().clone();
+        let callback = callback.as_function().unwrap();
+        assert_eq!(
+            callback.try_call(&[FakeValue::from(2)]),
+            Ok(FakeValue::from(4))
+        );
+        assert_eq!(
+            callback.try_call(&[FakeValue::from(20)]),
+            Err(FakeValue::from("too big"))
+        );
+    });
+}
+
+#[test]
+fn callbacks_drop_arguments() {
+    let fake = fake_points();
+    let stored = stored_callback(&fake);
+    fake.run(|| {
+        let closure = Closure::new(|point: JsValue| point_x(&point));
+        set_point_callback(&closure);
+        let callback = stored.lock().unwrap().clone();
+        let callback = callback.as_function().unwrap();
+        let alive = fake.heap_objects_alive();
+        let first_id = make_point(0.0, 0.0).id();
+
+        // The arguments are dropped with the message after the callback's response.
+        // Debug builds check that the fake's free list matches Rust's on every message
+        for i in 0..50 {
+            let x = i as f64;
+            let point = FakeObject::new();
+            point.set("x", FakeValue::from(x));
+            assert_eq!(callback.call(&[point.into()]), FakeValue::from(x));
+            let other = make_point(-x, 0.0);
+            assert_eq!(point_x(&other), -x);
+            assert!(
+                other.id() < first_id + 8,
+                "heap id {} was not reused",
+                other.id()
+            );
+        }
+        assert_eq!(fake.heap_objects_alive(), alive);
+    });
+}
+
+#[test]
+fn batch_timeouts() {
+    let fake = fake_add();
+    fake.run(|| {
+        fake.pause();
+        let error = batch_with_timeout(Duration::from_millis(20), || add(1, 2)).unwrap_err();
+        assert_eq!(error.timeout, Duration::from_millis(20));
+        assert_eq!(error.operations.len(), 1);
+        assert!(error.operations[0].location.file().ends_with("fake_js.rs"));
+        assert!(error.to_string().contains("add"));
+
+        // JS answering the timed out batch late doesn't confuse the next flush
+        fake.resume();
+        assert_eq!(add(2, 3), 5);
+        assert_eq!(
+            batch_with_timeout(Duration::from_secs(10), || add(3, 4)).unwrap(),
+            7
+        );
+    });
+}
+
+#[test]
+#[should_panic(expected = "JS did not respond to a batch of 1 operations")]
+fn flush_timeouts_panic() {
+    let fake = fake_add();
+    fake.run(|| {
+        set_flush_timeout(Some(Duration::from_millis(20)));
+        fake.pause();
+        add(1, 2)
+    });
+}
+
+#[test]
+fn reloads_reset_the_runtime() {
+    let fake = fake_points();
+    fake.run(|| {
+        let reloads = Rc::new(Cell::new(0));
+        on_reload({
+            let reloads = reloads.clone();
+            move || reloads.set(reloads.get() + 1)
+        });
+        let old_point = make_point(1.0, 2.0);
+
+        fake.reload();
+        assert_eq!(reloads.get(), 1);
+        assert_eq!(fake.heap_objects_alive(), 0);
+
+        // The new page starts with an empty heap and type cache
+        let point = make_point(3.0, 4.0);
+        assert_eq!(point_x(&point), 3.0);
+        assert_ne!(point, old_point);
+
+        // Dropping a value from the old page doesn't free the new page's value with the same id
+        drop(old_point);
+        assert_eq!(fake.heap_objects_alive(), 1);
+        assert_eq!(point_x(&point), 3.0);
+    });
+}
+
+#[test]
+#[should_panic(expected = "from before the webview reloaded")]
+fn values_from_before_a_reload_panic() {
+    let fake = fake_points();
+    fake.run(|| {
+        let point = make_point(1.0, 2.0);
+        fake.reload();
+        point_x(&point)
+    });
+}
+
+#[test]
+fn send_js_values() {
+    let fake = fake_points();
+    fake.run(|| {
+        let alive = fake.heap_objects_alive();
+        let handle = SendJsValue::new(make_point(1.0, 2.0));
+
+        // The handle can only be resolved on the runtime that owns it
+        let handle = std::thread::spawn(move || handle.try_into_js_value().unwrap_err())
+            .join()
+            .unwrap();
+        assert_eq!(handle.with(point_x), 1.0);
+        let point = handle.into_js_value();
+        assert_eq!(point_x(&point), 1.0);
+        assert_eq!(fake.heap_objects_alive(), alive + 1);
+
+        // Dropping a handle on another thread frees the value on the next call into JS
+        let handle = SendJsValue::from(point);
+        std::thread::spawn(move || drop(handle)).join().unwrap();
+        assert_eq!(fake.heap_objects_alive(), alive + 1);
+        let other = make_point(3.0, 4.0);
+        assert_eq!(point_x(&other), 3.0);
+        assert_eq!(fake.heap_objects_alive(), alive + 1);
+    });
+}
+
+#[test]
+fn runtime_handles() {
+    let fake = fake_points();
+    fake.run(|| {
+        let runtime = RuntimeHandle::current();
+        // On the runtime thread the closure runs immediately
+        assert_eq!(runtime.run_blocking(|| point_x(&make_point(1.0, 0.0))), 1.0);
+
+        let (done, finished) = futures_channel::oneshot::channel();
+        let worker = std::thread::spawn(move || {
+            let x = runtime.run_blocking(|| point_x(&make_point(2.0, 0.0)));
+            runtime.spawn(move || async move {
+                _ = done.send(x + point_x(&make_point(3.0, 0.0)));
+            });
+        });
+
+        // The work runs while the app waits on handle_callbacks
+        let sum = pollster::block_on(async {
+            futures_util::select! {
+                _ = Box::pin(handle_callbacks()).fuse() => unreachable!(),
+                sum = finished.fuse() => sum.unwrap(),
+            }
+        });
+        worker.join().unwrap();
+        assert_eq!(sum, 5.0);
+    });
+}
+
+#[test]
+fn interned_strings() {
+    static ECHO: LazyJsFunction<fn(String) -> String> =
+        wry_bindgen::__wry_submit_js_function!("(a0) => echo(a0)");
+
+    let fake = FakeJs::new();
+    fake.on_import("echo", |args| Ok(args[0].clone()));
+    fake.run(|| {
+        let alive = fake.heap_objects_alive();
+        let click = wry_bindgen::intern("click");
+        assert_eq!(fake.heap_objects_alive(), alive + 1);
+        // Interned strings are sent as a reference to the JS string
+        assert_eq!(ECHO.call(click.to_string()), "click");
+        assert_eq!(ECHO.call("style".to_string()), "style");
+
+        wry_bindgen::unintern(click);
+        assert_eq!(fake.heap_objects_alive(), alive);
+        assert_eq!(ECHO.call(click.to_string()), "click");
+    });
+}
+
+mod numbers {
+    use wry_bindgen::wasm_bindgen;
+
+    #[wasm_bindgen(crate = wry_bindgen)]
+    extern "C" {
+        #[wasm_bindgen(js_name = sharedEcho)]
+        pub fn echo(value: u32) -> u32;
+    }
+}
+
+mod text {
+    use wry_bindgen::wasm_bindgen;
+
+    #[wasm_bindgen(crate = wry_bindgen)]
+    extern "C" {
+        #[wasm_bindgen(js_name = sharedEcho)]
+        pub fn echo(value: String) -> String;
+    }
+}
+
+fn shared_echo_code() -> String {
+    "(a0) => sharedEcho(a0)".to_string()
+}
+
+// Two specs with one code function, like the imports above once a release build
+// merges their identical code closures
+static ECHO_NUMBER: JsFunctionSpec = JsFunctionSpec::new(shared_echo_code)
+    .with_signature(|buf| JSFunction::<fn(u32) -> u32>::new(0).encode_signature(buf));
+static ECHO_TEXT: JsFunctionSpec = JsFunctionSpec::new(shared_echo_code)
+    .with_signature(|buf| JSFunction::<fn(String) -> String>::new(0).encode_signature(buf));
+wry_bindgen::inventory::submit! { ECHO_NUMBER }
+wry_bindgen::inventory::submit! { ECHO_TEXT }
+
+#[test]
+fn imports_of_the_same_js_keep_their_types() {
+    static NUMBER: LazyJsFunction<fn(u32) -> u32> = ECHO_NUMBER.resolve_as();
+    static TEXT: LazyJsFunction<fn(String) -> String> = ECHO_TEXT.resolve_as();
+
+    let fake = FakeJs::new();
+    fake.on_import("sharedEcho", |args| Ok(args[0].clone()));
+    fake.run(|| {
+        assert_eq!(NUMBER.call(7), 7);
+        assert_eq!(TEXT.call("seven".to_string()), "seven");
+        assert_eq!(numbers::echo(8), 8);
+        assert_eq!(text::echo("eight".to_string()), "eight");
+    });
+}
+
+#[test]
+fn js_values() {
+    FakeJs::new().run(|| {
+        let value = JsValue::from_str("hello");
+        assert_eq!(value.as_string().as_deref(), Some("hello"));
+        assert!(value.is_string());
+        assert!(JsValue::from(1.5).as_f64() == Some(1.5));
+        let cloned = value.clone();
+        assert_eq!(cloned.as_string().as_deref(), Some("hello"));
+    });
+}
+
+#[wasm_bindgen(crate = wry_bindgen)]
+pub fn greet(name: &str) -> String {
+    format!("Hello, {name}!")
+}
+
+#[wasm_bindgen(crate = wry_bindgen, js_name = sumAll)]
+pub fn sum_all(values: &[u32], offset: Option<u32>) -> u32 {
+    values.iter().sum::<u32>() + offset.unwrap_or_default()
+}
+
+#[wasm_bindgen(crate = wry_bindgen, js_name = expectString)]
+pub fn expect_string(value: JsValue) -> Result<String, JsValue> {
+    value.as_string().ok_or(value)
+}
+
+#[test]
+fn exported_functions() {
+    let fake = FakeJs::new();
+    fake.run(|| {
+        assert_eq!(
+            fake.call_export("greet", &[FakeValue::from("JS")]),
+            Ok(FakeValue::from("Hello, JS!"))
+        );
+        let values = FakeValue::Array(vec![FakeValue::from(1), FakeValue::from(2)]);
+        assert_eq!(
+            fake.call_export("sumAll", &[values.clone(), FakeValue::Null]),
+            Ok(FakeValue::from(3))
+        );
+        assert_eq!(
+            fake.call_export("sumAll", &[values, FakeValue::from(10)]),
+            Ok(FakeValue::from(13))
+        );
+        // Returning Err throws the value in JS
+        assert_eq!(
+            fake.call_export("expectString", &[FakeValue::from("text")]),
+            Ok(FakeValue::from("text"))
+        );
+        assert_eq!(
+            fake.call_export("expectString", &[FakeValue::from(5)]),
+            Err(FakeValue::from(5))
+        );
+    });
+}
+
+static STARTED: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+#[wasm_bindgen(crate = wry_bindgen, start)]
+fn start() {
+    STARTED.lock().unwrap().push(add(1, 2));
+}
+
+#[wasm_bindgen(crate = wry_bindgen, start)]
+async fn start_async() -> Result<(), JsValue> {
+    STARTED.lock().unwrap().push(add(3, 4));
+    Ok(())
+}
+
+#[test]
+fn start_functions() {
+    fake_add().run(|| {
+        wry_bindgen::run_start_functions()
+            .now_or_never()
+            .expect("start functions don't wait on JS");
+    });
+    let mut started = STARTED.lock().unwrap().clone();
+    started.sort();
+    assert_eq!(started, [3, 7]);
+}
+
+#[wasm_bindgen(crate = wry_bindgen)]
+pub struct Label {
+    text: String,
+}
+
+#[wasm_bindgen(crate = wry_bindgen)]
+impl Label {
+    #[wasm_bindgen(constructor)]
+    pub fn new(text: &str) -> Label {
+        Label {
+            text: text.to_string(),
+        }
+    }
+
+    pub fn append(&mut self, suffix: Option<String>) {
+        self.text.push_str(suffix.as_deref().unwrap_or("!"));
+    }
+
+    pub fn repeated(&self, count: u32) -> Self {
+        Label {
+            text: self.text.repeat(count as usize),
+        }
+    }
+
+    pub fn words(&self) -> Vec<String> {
+        self.text.split(' ').map(String::from).collect()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn text(&self) -> String {
+        self.text.clone()
+    }
+
+    #[wasm_bindgen(js_name = fromValue)]
+    pub fn from_value(value: JsValue) -> Result<Label, JsValue> {
+        let text = value.as_string().ok_or(value)?;
+        Ok(Label { text })
+    }
+}
+
+#[test]
+fn exported_class_members() {
+    let fake = FakeJs::new();
+    fake.run(|| {
+        let label = fake
+            .call_export("Label::new", &[FakeValue::from("hi there")])
+            .unwrap();
+        fake.call_export("Label::append", &[label.clone(), FakeValue::Null])
+            .unwrap();
+        fake.call_export("Label::append", &[label.clone(), FakeValue::from("?")])
+            .unwrap();
+        assert_eq!(
+            fake.call_export("Label::text", std::slice::from_ref(&label)),
+            Ok(FakeValue::from("hi there!?"))
+        );
+        assert_eq!(
+            fake.call_export("Label::words", std::slice::from_ref(&label)),
+            Ok(FakeValue::Array(vec![
+                FakeValue::from("hi"),
+                FakeValue::from("there!?")
+            ]))
+        );
+
+        // Returned structs are wrapped in a new JS object
+        let repeated = fake
+            .call_export("Label::repeated", &[label, FakeValue::from(2)])
+            .unwrap();
+        let handle = repeated.as_object().unwrap().get("__handle");
+        assert_eq!(
+            fake.call_export("Label::text", &[handle]),
+            Ok(FakeValue::from("hi there!?hi there!?"))
+        );
+
+        let from_value = fake
+            .call_export("Label::fromValue", &[FakeValue::from("text")])
+            .unwrap();
+        assert_eq!(
+            from_value.as_object().unwrap().get("__className"),
+            FakeValue::from("Label")
+        );
+        assert_eq!(
+            fake.call_export("Label::fromValue", &[FakeValue::from(5)]),
+            Err(FakeValue::from(5))
+        );
+    });
+}
+
+#[wasm_bindgen(crate = wry_bindgen)]
+extern "C" {
+    #[wasm_bindgen(js_name = storeLabels)]
+    fn store_labels(first: Label, rest: Vec<Label>, extra: Option<Label>);
+
+    #[wasm_bindgen(js_name = takeLabel)]
+    fn take_label() -> Option<Label>;
+
+    #[wasm_bindgen(js_name = inspectLabel)]
+    fn inspect_label(label: &Label) -> JsValue;
+
+    #[wasm_bindgen(js_name = setLabelCallback)]
+    fn set_label_callback(callback: &Closure<dyn FnMut(&Label) -> String>);
+}
+
+#[test]
+fn exported_structs_in_imports() {
+    let fake = FakeJs::new();
+    let stored = Arc::new(Mutex::new(Vec::new()));
+    let callback = Arc::new(Mutex::new(FakeValue::Undefined));
+    fake.on_import("storeLabels", {
+        let stored = stored.clone();
+        move |args| {
+            let mut stored = stored.lock().unwrap();
+            stored.push(args[0].clone());
+            let FakeValue::Array(rest) = &args[1] else {
+                panic!("expected an array of labels");
+            };
+            stored.extend(rest.iter().cloned());
+            stored.push(args[2].clone());
+            Ok(FakeValue::Undefined)
+        }
+    });
+    fake.on_import("takeLabel", {
+        let stored = stored.clone();
+        move |_| Ok(stored.lock().unwrap().pop().unwrap_or(FakeValue::Null))
+    });
+    fake.on_import("inspectLabel", |args| Ok(args[0].clone()));
+    fake.on_import("setLabelCallback", {
+        let callback = callback.clone();
+        move |args| {
+            *callback.lock().unwrap() = args[0].clone();
+            Ok(FakeValue::Undefined)
+        }
+    });
+    fake.run(|| {
+        // Labels passed by value move to JS wrappers that own them
+        store_labels(
+            Label::new("first"),
+            vec![Label::new("second"), Label::new("third")],
+            Some(Label::new("fourth")),
+        );
+        let wrappers = stored.lock().unwrap().clone();
+        assert_eq!(wrappers.len(), 4);
+        for wrapper in &wrappers {
+            let wrapper = wrapper.as_object().unwrap();
+            assert_eq!(wrapper.get("__className"), FakeValue::from("Label"));
+            assert_eq!(wrapper.get("__owned"), FakeValue::from(true));
+        }
+        let handle = wrappers[1].as_object().unwrap().get("__handle");
+        assert_eq!(
+            fake.call_export("Label::text", &[handle]),
+            Ok(FakeValue::from("second"))
+        );
+
+        // A callback borrows the label without taking it from the JS wrapper
+        let closure: Closure<dyn FnMut(&Label) -> String> =
+            Closure::new(|label: &Label| label.text.to_uppercase());
+        set_label_callback(&closure);
+        let callback = callback.lock().unwrap().clone();
+        let result = callback
+            .as_function()
+            .unwrap()
+            .call(std::slice::from_ref(&wrappers[0]));
+        assert_eq!(result, FakeValue::from("FIRST"));
+        assert_ne!(
+            wrappers[0].as_object().unwrap().get("__handle"),
+            FakeValue::from(0)
+        );
+
+        // Returning a label moves it back to Rust
+        assert_eq!(take_label().unwrap().text, "fourth");
+        assert_eq!(
+            wrappers[3].as_object().unwrap().get("__handle"),
+            FakeValue::from(0)
+        );
+        assert_eq!(take_label().unwrap().text, "third");
+        stored.lock().unwrap().clear();
+        assert!(take_label().is_none());
+
+        // A label passed by reference is only lent to the call
+        let label = Label::new("lent");
+        let wrapper = fake.value_of(&inspect_label(&label));
+        assert_eq!(
+            wrapper.as_object().unwrap().get("__owned"),
+            FakeValue::from(false)
+        );
+        assert_eq!(label.text, "lent");
+    });
+}
+
+#[test]
+fn lent_structs_are_released_when_the_call_unwinds() {
+    let fake = FakeJs::new();
+    let wrapper = Arc::new(Mutex::new(FakeValue::Undefined));
+    fake.on_import("inspectLabel", {
+        let wrapper = wrapper.clone();
+        move |args| {
+            *wrapper.lock().unwrap() = args[0].clone();
+            Err(FakeObject::error("boom").into())
+        }
+    });
+    fake.run(|| {
+        let objects = heap::live_objects();
+        let label = Label::new("lent");
+        let result =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| inspect_label(&label)));
+        assert!(result.is_err());
+        assert_eq!(heap::live_objects(), objects);
+
+        // The wrapper JS kept can't reach the label after the call unwound
+        let handle = wrapper.lock().unwrap().as_object().unwrap().get("__handle");
+        assert!(fake.call_export("Label::text", &[handle]).is_err());
+        assert_eq!(label.text, "lent");
+    });
+}
+
+thread_local! {
+    /// The suffix `Label::wait_for_suffix` waits for.
+    static SUFFIX: Cell<Option<futures_channel::oneshot::Receiver<String>>> = const { Cell::new(None) };
+}
+
+#[wasm_bindgen(crate = wry_bindgen)]
+impl Label {
+    pub async fn wait_for_suffix(&mut self) -> Result<String, JsValue> {
+        let suffix = SUFFIX.take().ok_or("no suffix")?;
+        let suffix = suffix.await.map_err(|_| "cancelled")?;
+        self.text.push_str(&suffix);
+        Ok(self.text.clone())
+    }
+
+    pub async fn shout(text: &str) -> String {
+        text.to_uppercase()
+    }
+}
+
+/// Run the futures started on the runtime until `promise` settles.
+fn settle(promise: &FakeValue) -> (FakeValue, FakeValue) {
+    with_callbacks(settled(promise))
+}
+
+/// Run `future` while the futures started on the runtime run.
+fn with_callbacks<T>(future: impl Future<Output = T>) -> T {
+    pollster::block_on(async {
+        futures_util::select! {
+            _ = Box::pin(handle_callbacks()).fuse() => unreachable!(),
+            output = Box::pin(future).fuse() => output,
+        }
+    })
+}
+
+/// Wait until `promise` settles and return its state and value.
+async fn settled(promise: &FakeValue) -> (FakeValue, FakeValue) {
+    let promise = promise.as_object().unwrap();
+    futures_util::future::poll_fn(|cx| {
+        if promise.get("state") == FakeValue::from("pending") {
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        Poll::Ready(())
+    })
+    .await;
+    (promise.get("state"), promise.get("value"))
+}
+
+#[test]
+fn async_exported_methods() {
+    let fake = FakeJs::new();
+    fake.run(|| {
+        let promise = fake
+            .call_export("Label::shout", &[FakeValue::from("hey")])
+            .unwrap();
+        assert_eq!(
+            settle(&promise),
+            (FakeValue::from("fulfilled"), FakeValue::from("HEY"))
+        );
+
+        let label = fake
+            .call_export("Label::new", &[FakeValue::from("wait")])
+            .unwrap();
+        let (send, receive) = futures_channel::oneshot::channel();
+        SUFFIX.set(Some(receive));
+        let promise = fake
+            .call_export("Label::wait_for_suffix", std::slice::from_ref(&label))
+            .unwrap();
+        // The promise is pending until the method's future finishes
+        assert_eq!(
+            promise.as_object().unwrap().get("state"),
+            FakeValue::from("pending")
+        );
+        send.send("ed".to_string()).unwrap();
+        assert_eq!(
+            settle(&promise),
+            (FakeValue::from("fulfilled"), FakeValue::from("waited"))
+        );
+        assert_eq!(
+            fake.call_export("Label::text", std::slice::from_ref(&label)),
+            Ok(FakeValue::from("waited"))
+        );
+
+        // An `Err` rejects the promise
+        let promise = fake
+            .call_export("Label::wait_for_suffix", &[label])
+            .unwrap();
+        assert_eq!(
+            settle(&promise),
+            (FakeValue::from("rejected"), FakeValue::from("no suffix"))
+        );
+    });
+}
+
+#[test]
+fn methods_called_while_an_async_method_runs_throw() {
+    let fake = FakeJs::new();
+    fake.run(|| {
+        let label = fake
+            .call_export("Label::new", &[FakeValue::from("wait")])
+            .unwrap();
+        let (send, receive) = futures_channel::oneshot::channel();
+        SUFFIX.set(Some(receive));
+        with_callbacks(async {
+            let waiting = fake
+                .call_export("Label::wait_for_suffix", std::slice::from_ref(&label))
+                .unwrap();
+            // Let the method start and wait for the suffix
+            futures_util::future::poll_fn(|cx| {
+                let receive = SUFFIX.take();
+                if receive.is_none() {
+                    return Poll::Ready(());
+                }
+                SUFFIX.set(receive);
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            })
+            .await;
+
+            // The pending `&mut self` method borrows the label until it finishes
+            let error = fake
+                .call_export("Label::text", std::slice::from_ref(&label))
+                .unwrap_err();
+            assert_eq!(
+                error.as_object().unwrap().get("message"),
+                FakeValue::from(
+                    "cannot use `fake_js::Label` while a `&mut self` method on it is running"
+                )
+            );
+            let promise = fake
+                .call_export("Label::wait_for_suffix", std::slice::from_ref(&label))
+                .unwrap();
+            let (state, error) = settled(&promise).await;
+            assert_eq!(state, FakeValue::from("rejected"));
+            assert_eq!(
+                error.as_object().unwrap().get("message"),
+                FakeValue::from(
+                    "cannot mutate `fake_js::Label` while another method on it is running"
+                )
+            );
+
+            // The first call is not affected
+            send.send("ed".to_string()).unwrap();
+            assert_eq!(
+                settled(&waiting).await,
+                (FakeValue::from("fulfilled"), FakeValue::from("waited"))
+            );
+        });
+        assert_eq!(
+            fake.call_export("Label::text", &[label]),
+            Ok(FakeValue::from("waited"))
+        );
+    });
+}
@@ -0,0 +1,462 @@
+//! Run an app against a desktop browser instead of a wry webview.
+//!
+//! The page, init.js and the snippets are served over a localhost HTTP server.
+//! JS calls into Rust with the same synchronous requests it uses in the webview,
+//! and Rust pushes messages to JS over a WebSocket instead of `evaluate_script`.
+
+use std::cell::RefCell;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
+
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+use tungstenite::Message;
+use tungstenite::protocol::{Role, WebSocket};
+use wasm_bindgen::runtime::WryBindgenEvent;
+use wasm_bindgen::wry::{Transport, WryBindgen, not_found_response};
+use wry::http::{Request, Response, StatusCode, header};
+
+use crate::home::browser_root_response;
+
+const PROTOCOL_SCHEME: &str = "wry";
+
+/// The path JS connects the WebSocket to.
+const SOCKET_PATH: &str = "/__wbg__/ws";
+
+/// Events handled on the thread that owns the [`WryBindgen`] state.
+enum BrowserEvent {
+    /// An event from the wry-bindgen runtime
+    App(WryBindgenEvent),
+    /// An HTTP request from the browser, and where to send the response
+    Request {
+        request: Box<Request<Vec<u8>>>,
+        respond: Sender<Response<Vec<u8>>>,
+    },
+    /// The browser opened the WebSocket, and where to send the messages to push over it
+    SocketConnected(UnboundedSender<Vec<u8>>),
+    /// A message JS sent over the WebSocket
+    SocketMessage(Vec<u8>),
+    /// The app finished
+    Shutdown,
+}
+
+/// State shared between the event loop and the transport.
+#[derive(Default)]
+struct SocketState {
+    socket: Option<UnboundedSender<Vec<u8>>>,
+    /// Messages pushed before the browser connected
+    queued: Vec<Vec<u8>>,
+}
+
+/// A [`Transport`] that pushes messages over the browser's WebSocket.
+#[derive(Clone, Default)]
+struct WebSocketTransport {
+    state: Rc<RefCell<SocketState>>,
+}
+
+impl WebSocketTransport {
+    /// Start sending over a newly connected socket, flushing any queued messages.
+    fn connect(&self, socket: UnboundedSender<Vec<u8>>) {
+        let mut state = self.state.borrow_mut();
+        for message in state.queued.drain(..) {
+            send_binary(&socket, message);
+        }
+        state.socket = Some(socket);
+    }
+}
+
+impl Transport for WebSocketTransport {
+    fn push(&mut self, message: &[u8]) {
+        let mut state = self.state.borrow_mut();
+        match &state.socket {
+            Some(socket) => send_binary(socket, message.to_vec()),
+            None => state.queued.push(message.to_vec()),
+        }
+    }
+}
+
+fn send_binary(socket: &UnboundedSender<Vec<u8>>, message: Vec<u8>) {
+    if socket.send(message).is_err() {
+        eprintln!("Failed to send message over the WebSocket: the socket is closed");
+    }
+}
+
+/// What each connection needs to talk to the event loop and to turn away other pages.
+#[derive(Clone)]
+struct Connections {
+    events: Sender<BrowserEvent>,
+    /// The origin of the page the server hands out, `http://<bound addr>`
+    origin: Arc<str>,
+    /// Set once a page opens the WebSocket. The app's JS state lives in that
+    /// page, so no other page can take over.
+    socket_taken: Arc<AtomicBool>,
+}
+
+impl Connections {
+    /// Whether `request` may come from another site. Browsers only leave out the
+    /// `Origin` header for same-origin GETs and navigations, which can't read the
+    /// response of another origin anyway.
+    fn is_cross_origin(&self, request: &Request<Vec<u8>>) -> bool {
+        request
+            .headers()
+            .get(header::ORIGIN)
+            .is_some_and(|origin| origin.as_bytes() != self.origin.as_bytes())
+    }
+}
+
+pub(crate) fn run_browser<F>(
+    addr: impl ToSocketAddrs,
+    configure: impl FnOnce(WryBindgen) -> io::Result<WryBindgen>,
+    app: impl FnOnce() -> F + Send + 'static,
+) -> io::Result<()>
+where
+    F: Future<Output = ()> + 'static,
+{
+    let listener = TcpListener::bind(addr)?;
+    let local_addr = listener.local_addr()?;
+    let (events, receiver) = mpsc::channel();
+
+    let wry_bindgen = configure(WryBindgen::new({
+        let events = events.clone();
+        move |event| {
+            _ = events.send(BrowserEvent::App(event));
+        }
+    }))?;
+
+    let app_builder = wry_bindgen.app_builder();
+    let protocol_handler = app_builder.protocol_handler();
+    let transport = WebSocketTransport::default();
+    let run_app = app_builder.build_with_transport(app, transport.clone());
+
+    {
+        let connections = Connections {
+            events: events.clone(),
+            origin: format!("http://{local_addr}").into(),
+            socket_taken: Arc::default(),
+        };
+        std::thread::spawn(move || accept_connections(listener, connections));
+    }
+
+    {
+        let events = events.clone();
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                runtime.block_on(run_app.into_future())
+            }));
+            // The panic message was already printed, don't leave the page hanging
+            if result.is_err() {
+                std::process::exit(101);
+            }
+            // Signal the event loop to exit after app completes
+            _ = events.send(BrowserEvent::Shutdown);
+        });
+    }
+
+    println!("Open http://{local_addr} in a browser to run the app");
+
+    while let Ok(event) = receiver.recv() {
+        match event {
+            BrowserEvent::App(app_event) => {
+                if let Some(error) = app_event.protocol_error() {
+                    return Err(io::Error::other(error.clone()));
+                }
+                if let Some(timeout) = app_event.flush_timeout() {
+                    eprintln!("The browser stopped responding: {timeout}");
+                }
+                wry_bindgen.handle_user_event(app_event)
+            }
+            BrowserEvent::Request { request, respond } => {
+                let send_app_event = |event| {
+                    _ = events.send(BrowserEvent::App(event));
+                };
+                let responder = protocol_handler.handle_request(
+                    PROTOCOL_SCHEME,
+                    send_app_event,
+                    &request,
+                    move |response| {
+                        _ = respond.send(response);
+                    },
+                );
+                if let Some(responder) = responder {
+                    let response = if request.uri().path() == "/" {
+                        browser_root_response(SOCKET_PATH)
+                    } else {
+                        not_found_response()
+                    };
+                    responder(response);
+                }
+            }
+            BrowserEvent::SocketConnected(socket) => transport.connect(socket),
+            BrowserEvent::SocketMessage(data) => {
+                if !protocol_handler.handle_message(data) {
+                    eprintln!("Ignoring an invalid message from the WebSocket");
+                }
+            }
+            BrowserEvent::Shutdown => break,
+        }
+    }
+
+    Ok(())
+}
+
+fn accept_connections(listener: TcpListener, connections: Connections) {
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let connections = connections.clone();
+        // Sync requests stay open until Rust responds, so each connection gets its own thread
+        std::thread::spawn(move || {
+            if let Err(err) = handle_connection(stream, connections) {
+                eprintln!("Error handling browser connection: {err}");
+            }
+        });
+    }
+}
+
+fn handle_connection(stream: TcpStream, connections: Connections) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let request = read_request(&mut reader)?;
+
+    if request.uri().path() == SOCKET_PATH {
+        return accept_socket(stream, &request, &connections);
+    }
+    // Other sites can't drive the app from a page open in the same browser
+    if connections.is_cross_origin(&request) {
+        return write_response(stream, status_response(StatusCode::FORBIDDEN));
+    }
+
+    let (respond, response) = mpsc::channel();
+    if connections
+        .events
+        .send(BrowserEvent::Request {
+            request: Box::new(request),
+            respond,
+        })
+        .is_err()
+    {
+        return Ok(());
+    }
+    if let Ok(response) = response.recv() {
+        write_response(stream, response)?;
+    }
+    Ok(())
+}
+
+/// Finish the WebSocket handshake and run the socket on this thread until it closes.
+///
+/// Only the first socket from the app's own page is accepted.
+fn accept_socket(
+    mut stream: TcpStream,
+    request: &Request<Vec<u8>>,
+    connections: &Connections,
+) -> io::Result<()> {
+    let Some(key) = request.headers().get("sec-websocket-key") else {
+        return write_response(stream, not_found_response());
+    };
+    // Browsers always send the origin of the page opening a WebSocket
+    let same_origin = request
+        .headers()
+        .get(header::ORIGIN)
+        .is_some_and(|origin| origin.as_bytes() == connections.origin.as_bytes());
+    if !same_origin {
+        return write_response(stream, status_response(StatusCode::FORBIDDEN));
+    }
+    if connections.socket_taken.swap(true, Ordering::SeqCst) {
+        eprintln!("Refusing a WebSocket from a second page, the app already runs in another one");
+        return write_response(stream, status_response(StatusCode::CONFLICT));
+    }
+    let accept = tungstenite::handshake::derive_accept_key(key.as_bytes());
+    write!(
+        stream,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {accept}\r\n\r\n"
+    )?;
+    stream.flush()?;
+
+    let events = &connections.events;
+    let (outgoing, receiver) = unbounded_channel();
+    if events
+        .send(BrowserEvent::SocketConnected(outgoing))
+        .is_err()
+    {
+        return Ok(());
+    }
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_io()
+        .build()?;
+    // The socket ends with an error when the browser drops the connection, which
+    // is as final as a close frame
+    _ = runtime.block_on(run_socket(stream, receiver, events));
+    Ok(())
+}
+
+/// A tokio stream that tungstenite reads and writes without blocking. Reads and
+/// writes fail with `WouldBlock` until the stream is ready again.
+struct NonBlocking(tokio::net::TcpStream);
+
+impl io::Read for NonBlocking {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.try_read(buf)
+    }
+}
+
+impl Write for NonBlocking {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.try_write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Push the event loop's messages to the browser and forward the browser's
+/// messages to the event loop.
+///
+/// Every read and write goes through the one `WebSocket`, so the pongs and close
+/// frames tungstenite answers with while reading can't be written in the middle
+/// of a message from the event loop.
+async fn run_socket(
+    stream: TcpStream,
+    mut outgoing: UnboundedReceiver<Vec<u8>>,
+    events: &Sender<BrowserEvent>,
+) -> Result<(), tungstenite::Error> {
+    stream.set_nonblocking(true)?;
+    let stream = NonBlocking(tokio::net::TcpStream::from_std(stream)?);
+    let mut socket = WebSocket::from_raw_socket(stream, Role::Server, None);
+
+    loop {
+        let next = tokio::select! {
+            message = outgoing.recv() => Ok(Some(message)),
+            ready = socket.get_ref().0.readable() => ready.map(|()| None),
+        };
+        let open = match next? {
+            // The transport was dropped with the app
+            Some(None) => return Ok(()),
+            Some(Some(message)) => {
+                // A message that doesn't fit in the socket yet stays buffered for the flush
+                nonblocking(socket.write(Message::binary(message)))?;
+                true
+            }
+            None => read_messages(&mut socket, events)?,
+        };
+        // Write out the message and any replies tungstenite queued while reading
+        while nonblocking(socket.flush())?.is_none() {
+            socket.get_ref().0.writable().await?;
+        }
+        if !open {
+            return Ok(());
+        }
+    }
+}
+
+/// Forward every message the browser sent so far to the event loop. Returns
+/// false once the socket is closed.
+fn read_messages(
+    socket: &mut WebSocket<NonBlocking>,
+    events: &Sender<BrowserEvent>,
+) -> Result<bool, tungstenite::Error> {
+    while let Some(message) = nonblocking(socket.read())? {
+        match message {
+            Message::Binary(data) => {
+                if events
+                    .send(BrowserEvent::SocketMessage(data.to_vec()))
+                    .is_err()
+                {
+                    return Ok(false);
+                }
+            }
+            Message::Close(_) => return Ok(false),
+            _ => {}
+        }
+    }
+    Ok(true)
+}
+
+/// Turn the `WouldBlock` error of a socket operation that has to wait into `None`.
+fn nonblocking<T>(result: Result<T, tungstenite::Error>) -> Result<Option<T>, tungstenite::Error> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(tungstenite::Error::Io(err)) if err.kind() == io::ErrorKind::WouldBlock => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+fn read_request(reader: &mut impl BufRead) -> io::Result<Request<Vec<u8>>> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(uri)) = (parts.next(), parts.next()) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Malformed HTTP request line",
+        ));
+    };
+    let mut builder = Request::builder().method(method).uri(uri);
+
+    let mut content_length = 0;
+    loop {
+        line.clear();
+        reader.read_line(&mut line)?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            content_length = value.parse().map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidData, "Invalid Content-Length")
+            })?;
+        }
+        builder = builder.header(name, value);
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    builder
+        .body(body)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+fn write_response(mut stream: TcpStream, response: Response<Vec<u8>>) -> io::Result<()> {
+    let status = response.status();
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\n",
+        status.as_u16(),
+        status.canonical_reason().unwrap_or_default()
+    )?;
+    for (name, value) in response.headers() {
+        // The webview loads from a custom protocol and needs this, but over HTTP it
+        // would let any site read the app's responses
+        if *name == header::ACCESS_CONTROL_ALLOW_ORIGIN {
+            continue;
+        }
+        stream.write_all(name.as_str().as_bytes())?;
+        stream.write_all(b": ")?;
+        stream.write_all(value.as_bytes())?;
+        stream.write_all(b"\r\n")?;
+    }
+    write!(
+        stream,
+        "Content-Length: {}\r\nConnection: close\r\n\r\n",
+        response.body().len()
+    )?;
+    stream.write_all(response.body())?;
+    stream.flush()
+}
+
+fn status_response(status: StatusCode) -> Response<Vec<u8>> {
+    Response::builder().status(status).body(Vec::new()).unwrap()
+}
@@ -1,18 +1,33 @@
 pub fn root_response() -> wry::http::Response<Vec<u8>> {
-    let html = r#"<!DOCTYPE html>
+    page_response("")
+}
+
+/// The root page when the app is opened in a browser. Rust pushes messages
+/// over a WebSocket instead of `evaluate_script`.
+pub fn browser_root_response(socket_path: &str) -> wry::http::Response<Vec<u8>> {
+    page_response(&format!(
+        r#"<script>window.connectWebSocket("{socket_path}");</script>"#
+    ))
+}
+
+fn page_response(extra_head: &str) -> wry::http::Response<Vec<u8>> {
+    let html = format!(
+        r#"<!DOCTYPE html>
 <html>
 <head>
     <title>Wry Test</title>
     <script src="/__wbg__/init.js"></script>
+    {extra_head}
 </head>
 <body>
 </body>
-</html>"#;
+</html>"#
+    );
 
     wry::http::Response::builder()
         .header("Content-Type", "text/html")
         .header("access-control-allow-origin", "*")
-        .body(html.as_bytes().to_vec())
+        .body(html.into_bytes())
         .map_err(|e| e.to_string())
         .expect("Failed to build response")
 }
@@ -7,13 +7,15 @@ use tao::dpi::LogicalSize;
 use tao::event_loop::EventLoopBuilder;
 
 use wasm_bindgen::Closure;
+use wasm_bindgen::record::Recorder;
 use wasm_bindgen::wry::WryBindgen;
 
 pub mod bindings;
+mod browser;
 mod home;
 mod webview;
 
-use webview::{WryEvent, run_event_loop};
+use webview::{WindowRequest, WryEvent, current_event_loop, run_event_loop};
 
 // Re-export bindings for convenience
 pub use bindings::set_on_log;
@@ -21,6 +23,7 @@ pub use bindings::set_on_log;
 // Re-export prelude items that apps need
 pub use wasm_bindgen::JsValue;
 pub use wasm_bindgen::prelude::batch;
+pub use wasm_bindgen::runtime::RuntimeHandle;
 
 // Re-export tao and wry for users to configure builders
 pub use tao;
@@ -55,6 +58,10 @@ pub use wry::WebViewBuilder;
 pub struct LaunchBuilder {
     window: WindowBuilder,
     webview: WebViewBuilder<'static>,
+    recording: Option<std::path::PathBuf>,
+    flush_timeout: Option<std::time::Duration>,
+    auto_batch: bool,
+    preload: Vec<String>,
 }
 
 impl Default for LaunchBuilder {
@@ -71,6 +78,10 @@ impl LaunchBuilder {
                 .with_title("wry-launch")
                 .with_inner_size(LogicalSize::new(800.0, 600.0)),
             webview: WebViewBuilder::new().with_devtools(true),
+            recording: None,
+            flush_timeout: None,
+            auto_batch: false,
+            preload: Vec::new(),
         }
     }
 
@@ -112,6 +123,66 @@ impl LaunchBuilder {
         self
     }
 
+    /// Record every IPC message to the file at `path`.
+    ///
+    /// The recording can be printed or replayed with `wasm_bindgen::record`.
+    pub fn record(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.recording = Some(path.into());
+        self
+    }
+
+    /// Fail loudly instead of freezing when the webview goes `timeout` without
+    /// responding to a synchronous call.
+    ///
+    /// The stuck batch is printed and the app thread panics. Use
+    /// `wasm_bindgen::batch::batch_with_timeout` to handle a timeout in the app.
+    pub fn flush_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.flush_timeout = Some(timeout);
+        self
+    }
+
+    /// Batch the JS calls the app makes in each poll of its future, without
+    /// wrapping code in `wasm_bindgen::batch::batch`.
+    ///
+    /// Calls that return JS objects are sent together when the app awaits or
+    /// needs a value back from JS, so existing web-sys code makes fewer round trips.
+    pub fn auto_batch(mut self, auto_batch: bool) -> Self {
+        self.auto_batch = auto_batch;
+        self
+    }
+
+    /// Send the JS functions declared in the Rust `modules`, or their submodules,
+    /// to the webview when it loads, instead of fetching each one the first time
+    /// it is called.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// LaunchBuilder::new().preload(["my_app::startup"])
+    /// ```
+    pub fn preload(mut self, modules: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.preload.extend(modules.into_iter().map(Into::into));
+        self
+    }
+
+    /// Run the functions marked with `#[wasm_bindgen(start)]` with the configured settings.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// #[wasm_bindgen(start)]
+    /// fn start() {
+    ///     // Your app code here
+    /// }
+    ///
+    /// fn main() -> wry::Result<()> {
+    ///     LaunchBuilder::new().auto_batch(true).run_start()
+    /// }
+    /// ```
+    pub fn run_start(self) -> wry::Result<()> {
+        self.run(wasm_bindgen::run_start_functions)
+    }
+
     /// Run the application with the configured settings.
     pub fn run<F, Fut>(self, app: F) -> wry::Result<()>
     where
@@ -119,36 +190,177 @@ impl LaunchBuilder {
         Fut: std::future::Future<Output = ()>,
     {
         let app = || async move {
-            set_on_error(Closure::new(|err: String, stack: String| {
-                println!("[ERROR IN JS CONSOLE] {err}\nStack trace:\n{stack}");
-            }));
-
-            set_on_log(Closure::new(|msg: String| {
-                println!("[JS] {msg}");
-            }));
+            forward_console_to_stdout();
             app().await
         };
 
         let event_loop = EventLoopBuilder::<WryEvent>::with_user_event().build();
         let proxy = event_loop.create_proxy();
 
-        let event_loop_proxy = {
-            let proxy = proxy.clone();
-            move |event| {
-                _ = proxy.send_event(WryEvent::App(event));
-            }
+        let event_loop_proxy = move |event| {
+            _ = proxy.send_event(WryEvent::App(event));
         };
 
-        let wry_bindgen = WryBindgen::new(event_loop_proxy);
+        let wry_bindgen = Self::configure(
+            WryBindgen::new(event_loop_proxy),
+            self.recording,
+            self.flush_timeout,
+            self.auto_batch,
+            self.preload,
+        )?;
 
         run_event_loop(event_loop, wry_bindgen, app, self.window, self.webview);
 
         Ok(())
     }
+
+    /// Run the app against a desktop browser instead of a webview window, with
+    /// the configured settings.
+    ///
+    /// This serves the app on a localhost HTTP server at `addr`. Open the printed
+    /// address in any browser to run the app there, with that browser's devtools.
+    /// The window and webview settings don't apply to the browser.
+    ///
+    /// Only one page can run the app. The server refuses requests from other
+    /// origins and a WebSocket from any page after the first.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// fn main() -> std::io::Result<()> {
+    ///     LaunchBuilder::new()
+    ///         .auto_batch(true)
+    ///         .run_in_browser("127.0.0.1:8080", || async {
+    ///             // Your app code here
+    ///         })
+    /// }
+    /// ```
+    pub fn run_in_browser<F, Fut>(
+        self,
+        addr: impl std::net::ToSocketAddrs,
+        app: F,
+    ) -> std::io::Result<()>
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: std::future::Future<Output = ()>,
+    {
+        let configure = |wry_bindgen| {
+            Self::configure(
+                wry_bindgen,
+                self.recording,
+                self.flush_timeout,
+                self.auto_batch,
+                self.preload,
+            )
+        };
+        browser::run_browser(addr, configure, || async move {
+            forward_console_to_stdout();
+            app().await
+        })
+    }
+
+    /// Apply the recording, timeout, batching and preload settings to `wry_bindgen`.
+    fn configure(
+        mut wry_bindgen: WryBindgen,
+        recording: Option<std::path::PathBuf>,
+        flush_timeout: Option<std::time::Duration>,
+        auto_batch: bool,
+        preload: Vec<String>,
+    ) -> std::io::Result<WryBindgen> {
+        if let Some(path) = recording {
+            wry_bindgen = wry_bindgen.with_recorder(Recorder::create(path)?);
+        }
+        if let Some(timeout) = flush_timeout {
+            wry_bindgen = wry_bindgen.with_flush_timeout(timeout);
+        }
+        Ok(wry_bindgen
+            .with_auto_batch(auto_batch)
+            .with_preload(preload))
+    }
 }
 
 use crate::bindings::set_on_error;
 
+/// Why [`open_window`] couldn't open a window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpenWindowError {
+    /// It was called outside an app thread started by [`LaunchBuilder::run`]
+    NotInApp,
+    /// The event loop already exited
+    EventLoopClosed,
+}
+
+impl std::fmt::Display for OpenWindowError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotInApp => write!(
+                f,
+                "open_window can only be called from the thread of an app started by wry-launch"
+            ),
+            Self::EventLoopClosed => write!(f, "the event loop of the app already exited"),
+        }
+    }
+}
+
+impl std::error::Error for OpenWindowError {}
+
+/// Open another window running `app`, alongside the app's other windows.
+///
+/// The window and webview builders aren't `Send`, so `builders` creates them on
+/// the event loop thread. Each window gets its own runtime on its own thread, so
+/// JS values can't be shared between windows. Closing the window stops only its
+/// app, and the process exits when the last window closes.
+///
+/// The window is opened by the event loop of the app running on this thread.
+/// From a worker thread, call it through `RuntimeHandle::run_blocking`.
+///
+/// # Errors
+///
+/// Fails if called outside an app started with [`LaunchBuilder::run`], or after
+/// its event loop exited.
+///
+/// # Example
+///
+/// ```ignore
+/// wry_launch::open_window(
+///     || {
+///         let window = WindowBuilder::new().with_title("Settings");
+///         (window, WebViewBuilder::new())
+///     },
+///     || async {
+///         // The settings window's app code here
+///     },
+/// )?;
+/// ```
+pub fn open_window<F, Fut>(
+    builders: impl FnOnce() -> (WindowBuilder, WebViewBuilder<'static>) + Send + 'static,
+    app: F,
+) -> Result<(), OpenWindowError>
+where
+    F: FnOnce() -> Fut + Send + 'static,
+    Fut: std::future::Future<Output = ()> + 'static,
+{
+    let app = || async move {
+        forward_console_to_stdout();
+        app().await
+    };
+    let proxy = current_event_loop().ok_or(OpenWindowError::NotInApp)?;
+    proxy
+        .send_event(WryEvent::OpenWindow(WindowRequest::new(builders, app)))
+        .map_err(|_| OpenWindowError::EventLoopClosed)
+}
+
+/// Forward JS errors and console logs to stdout.
+fn forward_console_to_stdout() {
+    set_on_error(Closure::new(|err: String, stack: String| {
+        println!("[ERROR IN JS CONSOLE] {err}\nStack trace:\n{stack}");
+    }));
+
+    set_on_log(Closure::new(|msg: String| {
+        println!("[JS] {msg}");
+    }));
+}
+
 /// Run a webview application with the given app function.
 ///
 /// The app function will be spawned in a separate thread and can use
@@ -178,6 +390,27 @@ where
     LaunchBuilder::new().run(app)
 }
 
+/// Run the functions marked with `#[wasm_bindgen(start)]` in a webview.
+///
+/// This launches crates written for wasm without changing them.
+///
+/// # Example
+///
+/// ```ignore
+/// #[wasm_bindgen(start)]
+/// fn start() {
+///     let document = web_sys::window().unwrap().document().unwrap();
+///     // ... build your UI
+/// }
+///
+/// fn main() -> wry::Result<()> {
+///     wry_launch::run_start()
+/// }
+/// ```
+pub fn run_start() -> wry::Result<()> {
+    LaunchBuilder::new().run_start()
+}
+
 /// Run a headless webview application with the given app function.
 ///
 /// This is identical to `run()` except the window will be invisible.
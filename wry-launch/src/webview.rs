@@ -1,7 +1,11 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::pin::Pin;
+
 use tao::{
     event::{Event, WindowEvent},
-    event_loop::{ControlFlow, EventLoop},
-    window::WindowBuilder,
+    event_loop::{ControlFlow, EventLoop, EventLoopProxy, EventLoopWindowTarget},
+    window::{Window, WindowBuilder},
 };
 use wry::WebViewBuilder;
 
@@ -15,8 +19,50 @@ use crate::home::root_response;
 pub(crate) enum WryEvent {
     /// An event from wry-bindgen runtime
     App(WryBindgenEvent),
-    /// Shutdown the event loop
-    Shutdown,
+    /// Open a new window running its own app
+    OpenWindow(WindowRequest),
+    /// The app of the webview with this id finished
+    AppExited { webview_id: u64, panicked: bool },
+}
+
+type AppFuture = Pin<Box<dyn Future<Output = ()> + 'static>>;
+
+thread_local! {
+    /// The event loop of the app running on this thread, set on each app thread
+    static EVENT_LOOP: RefCell<Option<EventLoopProxy<WryEvent>>> = const { RefCell::new(None) };
+}
+
+/// The event loop of the app running on this thread, if any.
+pub(crate) fn current_event_loop() -> Option<EventLoopProxy<WryEvent>> {
+    EVENT_LOOP.with_borrow(Clone::clone)
+}
+
+/// A window to open on the event loop thread, and the app to run in it.
+pub(crate) struct WindowRequest {
+    /// Builders aren't `Send`, so they are created on the event loop thread
+    builders: Box<dyn FnOnce() -> (WindowBuilder, WebViewBuilder<'static>) + Send>,
+    app: Box<dyn FnOnce() -> AppFuture + Send>,
+}
+
+impl WindowRequest {
+    pub(crate) fn new<F>(
+        builders: impl FnOnce() -> (WindowBuilder, WebViewBuilder<'static>) + Send + 'static,
+        app: impl FnOnce() -> F + Send + 'static,
+    ) -> Self
+    where
+        F: Future<Output = ()> + 'static,
+    {
+        Self {
+            builders: Box::new(builders),
+            app: Box::new(|| Box::pin(app())),
+        }
+    }
+}
+
+impl std::fmt::Debug for WindowRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WindowRequest").finish_non_exhaustive()
+    }
 }
 
 // Each platform has a different custom protocol scheme
@@ -40,15 +86,111 @@ pub(crate) fn run_event_loop<F>(
 ) where
     F: Future<Output = ()> + 'static,
 {
-    let window = window_builder.build(&event_loop).unwrap();
-
     let proxy = event_loop.create_proxy();
-    let proxy_clone = proxy.clone();
+
+    // Windows are routed by the id of the webview inside them
+    let mut windows = HashMap::new();
+    let (webview_id, window) = open_window(
+        &event_loop,
+        &wry_bindgen,
+        &proxy,
+        window_builder,
+        webview_builder,
+        app,
+    );
+    windows.insert(webview_id, window);
+
+    event_loop.run(move |event, target, control_flow| {
+        *control_flow = ControlFlow::Wait;
+
+        match event {
+            Event::WindowEvent {
+                window_id,
+                event: WindowEvent::CloseRequested,
+                ..
+            } => {
+                let closed = windows
+                    .iter()
+                    .find(|(_, window)| window.id() == window_id)
+                    .map(|(webview_id, _)| *webview_id);
+                if let Some(webview_id) = closed {
+                    // Drop the webview before the window it lives in
+                    wry_bindgen.remove_webview(webview_id);
+                    windows.remove(&webview_id);
+                }
+                if windows.is_empty() {
+                    std::process::exit(0);
+                }
+            }
+            Event::UserEvent(wry_event) => match wry_event {
+                WryEvent::OpenWindow(request) => {
+                    let (window_builder, webview_builder) = (request.builders)();
+                    let (webview_id, window) = open_window(
+                        target,
+                        &wry_bindgen,
+                        &proxy,
+                        window_builder,
+                        webview_builder,
+                        request.app,
+                    );
+                    windows.insert(webview_id, window);
+                }
+                WryEvent::AppExited {
+                    webview_id,
+                    panicked,
+                } => {
+                    // The app stopped because its window was already closed
+                    if !windows.contains_key(&webview_id) {
+                        return;
+                    }
+                    // The panic message was already printed, don't leave the window open and frozen
+                    if panicked {
+                        std::process::exit(101);
+                    }
+                    wry_bindgen.remove_webview(webview_id);
+                    windows.remove(&webview_id);
+                    if windows.is_empty() {
+                        *control_flow = ControlFlow::Exit;
+                    }
+                }
+                WryEvent::App(app_event) => {
+                    if let Some(error) = app_event.protocol_error() {
+                        eprintln!("[wry-bindgen] {error}");
+                        std::process::exit(1);
+                    }
+                    if let Some(timeout) = app_event.flush_timeout() {
+                        eprintln!("[wry-bindgen] The webview stopped responding: {timeout}");
+                    }
+                    wry_bindgen.handle_user_event(app_event);
+                }
+            },
+            _ => {}
+        }
+    });
+}
+
+/// Build a window and webview, and start its app on a new thread.
+///
+/// Returns the id of the webview and the window it lives in. The webview
+/// itself is owned by the wry-bindgen transport.
+fn open_window<F>(
+    target: &EventLoopWindowTarget<WryEvent>,
+    wry_bindgen: &WryBindgen,
+    proxy: &EventLoopProxy<WryEvent>,
+    window_builder: WindowBuilder,
+    webview_builder: WebViewBuilder<'static>,
+    app: impl FnOnce() -> F + Send + 'static,
+) -> (u64, Window)
+where
+    F: Future<Output = ()> + 'static,
+{
+    let window = window_builder.build(target).unwrap();
 
     let app_builder = wry_bindgen.app_builder();
     let protocol_handler = app_builder.protocol_handler();
 
     // Add the required protocol handler and URL to the user-provided webview builder
+    let proxy_clone = proxy.clone();
     let builder = webview_builder
         .with_asynchronous_custom_protocol(PROTOCOL_SCHEME.into(), move |_, request, responder| {
             let responder = |response| responder.respond(response);
@@ -84,36 +226,24 @@ pub(crate) fn run_event_loop<F>(
         _ = webview.evaluate_script(script);
     };
     let run_app = app_builder.build(app, evaluate_script);
+    let webview_id = run_app.id();
 
+    let proxy = proxy.clone();
     std::thread::spawn(move || {
-        tokio::runtime::Builder::new_current_thread()
+        EVENT_LOOP.set(Some(proxy.clone()));
+        let runtime = tokio::runtime::Builder::new_current_thread()
             .enable_all()
             .build()
-            .unwrap()
-            .block_on(run_app.into_future());
-        // Signal the event loop to exit after app completes
-        let _ = proxy.send_event(WryEvent::Shutdown);
+            .unwrap();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            runtime.block_on(run_app.into_future())
+        }));
+        // Let the event loop close the window, or exit if the app panicked
+        _ = proxy.send_event(WryEvent::AppExited {
+            webview_id,
+            panicked: result.is_err(),
+        });
     });
 
-    event_loop.run(move |event, _, control_flow| {
-        *control_flow = ControlFlow::Wait;
-
-        match event {
-            Event::WindowEvent {
-                event: WindowEvent::CloseRequested,
-                ..
-            } => {
-                std::process::exit(0);
-            }
-            Event::UserEvent(wry_event) => match wry_event {
-                WryEvent::Shutdown => {
-                    *control_flow = ControlFlow::Exit;
-                }
-                WryEvent::App(app_event) => {
-                    wry_bindgen.handle_user_event(app_event);
-                }
-            },
-            _ => {}
-        }
-    });
+    (webview_id, window)
 }
@@ -70,11 +70,26 @@ async fn async_test_with_js_context<Fut: std::future::Future<Output = ()>, F: Fn
     .await;
 }
 
+/// Run a test that must drop every JS value it creates, and report where the
+/// leaked ones were created.
+async fn test_without_leaks<F: Fn()>(f: F) {
+    async_test_with_js_context_allow_new_js_values(async || {
+        let before = heap_objects_alive();
+        wasm_bindgen::heap::start_leak_check();
+        f();
+        let leaks = wasm_bindgen::heap::finish_leak_check();
+        assert!(leaks.is_empty(), "JS heap object leak detected: {leaks}");
+        let after = heap_objects_alive();
+        assert_eq!(before, after, "JS heap object leak detected");
+    })
+    .await;
+}
+
 fn main() {
     wry_launch::run_headless(|| async {
         // Adding numbers with and without batching
-        test_with_js_context(add_number_js::test_add_number_js).await;
-        test_with_js_context(add_number_js::test_add_number_js_batch).await;
+        test_without_leaks(add_number_js::test_add_number_js).await;
+        test_without_leaks(add_number_js::test_add_number_js_batch).await;
 
         // Roundtrip tests
         test_with_js_context(roundtrip::test_roundtrip).await;